@@ -0,0 +1,532 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::bus::Bus;
+use crate::cpu::{Cpu, Variant};
+use crate::expr::{self, EvalError, ExprParser, Scope, SyntaxError, Token, tokenize};
+use crate::instructions::{AddressingMode, Instruction};
+
+// Where code goes until the first .org, same place Cpu::new starts executing from
+pub const DEFAULT_ORIGIN: u16 = 0x600;
+
+// Two-pass assembler. The first pass assigns addresses to labels, the second one emits bytes.
+// Operands that are known and fit in a byte during the first pass get zero page encodings,
+// forward references always get absolute ones
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Assembler {
+    variant: Variant
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub origin: u16,
+    pub bytes: Vec<u8>
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Program {
+    pub segments: Vec<Segment>,
+    pub labels: HashMap<String, u16>
+}
+
+impl Program {
+    pub fn load_into<B: Bus>(&self, cpu: &mut Cpu<B>) {
+        for segment in &self.segments {
+            cpu.load_at(segment.origin as usize, &segment.bytes);
+        }
+    }
+
+    // Flat image from the lowest to the highest assembled address, gaps are filled with zeroes
+    pub fn to_binary(&self) -> Vec<u8> {
+        let start = self.segments.iter().map(|s| s.origin as usize).min().unwrap_or(0);
+        let end = self.segments.iter().map(|s| s.origin as usize + s.bytes.len()).max().unwrap_or(0);
+        let mut binary = vec![0; end - start];
+        for segment in &self.segments {
+            let offset = segment.origin as usize - start;
+            binary[offset..offset + segment.bytes.len()].copy_from_slice(&segment.bytes);
+        }
+        binary
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AssemblyErrorKind {
+    Syntax(String),
+    UnknownInstruction(String),
+    UnknownDirective(String),
+    InvalidAddressingMode(String), // Instruction exists, but not with this operand
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    ValueOutOfRange(i64),
+    BranchOutOfRange(i64), // Distance from the end of the branch instruction
+    Overflow // An expression that doesn't fit in 64 bits on the way to its value
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssemblyError {
+    pub line: usize, // Starts from 1
+    pub kind: AssemblyErrorKind
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            AssemblyErrorKind::Syntax(message) => write!(f, "{}", message),
+            AssemblyErrorKind::UnknownInstruction(name) => write!(f, "unknown instruction {}", name),
+            AssemblyErrorKind::UnknownDirective(name) => write!(f, "unknown directive {}", name),
+            AssemblyErrorKind::InvalidAddressingMode(name) => write!(f, "invalid addressing mode for {}", name),
+            AssemblyErrorKind::UndefinedLabel(name) => write!(f, "undefined label {}", name),
+            AssemblyErrorKind::DuplicateLabel(name) => write!(f, "label {} is already defined", name),
+            AssemblyErrorKind::ValueOutOfRange(value) => write!(f, "value {} is out of range", value),
+            AssemblyErrorKind::BranchOutOfRange(offset) => write!(f, "branch offset {} is out of range", offset),
+            AssemblyErrorKind::Overflow => write!(f, "arithmetic overflow")
+        }
+    }
+}
+
+impl std::error::Error for AssemblyError {}
+
+type Result<T> = std::result::Result<T, AssemblyErrorKind>;
+
+impl From<SyntaxError> for AssemblyErrorKind {
+    fn from(error: SyntaxError) -> Self {
+        AssemblyErrorKind::Syntax(error.0)
+    }
+}
+
+impl From<EvalError> for AssemblyErrorKind {
+    fn from(error: EvalError) -> Self {
+        match error {
+            EvalError::DivisionByZero => AssemblyErrorKind::Syntax(String::from("division by zero")),
+            EvalError::Overflow => AssemblyErrorKind::Overflow
+        }
+    }
+}
+
+// Names in expressions are labels, looked up when the expression is evaluated
+type Expr = expr::Expr<String>;
+
+#[derive(Clone, Debug)]
+enum Operand {
+    None,
+    Accumulator,
+    Immediate(Expr),
+    Direct(Expr),
+    IndexedX(Expr),
+    IndexedY(Expr),
+    Indirect(Expr),
+    IndirectX(Expr),
+    IndirectY(Expr),
+    ZeroPageRelative(Expr, Expr)
+}
+
+#[derive(Clone, Debug)]
+enum Data {
+    Expr(Expr),
+    Str(String)
+}
+
+#[derive(Clone, Debug)]
+enum Statement {
+    Label(String),
+    Constant(String, Expr),
+    Org(Expr),
+    Byte(Vec<Data>),
+    Word(Vec<Expr>),
+    Instruction { mnemonic: String, operand: Operand }
+}
+
+fn parse_expr(tokens: &[Token]) -> Result<Expr> {
+    ExprParser::parse(tokens, false, |name| Ok(name.to_string()))
+}
+
+// Labels and the address of the current statement
+struct Labels<'a> {
+    labels: &'a HashMap<String, u16>,
+    pc: u16
+}
+
+impl Scope<String> for Labels<'_> {
+    type Error = AssemblyErrorKind;
+
+    fn ident(&self, name: &String) -> Result<i64> {
+        match self.labels.get(name) {
+            Some(value) => Ok(*value as i64),
+            None => Err(AssemblyErrorKind::UndefinedLabel(name.clone()))
+        }
+    }
+
+    fn pc(&self) -> i64 {
+        self.pc as i64
+    }
+
+    // parse_expr doesn't allow [addr]
+    fn memory(&self, _addr: u16) -> i64 {
+        unreachable!()
+    }
+}
+
+impl Expr {
+    fn eval(&self, labels: &HashMap<String, u16>, pc: u16) -> Result<i64> {
+        self.evaluate(&Labels { labels, pc })
+    }
+}
+
+fn byte_value(value: i64) -> Result<u8> {
+    if (-0x80..=0xff).contains(&value) {
+        Ok(value as u8)
+    } else {
+        Err(AssemblyErrorKind::ValueOutOfRange(value))
+    }
+}
+
+fn word_value(value: i64) -> Result<u16> {
+    if (-0x8000..=0xffff).contains(&value) {
+        Ok(value as u16)
+    } else {
+        Err(AssemblyErrorKind::ValueOutOfRange(value))
+    }
+}
+
+fn define(labels: &mut HashMap<String, u16>, name: &str, value: u16) -> Result<()> {
+    match labels.insert(name.to_string(), value) {
+        Some(_) => Err(AssemblyErrorKind::DuplicateLabel(name.to_string())),
+        None => Ok(())
+    }
+}
+
+fn is_register(token: &Token, name: &str) -> bool {
+    matches!(token, Token::Ident(ident) if ident.eq_ignore_ascii_case(name))
+}
+
+// Splits on commas that aren't inside parentheses
+fn split_commas(tokens: &[Token]) -> Vec<&[Token]> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Op("(") => { depth += 1; }
+            Token::Op(")") => { depth -= 1; }
+            Token::Op(",") if depth == 0 => {
+                parts.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&tokens[start..]);
+    parts
+}
+
+fn parse_operand(tokens: &[Token]) -> Result<Operand> {
+    match tokens {
+        [] => { return Ok(Operand::None); }
+        [register] if is_register(register, "A") => { return Ok(Operand::Accumulator); }
+        [Token::Op("#"), rest @ ..] => { return Ok(Operand::Immediate(parse_expr(rest)?)); }
+        _ => {}
+    }
+
+    // An operand starting with a parenthesis is only indirect when the parentheses wrap all of it,
+    // so (label+1)*2 still parses as an expression
+    if tokens[0] == Token::Op("(") {
+        let mut depth = 0;
+        let close = tokens.iter().position(|token| {
+            match token {
+                Token::Op("(") => { depth += 1; }
+                Token::Op(")") => { depth -= 1; }
+                _ => {}
+            }
+            depth == 0
+        });
+
+        if let Some(close) = close {
+            let inner = &tokens[1..close];
+            let inner_parts = split_commas(inner);
+            match &tokens[close + 1..] {
+                [] => match inner_parts.as_slice() {
+                    [expr] => { return Ok(Operand::Indirect(parse_expr(expr)?)); }
+                    [expr, [register]] if is_register(register, "X") => {
+                        return Ok(Operand::IndirectX(parse_expr(expr)?));
+                    }
+                    _ => { return Err(AssemblyErrorKind::Syntax(String::from("invalid indirect operand"))); }
+                },
+                [Token::Op(","), register] if is_register(register, "Y") && inner_parts.len() == 1 => {
+                    return Ok(Operand::IndirectY(parse_expr(inner)?));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    match split_commas(tokens).as_slice() {
+        [expr] => Ok(Operand::Direct(parse_expr(expr)?)),
+        [expr, [register]] if is_register(register, "X") => Ok(Operand::IndexedX(parse_expr(expr)?)),
+        [expr, [register]] if is_register(register, "Y") => Ok(Operand::IndexedY(parse_expr(expr)?)),
+        [addr, target] => Ok(Operand::ZeroPageRelative(parse_expr(addr)?, parse_expr(target)?)),
+        _ => Err(AssemblyErrorKind::Syntax(String::from("too many operands")))
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => { in_string = !in_string; }
+            // A character literal is always three characters long, so lda #';' keeps its operand
+            '\'' if !in_string => { chars.nth(1); }
+            ';' if !in_string => { return &line[..i]; }
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_line(line: &str) -> Result<Vec<Statement>> {
+    let tokens = tokenize(strip_comment(line))?;
+    let mut statements = vec![];
+    let mut rest = tokens.as_slice();
+
+    if let [Token::Ident(name), Token::Op(":"), tail @ ..] = rest {
+        statements.push(Statement::Label(name.clone()));
+        rest = tail;
+    }
+
+    match rest {
+        [] => {}
+        [Token::Ident(name), Token::Op("="), expr @ ..] => {
+            statements.push(Statement::Constant(name.clone(), parse_expr(expr)?));
+        }
+        [Token::Ident(directive), args @ ..] if directive.starts_with('.') => {
+            let statement = match directive.to_ascii_lowercase().as_str() {
+                ".org" => Statement::Org(parse_expr(args)?),
+                ".byte" => Statement::Byte(split_commas(args).into_iter().map(|arg| match arg {
+                    [Token::Str(s)] => Ok(Data::Str(s.clone())),
+                    _ => parse_expr(arg).map(Data::Expr)
+                }).collect::<Result<_>>()?),
+                ".word" => Statement::Word(split_commas(args).into_iter().map(parse_expr).collect::<Result<_>>()?),
+                _ => { return Err(AssemblyErrorKind::UnknownDirective(directive.clone())); }
+            };
+            statements.push(statement);
+        }
+        [Token::Ident(mnemonic), operand @ ..] => {
+            statements.push(Statement::Instruction { mnemonic: mnemonic.to_ascii_uppercase(), operand: parse_operand(operand)? });
+        }
+        [token, ..] => { return Err(AssemblyErrorKind::Syntax(format!("unexpected {:?}", token))); }
+    }
+
+    Ok(statements)
+}
+
+// Instruction that has already been sized by the first pass
+#[derive(Copy, Clone, Debug)]
+struct Encoding {
+    opcode: u8,
+    mode: AddressingMode
+}
+
+impl Assembler {
+    pub fn new(variant: Variant) -> Assembler {
+        Assembler { variant }
+    }
+
+    // Finds the opcode for a mnemonic in a given mode. Documented opcodes win over undocumented duplicates
+    fn opcode(&self, mnemonic: &str, mode: AddressingMode) -> Option<u8> {
+        // RMB0-RMB7 and friends: the bit number lives in bits 4-6 of the opcode
+        let (name, bit) = match mnemonic.len() {
+            4 if ["RMB", "SMB", "BBR", "BBS"].contains(&&mnemonic[..3]) => {
+                match mnemonic[3..].parse::<u8>() {
+                    Ok(bit) if bit < 8 => (&mnemonic[..3], Some(bit)),
+                    _ => { return None; }
+                }
+            }
+            _ => (mnemonic, None)
+        };
+
+        let matches = |opcode: &u8| {
+            let (instruction, m, _) = self.variant.opcodes()[*opcode as usize];
+            instruction != Instruction::None && m == mode && format!("{:?}", instruction) == name
+                && bit.is_none_or(|bit| (opcode >> 4) & 0b111 == bit)
+        };
+        let candidates: Vec<u8> = (0..=0xff).filter(matches).collect();
+        candidates.iter().find(|opcode| !self.variant.is_undocumented(**opcode)).or(candidates.first()).copied()
+    }
+
+    fn is_mnemonic(&self, mnemonic: &str) -> bool {
+        ALL_MODES.iter().any(|mode| self.opcode(mnemonic, *mode).is_some())
+    }
+
+    // Picks an encoding for an instruction. `value` is the operand if it's already known in the first pass
+    fn encode(&self, mnemonic: &str, operand: &Operand, value: Option<i64>) -> Result<Encoding> {
+        let fits_zero_page = value.is_some_and(|value| (0..=0xff).contains(&value));
+        let candidates: &[AddressingMode] = match operand {
+            Operand::None => &[AddressingMode::Implied, AddressingMode::Accumulator],
+            Operand::Accumulator => &[AddressingMode::Accumulator],
+            Operand::Immediate(_) => &[AddressingMode::Immediate],
+            Operand::Direct(_) if fits_zero_page =>
+                &[AddressingMode::Relative, AddressingMode::ZeroPage, AddressingMode::Absolute],
+            Operand::Direct(_) => &[AddressingMode::Relative, AddressingMode::Absolute, AddressingMode::ZeroPage],
+            Operand::IndexedX(_) if fits_zero_page => &[AddressingMode::ZeroPageX, AddressingMode::AbsoluteX],
+            Operand::IndexedX(_) => &[AddressingMode::AbsoluteX, AddressingMode::ZeroPageX],
+            Operand::IndexedY(_) if fits_zero_page => &[AddressingMode::ZeroPageY, AddressingMode::AbsoluteY],
+            Operand::IndexedY(_) => &[AddressingMode::AbsoluteY, AddressingMode::ZeroPageY],
+            Operand::Indirect(_) => &[AddressingMode::Indirect, AddressingMode::ZeroPageIndirect],
+            Operand::IndirectX(_) => &[AddressingMode::IndirectX, AddressingMode::AbsoluteIndirectX],
+            Operand::IndirectY(_) => &[AddressingMode::IndirectY],
+            Operand::ZeroPageRelative(_, _) => &[AddressingMode::ZeroPageRelative]
+        };
+
+        for mode in candidates {
+            if let Some(opcode) = self.opcode(mnemonic, *mode) {
+                return Ok(Encoding { opcode, mode: *mode });
+            }
+        }
+
+        if self.is_mnemonic(mnemonic) {
+            Err(AssemblyErrorKind::InvalidAddressingMode(mnemonic.to_string()))
+        } else {
+            Err(AssemblyErrorKind::UnknownInstruction(mnemonic.to_string()))
+        }
+    }
+
+    fn emit(encoding: Encoding, operand: &Operand, labels: &HashMap<String, u16>, pc: u16, out: &mut Vec<u8>) -> Result<()> {
+        out.push(encoding.opcode);
+        let expr = match operand {
+            Operand::None | Operand::Accumulator => { return Ok(()); }
+            Operand::ZeroPageRelative(addr, target) => {
+                out.push(byte_value(addr.eval(labels, pc)?)?);
+                let offset = target.eval(labels, pc)? - (pc as i64 + 3);
+                if !(-0x80..=0x7f).contains(&offset) {
+                    return Err(AssemblyErrorKind::BranchOutOfRange(offset));
+                }
+                out.push(offset as u8);
+                return Ok(());
+            }
+            Operand::Immediate(expr) | Operand::Direct(expr) | Operand::IndexedX(expr) | Operand::IndexedY(expr)
+            | Operand::Indirect(expr) | Operand::IndirectX(expr) | Operand::IndirectY(expr) => expr
+        };
+
+        let value = expr.eval(labels, pc)?;
+        match encoding.mode {
+            AddressingMode::Relative => {
+                let offset = value - (pc as i64 + 2);
+                if !(-0x80..=0x7f).contains(&offset) {
+                    return Err(AssemblyErrorKind::BranchOutOfRange(offset));
+                }
+                out.push(offset as u8);
+            }
+            AddressingMode::Immediate => { out.push(byte_value(value)?); }
+            mode if mode.operand_bytes() == 1 => {
+                if !(0..=0xff).contains(&value) {
+                    return Err(AssemblyErrorKind::ValueOutOfRange(value));
+                }
+                out.push(value as u8);
+            }
+            _ => { out.extend_from_slice(&word_value(value)?.to_le_bytes()); }
+        }
+        Ok(())
+    }
+
+    pub fn assemble(&self, source: &str) -> std::result::Result<Program, AssemblyError> {
+        let mut statements = vec![];
+        for (i, line) in source.lines().enumerate() {
+            let parsed = parse_line(line).map_err(|kind| AssemblyError { line: i + 1, kind })?;
+            statements.extend(parsed.into_iter().map(|statement| (i + 1, statement)));
+        }
+
+        // First pass: figure out the size of everything and where the labels end up
+        let mut labels = HashMap::new();
+        let mut encodings = vec![];
+        let mut pc = DEFAULT_ORIGIN;
+        for (line, statement) in &statements {
+            let error = |kind| AssemblyError { line: *line, kind };
+
+            match statement {
+                Statement::Label(name) => { define(&mut labels, name, pc).map_err(error)?; }
+                Statement::Constant(name, expr) => {
+                    let value = expr.eval(&labels, pc).and_then(word_value).map_err(error)?;
+                    define(&mut labels, name, value).map_err(error)?;
+                }
+                Statement::Org(expr) => {
+                    pc = expr.eval(&labels, pc).and_then(word_value).map_err(error)?;
+                }
+                Statement::Byte(data) => {
+                    let len: usize = data.iter().map(|d| match d { Data::Str(s) => s.len(), Data::Expr(_) => 1 }).sum();
+                    pc = pc.wrapping_add(len as u16);
+                }
+                Statement::Word(exprs) => { pc = pc.wrapping_add(2 * exprs.len() as u16); }
+                Statement::Instruction { mnemonic, operand } => {
+                    let value = match operand {
+                        Operand::Direct(expr) | Operand::IndexedX(expr) | Operand::IndexedY(expr) => expr.eval(&labels, pc).ok(),
+                        _ => None
+                    };
+                    let encoding = self.encode(mnemonic, operand, value).map_err(error)?;
+                    encodings.push(encoding);
+                    pc = pc.wrapping_add(1 + encoding.mode.operand_bytes());
+                }
+            }
+        }
+
+        // Second pass: every label is known now, emit the bytes
+        let mut program = Program { segments: vec![Segment { origin: DEFAULT_ORIGIN, bytes: vec![] }], labels: HashMap::new() };
+        let mut encodings = encodings.into_iter();
+        pc = DEFAULT_ORIGIN;
+        for (line, statement) in &statements {
+            let error = |kind| AssemblyError { line: *line, kind };
+            let segment = program.segments.last_mut().unwrap();
+            let start = segment.bytes.len();
+
+            match statement {
+                Statement::Label(_) | Statement::Constant(_, _) => {}
+                Statement::Org(expr) => {
+                    pc = expr.eval(&labels, pc).and_then(word_value).map_err(error)?;
+                    if start == 0 {
+                        segment.origin = pc;
+                    } else {
+                        program.segments.push(Segment { origin: pc, bytes: vec![] });
+                    }
+                    continue;
+                }
+                Statement::Byte(data) => {
+                    for d in data {
+                        match d {
+                            Data::Str(s) => segment.bytes.extend_from_slice(s.as_bytes()),
+                            Data::Expr(expr) => segment.bytes.push(expr.eval(&labels, pc).and_then(byte_value).map_err(error)?)
+                        }
+                    }
+                }
+                Statement::Word(exprs) => {
+                    for expr in exprs {
+                        segment.bytes.extend_from_slice(&expr.eval(&labels, pc).and_then(word_value).map_err(error)?.to_le_bytes());
+                    }
+                }
+                Statement::Instruction { operand, .. } => {
+                    let encoding = encodings.next().unwrap();
+                    Self::emit(encoding, operand, &labels, pc, &mut segment.bytes).map_err(error)?;
+                }
+            }
+
+            pc = pc.wrapping_add((segment.bytes.len() - start) as u16);
+        }
+
+        program.segments.retain(|segment| !segment.bytes.is_empty());
+        program.labels = labels;
+        Ok(program)
+    }
+}
+
+const ALL_MODES: [AddressingMode; 16] = [
+    AddressingMode::Implied, AddressingMode::Accumulator, AddressingMode::Immediate, AddressingMode::ZeroPage,
+    AddressingMode::ZeroPageX, AddressingMode::ZeroPageY, AddressingMode::Relative, AddressingMode::Absolute,
+    AddressingMode::AbsoluteX, AddressingMode::AbsoluteY, AddressingMode::Indirect, AddressingMode::IndirectX,
+    AddressingMode::IndirectY, AddressingMode::ZeroPageIndirect, AddressingMode::AbsoluteIndirectX,
+    AddressingMode::ZeroPageRelative
+];
+
+// Assembles NMOS 6502 source
+pub fn assemble(source: &str) -> std::result::Result<Program, AssemblyError> {
+    Assembler::new(Variant::NMOS).assemble(source)
+}
+
+mod test;
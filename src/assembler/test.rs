@@ -0,0 +1,175 @@
+#![allow(clippy::module_inception)]
+
+#[cfg(test)]
+mod test {
+    use super::super::*;
+
+    fn bytes(source: &str) -> Vec<u8> {
+        assemble(source).unwrap().to_binary()
+    }
+
+    fn error(source: &str) -> AssemblyError {
+        assemble(source).unwrap_err()
+    }
+
+    #[test]
+    fn test_addressing_modes() {
+        let source = "
+            clc
+            asl
+            asl a
+            lda #$42
+            lda $20
+            lda $20,x
+            ldx $20,y
+            lda $1234
+            lda $1234,x
+            lda $20,y      ; no zero page,Y for LDA
+            jmp ($1234)
+            lda ($20,x)
+            lda ($20),y
+        ";
+        assert_eq!(bytes(source), [
+            0x18, 0x0a, 0x0a, 0xa9, 0x42, 0xa5, 0x20, 0xb5, 0x20, 0xb6, 0x20, 0xad, 0x34, 0x12,
+            0xbd, 0x34, 0x12, 0xb9, 0x20, 0x00, 0x6c, 0x34, 0x12, 0xa1, 0x20, 0xb1, 0x20
+        ]);
+    }
+
+    #[test]
+    fn test_labels_and_branches() {
+        let program = assemble("
+            ldx #$08
+        loop:
+            dex
+            bne loop
+            beq end
+            nop
+        end: brk
+        ").unwrap();
+        assert_eq!(program.labels["loop"], 0x602);
+        assert_eq!(program.labels["end"], 0x608);
+        assert_eq!(program.to_binary(), [0xa2, 0x08, 0xca, 0xd0, 0xfd, 0xf0, 0x01, 0xea, 0x00]);
+    }
+
+    #[test]
+    fn test_zero_page_selection() {
+        // Known values that fit in a byte get zero page, forward references get absolute
+        let source = "
+        ptr = $10
+            lda ptr
+            sta ptr+1,x
+            lda later
+            lda $0010
+        later = $20
+        ";
+        assert_eq!(bytes(source), [0xa5, 0x10, 0x95, 0x11, 0xad, 0x20, 0x00, 0xa5, 0x10]);
+    }
+
+    #[test]
+    fn test_expressions() {
+        let source = "
+        table = $1234
+            lda #<table
+            ldx #>table
+            lda #(2+3)*4
+            lda #%1010 | 'A'
+            lda #';' ; comment
+            lda #-1
+            lda #1 << 4 + 1
+            jmp *
+        ";
+        assert_eq!(bytes(source), [
+            0xa9, 0x34, 0xa2, 0x12, 0xa9, 0x14, 0xa9, 0x4b, 0xa9, 0x3b, 0xa9, 0xff, 0xa9, 0x20, 0x4c, 0x0e, 0x06
+        ]);
+        assert_eq!(bytes("lda #0x10"), bytes("lda #$10"));
+    }
+
+    #[test]
+    fn test_directives() {
+        let program = assemble("
+            .org $8000
+        reset:
+            .byte 1, $02, \"ab\"
+            .word reset, $1234
+            .org $fffc
+            .word reset
+        ").unwrap();
+        assert_eq!(program.segments, vec![
+            Segment { origin: 0x8000, bytes: vec![0x01, 0x02, 0x61, 0x62, 0x00, 0x80, 0x34, 0x12] },
+            Segment { origin: 0xfffc, bytes: vec![0x00, 0x80] }
+        ]);
+        assert_eq!(program.to_binary().len(), 0x7ffe);
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            ("lda #1\nfoo #1", AssemblyErrorKind::UnknownInstruction(String::from("FOO"))),
+            ("\n\njmp ($12),y", AssemblyErrorKind::InvalidAddressingMode(String::from("JMP"))),
+            ("jmp nowhere", AssemblyErrorKind::UndefinedLabel(String::from("nowhere"))),
+            ("a1: nop\na1: nop", AssemblyErrorKind::DuplicateLabel(String::from("a1"))),
+            (".fill 3", AssemblyErrorKind::UnknownDirective(String::from(".fill"))),
+            ("lda #256", AssemblyErrorKind::ValueOutOfRange(256)),
+            ("bne * + 200", AssemblyErrorKind::BranchOutOfRange(198)),
+            ("lda (1", AssemblyErrorKind::Syntax(String::from("expected )"))),
+            ("lda #1 / 0", AssemblyErrorKind::Syntax(String::from("division by zero"))),
+            ("lda #($7fffffffffffffff + 1) / -1", AssemblyErrorKind::Overflow),
+            ("lda #-($7fffffffffffffff + 1)", AssemblyErrorKind::Overflow)
+        ];
+        let lines = [2, 3, 1, 2, 1, 1, 1, 1, 1, 1, 1];
+
+        for ((source, kind), line) in cases.into_iter().zip(lines) {
+            assert_eq!(error(source), AssemblyError { line, kind });
+        }
+        assert_eq!(error("\nlda #256").to_string(), "line 2: value 256 is out of range");
+    }
+
+    #[test]
+    fn test_65c02() {
+        let program = Assembler::new(Variant::CMOS).assemble("
+            lda ($20)
+            jmp ($1234,x)
+            inc
+            stz $10
+            bra skip
+            rmb3 $20
+        skip:
+            bbs7 $20,skip
+        ").unwrap();
+        assert_eq!(program.to_binary(), [
+            0xb2, 0x20, 0x7c, 0x34, 0x12, 0x1a, 0x64, 0x10, 0x80, 0x02, 0x37, 0x20, 0xff, 0x20, 0xfd
+        ]);
+
+        assert_eq!(error("stz $10").kind, AssemblyErrorKind::UnknownInstruction(String::from("STZ")));
+    }
+
+    #[test]
+    fn test_undocumented() {
+        // Documented encodings win over undocumented duplicates
+        assert_eq!(bytes("lax $10\nsbc #$01\nnop"), [0xa7, 0x10, 0xe9, 0x01, 0xea]);
+    }
+
+    #[test]
+    fn test_run_assembled_program() {
+        let program = assemble("
+            ldx #$00
+        loop:
+            lda message,x
+            beq done
+            sta $0200,x
+            inx
+            bne loop
+        done:
+            brk
+        message:
+            .byte \"hi\", 0
+        ").unwrap();
+
+        let mut cpu = Cpu::new();
+        program.load_into(&mut cpu);
+        cpu.run_until_brk();
+        assert_eq!(cpu.get_byte(0x200), b'h');
+        assert_eq!(cpu.get_byte(0x201), b'i');
+        assert_eq!(cpu.pc(), program.labels["done"]);
+    }
+}
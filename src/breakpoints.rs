@@ -0,0 +1,298 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use crate::bus::Bus;
+use crate::cpu::{Cpu, Flags};
+use crate::expr::{EvalError, Expr, ExprParser, Scope, SyntaxError, tokenize};
+
+pub type BreakpointId = usize;
+
+// Kind of memory access a watchpoint reacts to. Hits are always reported as Read or Write
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite
+}
+
+impl Access {
+    fn matches(self, access: Access) -> bool {
+        self == Access::ReadWrite || self == access
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BreakpointKind {
+    Execute(u16), // Stops before the instruction at this address runs
+    Watch(RangeInclusive<u16>, Access) // Stops after an instruction touched the range
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub id: BreakpointId,
+    pub kind: BreakpointKind,
+    pub condition: Option<Condition>, // Only stops when this holds
+    pub enabled: bool,
+    pub hits: u64 // How many times it stopped a run
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.id)?;
+        match &self.kind {
+            BreakpointKind::Execute(addr) => write!(f, "break ${:04X}", addr)?,
+            BreakpointKind::Watch(range, access) => {
+                let access = match access {
+                    Access::Read => "r",
+                    Access::Write => "w",
+                    Access::ReadWrite => "rw"
+                };
+                write!(f, "watch {} ${:04X}-${:04X}", access, range.start(), range.end())?;
+            }
+        }
+        if let Some(condition) = &self.condition {
+            write!(f, " if {}", condition)?;
+        }
+        if !self.enabled {
+            write!(f, " (disabled)")?;
+        }
+        write!(f, ", {} hits", self.hits)
+    }
+}
+
+// Memory access that matched a watchpoint, waiting for the instruction to finish so its condition can be checked
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct WatchHit {
+    pub id: BreakpointId,
+    pub access: Access,
+    pub addr: u16,
+    pub value: u8
+}
+
+// Execution breakpoints and watchpoints of a CPU. Ids are never reused
+#[derive(Clone, Debug)]
+pub struct Breakpoints {
+    list: Vec<Breakpoint>,
+    next_id: BreakpointId,
+    watching: bool, // Any enabled watchpoints, checked on every memory access
+    hits: Vec<WatchHit>
+}
+
+impl Breakpoints {
+    pub fn new() -> Breakpoints {
+        Breakpoints { list: vec![], next_id: 1, watching: false, hits: vec![] }
+    }
+
+    pub fn add(&mut self, kind: BreakpointKind, condition: Option<Condition>) -> BreakpointId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.list.push(Breakpoint { id, kind, condition, enabled: true, hits: 0 });
+        self.update_watching();
+        id
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16) -> BreakpointId {
+        self.add(BreakpointKind::Execute(addr), None)
+    }
+
+    pub fn add_watchpoint(&mut self, range: RangeInclusive<u16>, access: Access) -> BreakpointId {
+        self.add(BreakpointKind::Watch(range, access), None)
+    }
+
+    pub fn remove(&mut self, id: BreakpointId) -> bool {
+        let len = self.list.len();
+        self.list.retain(|breakpoint| breakpoint.id != id);
+        self.update_watching();
+        self.list.len() != len
+    }
+
+    pub fn clear(&mut self) {
+        self.list.clear();
+        self.update_watching();
+    }
+
+    // Returns false if there's no such breakpoint
+    pub fn set_enabled(&mut self, id: BreakpointId, enabled: bool) -> bool {
+        let found = match self.list.iter_mut().find(|breakpoint| breakpoint.id == id) {
+            Some(breakpoint) => {
+                breakpoint.enabled = enabled;
+                true
+            }
+            None => false
+        };
+        self.update_watching();
+        found
+    }
+
+    pub fn set_condition(&mut self, id: BreakpointId, condition: Option<Condition>) -> bool {
+        match self.list.iter_mut().find(|breakpoint| breakpoint.id == id) {
+            Some(breakpoint) => {
+                breakpoint.condition = condition;
+                true
+            }
+            None => false
+        }
+    }
+
+    pub fn get(&self, id: BreakpointId) -> Option<&Breakpoint> {
+        self.list.iter().find(|breakpoint| breakpoint.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.list.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    // Enabled execution breakpoint at `addr`, if any
+    pub fn has_breakpoint_at(&self, addr: u16) -> bool {
+        self.list.iter().any(|breakpoint| breakpoint.enabled && breakpoint.kind == BreakpointKind::Execute(addr))
+    }
+
+    fn update_watching(&mut self) {
+        self.watching = self.list.iter()
+            .any(|breakpoint| breakpoint.enabled && matches!(breakpoint.kind, BreakpointKind::Watch(..)));
+    }
+
+    // Called from the CPU's memory accesses
+    pub(crate) fn record(&mut self, addr: u16, value: u8, access: Access) {
+        if !self.watching {
+            return;
+        }
+        for breakpoint in &self.list {
+            if let BreakpointKind::Watch(range, watched) = &breakpoint.kind {
+                if breakpoint.enabled && watched.matches(access) && range.contains(&addr) {
+                    self.hits.push(WatchHit { id: breakpoint.id, access, addr, value });
+                }
+            }
+        }
+    }
+
+    pub(crate) fn clear_hits(&mut self) {
+        self.hits.clear();
+    }
+
+    pub(crate) fn take_hits(&mut self) -> Vec<WatchHit> {
+        std::mem::take(&mut self.hits)
+    }
+
+    pub(crate) fn count_hit(&mut self, id: BreakpointId) {
+        if let Some(breakpoint) = self.list.iter_mut().find(|breakpoint| breakpoint.id == id) {
+            breakpoint.hits += 1;
+        }
+    }
+}
+
+impl Default for Breakpoints {
+    fn default() -> Self {
+        Breakpoints::new()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConditionError {
+    Syntax(String),
+    UnknownName(String)
+}
+
+impl fmt::Display for ConditionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConditionError::Syntax(message) => write!(f, "{}", message),
+            ConditionError::UnknownName(name) => write!(f, "unknown register or flag '{}'", name)
+        }
+    }
+}
+
+impl std::error::Error for ConditionError {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Register {
+    A,
+    X,
+    Y,
+    SP,
+    PC,
+    P,
+    Flag(Flags)
+}
+
+// Boolean expression over the registers and memory, like `A == $10 && [$00FE] > 3`.
+// Numbers and operators are the assembler's, registers and flags go by name and `*` is PC. Anything non-zero is true
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Condition {
+    source: String,
+    expr: Expr<Register>
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Condition {
+    pub fn parse(source: &str) -> Result<Condition, ConditionError> {
+        let tokens = tokenize(source)?;
+        let expr = ExprParser::parse(&tokens, true, register)?;
+        Ok(Condition { source: source.trim().to_string(), expr })
+    }
+
+    // Memory is only peeked at, so checking a condition has no side effects.
+    // Dividing by zero or overflowing makes it false
+    pub fn evaluate<B: Bus>(&self, cpu: &Cpu<B>) -> bool {
+        self.expr.evaluate(cpu).is_ok_and(|value| value != 0)
+    }
+}
+
+impl From<SyntaxError> for ConditionError {
+    fn from(error: SyntaxError) -> Self {
+        ConditionError::Syntax(error.0)
+    }
+}
+
+fn register(name: &str) -> Result<Register, ConditionError> {
+    Ok(match name.to_ascii_uppercase().as_str() {
+        "A" => Register::A,
+        "X" => Register::X,
+        "Y" => Register::Y,
+        "SP" | "S" => Register::SP,
+        "PC" => Register::PC,
+        "P" => Register::P,
+        "C" => Register::Flag(Flags::C),
+        "Z" => Register::Flag(Flags::Z),
+        "I" => Register::Flag(Flags::I),
+        "D" => Register::Flag(Flags::D),
+        "B" => Register::Flag(Flags::B),
+        "V" => Register::Flag(Flags::V),
+        "N" => Register::Flag(Flags::N),
+        _ => { return Err(ConditionError::UnknownName(name.to_string())); }
+    })
+}
+
+impl<B: Bus> Scope<Register> for Cpu<B> {
+    type Error = EvalError;
+
+    fn ident(&self, register: &Register) -> Result<i64, EvalError> {
+        Ok(match register {
+            Register::A => self.a() as i64,
+            Register::X => self.x() as i64,
+            Register::Y => self.y() as i64,
+            Register::SP => self.sp() as i64,
+            Register::PC => self.pc() as i64,
+            Register::P => self.status().bits() as i64,
+            Register::Flag(flag) => self.get_flag(*flag) as i64
+        })
+    }
+
+    fn pc(&self) -> i64 {
+        self.pc() as i64
+    }
+
+    fn memory(&self, addr: u16) -> i64 {
+        self.bus().peek(addr) as i64
+    }
+}
+
+mod test;
@@ -0,0 +1,114 @@
+#![allow(clippy::module_inception)]
+
+#[cfg(test)]
+mod test {
+    use super::super::*;
+    use crate::cpu::{Cpu, StopReason};
+
+    fn check(cpu: &Cpu, source: &str) -> bool {
+        Condition::parse(source).unwrap().evaluate(cpu)
+    }
+
+    #[test]
+    fn test_condition() {
+        let mut cpu = Cpu::new();
+        cpu.set_a(0x10);
+        cpu.set_x(2);
+        cpu.set_flag(Flags::C, true);
+        cpu.set_byte(0x00fe, 4);
+
+        assert!(check(&cpu, "A == $10 && [$00FE] > 3"));
+        assert!(!check(&cpu, "A == $10 && [$00FE] > 4"));
+        assert!(check(&cpu, "a != 0x11 || x == 0"));
+        assert!(check(&cpu, "[$f0 + 14] == %100"));
+        assert!(check(&cpu, "C && !Z"));
+        assert!(check(&cpu, "P & 1"));
+        // Same precedence as in C, == binds tighter than |
+        assert!(check(&cpu, "X | 1 == 1"));
+        assert!(!check(&cpu, "[$00FE] / 0"));
+        assert!(check(&cpu, "1 + 2 == 3 && (X - 3 < 0)"));
+        assert!(check(&cpu, "pc == $600 && sp == 255"));
+        assert!(!check(&cpu, "[pc]"));
+
+        assert_eq!(Condition::parse("A == $10").unwrap().to_string(), "A == $10");
+        assert_eq!(Condition::parse("Q == 1"), Err(ConditionError::UnknownName(String::from("Q"))));
+        assert_eq!(Condition::parse("A =="), Err(ConditionError::Syntax(String::from("unexpected end of expression"))));
+        assert_eq!(Condition::parse("[$10"), Err(ConditionError::Syntax(String::from("expected ]"))));
+        assert_eq!(Condition::parse(""), Err(ConditionError::Syntax(String::from("expected an expression"))));
+        assert_eq!(Condition::parse("A = 1"), Err(ConditionError::Syntax(String::from("unexpected Op(\"=\") in expression"))));
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let mut cpu = Cpu::new();
+        //     ldx #$00
+        // loop:
+        //     inx
+        //     jmp loop
+        cpu.load_at(0x600, &[0xa2, 0x00, 0xe8, 0x4c, 0x02, 0x06]);
+        let id = cpu.breakpoints_mut().add_breakpoint(0x602);
+
+        let summary = cpu.run_for_cycles(100);
+        assert_eq!(summary.stop, StopReason::Breakpoint { id, addr: 0x602 });
+        assert_eq!(summary.instructions, 1);
+        // Running again gets past the breakpoint
+        assert_eq!(cpu.run_for_cycles(100).stop, StopReason::Breakpoint { id, addr: 0x602 });
+        assert_eq!(cpu.x(), 1);
+        assert_eq!(cpu.breakpoints().get(id).unwrap().hits, 2);
+
+        cpu.breakpoints_mut().set_condition(id, Some(Condition::parse("X == 5").unwrap()));
+        assert_eq!(cpu.run_for_cycles(100).stop, StopReason::Breakpoint { id, addr: 0x602 });
+        assert_eq!(cpu.x(), 5);
+
+        cpu.breakpoints_mut().set_enabled(id, false);
+        assert_eq!(cpu.run_for_cycles(100).stop, StopReason::CycleLimit);
+        cpu.breakpoints_mut().set_enabled(id, true);
+        assert!(cpu.breakpoints_mut().remove(id));
+        assert!(!cpu.breakpoints_mut().remove(id));
+        assert_eq!(cpu.run_for_cycles(100).stop, StopReason::CycleLimit);
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let mut cpu = Cpu::new();
+        // lda #$42
+        // sta $0201
+        // lda $0200
+        // jsr $0610
+        // nop
+        cpu.load_at(0x600, &[0xa9, 0x42, 0x8d, 0x01, 0x02, 0xad, 0x00, 0x02, 0x20, 0x10, 0x06, 0xea]);
+        cpu.load_at(0x200, &[0x99]);
+        let write = cpu.breakpoints_mut().add_watchpoint(0x0200..=0x02ff, Access::Write);
+        let read = cpu.breakpoints_mut().add_watchpoint(0x0200..=0x02ff, Access::Read);
+        // The code itself is in here, but instruction fetches don't count
+        let code = cpu.breakpoints_mut().add_watchpoint(0x0600..=0x06ff, Access::Read);
+
+        let summary = cpu.run_for_cycles(100);
+        assert_eq!(summary.stop, StopReason::Watchpoint { id: write, access: Access::Write, addr: 0x201, value: 0x42, pc: 0x602 });
+        assert_eq!(cpu.pc(), 0x605);
+        assert_eq!(cpu.run_for_cycles(100).stop,
+                   StopReason::Watchpoint { id: read, access: Access::Read, addr: 0x200, value: 0x99, pc: 0x605 });
+
+        // Pushes go through the same path
+        cpu.breakpoints_mut().remove(code);
+        cpu.breakpoints_mut().add(BreakpointKind::Watch(0x01fe..=0x01ff, Access::ReadWrite), Some(Condition::parse("[$01ff] == 6").unwrap()));
+        let stop = cpu.run_for_cycles(100).stop;
+        assert!(matches!(stop, StopReason::Watchpoint { access: Access::Write, addr: 0x1ff, value: 0x06, pc: 0x608, .. }), "{:?}", stop);
+
+        // Only the run loop reports hits, execute forgets them
+        cpu.set_pc(0x602);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.run_for_cycles(2).stop,
+                   StopReason::Watchpoint { id: read, access: Access::Read, addr: 0x200, value: 0x99, pc: 0x605 });
+    }
+
+    #[test]
+    fn test_breakpoint_display() {
+        let mut breakpoints = Breakpoints::new();
+        breakpoints.add_breakpoint(0x600);
+        let id = breakpoints.add(BreakpointKind::Watch(0x10..=0x1f, Access::Write), Some(Condition::parse("A > 3").unwrap()));
+        breakpoints.set_enabled(id, false);
+        let lines: Vec<String> = breakpoints.iter().map(|breakpoint| breakpoint.to_string()).collect();
+        assert_eq!(lines, vec!["1: break $0600, 0 hits", "2: watch w $0010-$001F if A > 3 (disabled), 0 hits"]);
+    }
+}
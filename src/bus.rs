@@ -0,0 +1,80 @@
+use std::ops::{Index, IndexMut};
+use crate::state::{StateError, StateReader, StateWriter};
+
+// Everything the CPU can see through its address and data lines
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, val: u8);
+
+    // Same as read, but without any side effects. Used by debugging tools
+    fn peek(&self, addr: u16) -> u8;
+
+    // Called after every instruction with the amount of cycles it took
+    fn tick(&mut self, _cycles: u16) {}
+
+    // Combined state of the IRQ and NMI lines of everything attached to the bus
+    fn irq(&self) -> bool { false }
+    fn nmi(&self) -> bool { false }
+
+    // Everything needed to put the bus back the way it is now. Buses that don't implement these aren't saved
+    fn save_state(&self, _out: &mut StateWriter) {}
+    fn load_state(&mut self, _data: &mut StateReader) -> Result<(), StateError> { Ok(()) }
+}
+
+// Flat 64 KiB of RAM with nothing mapped into it
+pub struct Ram {
+    bytes: [u8; 0x10000]
+}
+
+impl Ram {
+    pub fn new() -> Ram {
+        Ram { bytes: [0; 0x10000] }
+    }
+}
+
+impl Default for Ram {
+    fn default() -> Self {
+        Ram::new()
+    }
+}
+
+impl Bus for Ram {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.bytes[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        self.bytes[addr as usize] = val;
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.bytes[addr as usize]
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        out.bytes(&self.bytes);
+    }
+
+    fn load_state(&mut self, data: &mut StateReader) -> Result<(), StateError> {
+        let bytes = data.bytes()?;
+        if bytes.len() != self.bytes.len() {
+            return Err(StateError::Mismatch("RAM size"));
+        }
+        self.bytes.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+impl Index<usize> for Ram {
+    type Output = u8;
+
+    fn index(&self, addr: usize) -> &u8 {
+        &self.bytes[addr]
+    }
+}
+
+impl IndexMut<usize> for Ram {
+    fn index_mut(&mut self, addr: usize) -> &mut u8 {
+        &mut self.bytes[addr]
+    }
+}
@@ -1,108 +1,289 @@
+use std::fmt;
+use std::io;
+use std::ops::RangeInclusive;
+use bitflags::bitflags;
 use crate::instructions::*;
-
-#[allow(non_snake_case)]
-pub struct CPU {
-    PC: u16,
-    SP: u8,
-    A: u8,
-    X: u8,
-    Y: u8,
+use crate::breakpoints::{Access, Breakpoints};
+use crate::bus::{Bus, Ram};
+use crate::disassembler::Disassembler;
+
+pub use self::run::{RunSummary, StopConditions, StopReason};
+pub use self::state::CpuState;
+
+pub struct Cpu<B: Bus = Ram> {
+    pc: u16,
+    sp: u8,
+    a: u8,
+    x: u8,
+    y: u8,
     status: u8,
-    memory: [u8; 0x10000]
+    memory: B,
+    variant: Variant,
+    cycles: u64,
+    strict: bool,
+    undocumented_opcodes: bool,
+    waiting: bool, // Set by WAI until an interrupt line is asserted
+    nmi_line: bool, // Last seen state of the NMI line, needed to detect edges
+    stop_conditions: StopConditions,
+    trace: Option<Box<dyn io::Write + Send>>,
+    tracing: bool,
+    breakpoints: Breakpoints
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Variant {
+    NMOS,  // Original MOS 6502 with all of its bugs
+    CMOS,  // WDC 65C02
+    RP2A03 // Ricoh 2A03 used in the NES: an NMOS core with the decimal mode cut out
+}
+
+// Everything that differs between the variants goes through here, so execute stays the same for all of them
+impl Variant {
+    pub fn opcodes(self) -> &'static [(Instruction, AddressingMode, Cycles); 256] {
+        match self {
+            Variant::NMOS | Variant::RP2A03 => &OPCODES,
+            Variant::CMOS => &OPCODES_65C02
+        }
+    }
+
+    pub fn is_undocumented(self, opcode: u8) -> bool {
+        match self {
+            Variant::NMOS | Variant::RP2A03 => is_undocumented(opcode),
+            Variant::CMOS => is_undocumented_65c02(opcode)
+        }
+    }
+
+    // On the 2A03 SED and CLD still work, but ADC and SBC never look at D
+    pub fn has_decimal_mode(self) -> bool {
+        self != Variant::RP2A03
+    }
+}
+
+bitflags! {
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+    pub struct Flags: u8 {
+        const C = 1 << 0; // Carry flag
+        const Z = 1 << 1; // Zero flag
+        const I = 1 << 2; // Interrupt mask
+        const D = 1 << 3; // BCD flag
+        const B = 1 << 4; // Break flag
+        const S = 1 << 5; // Stub flag (always set to 1)
+        const V = 1 << 6; // Overflow flag
+        const N = 1 << 7; // Negative flag
+    }
+}
+
+// Snapshot of all programmer-visible registers
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Registers {
+    pub pc: u16,
+    pub sp: u8,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub status: Flags
 }
 
-#[derive(Debug)]
-#[allow(dead_code)]
-enum Flags {
-    C = 0, // Carry flag
-    Z = 1, // Zero flag
-    I = 2, // Interrupt mask
-    D = 3, // BCD flag
-    B = 4, // Break flag
-    S = 5, // Stub flag (always set to 1)
-    V = 6, // Overflow flag
-    N = 7  // Negative flag
+pub const NMI_VECTOR: u16 = 0xfffa;
+pub const RESET_VECTOR: u16 = 0xfffc;
+pub const IRQ_VECTOR: u16 = 0xfffe;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecutionError {
+    IllegalOpcode { addr: u16, opcode: u8 }, // Opcode that isn't implemented for the current variant
+    Jam { addr: u16, opcode: u8 }, // One of the KIL opcodes that lock up NMOS parts
+    Stopped { addr: u16 }, // 65C02 STP, only a reset gets the CPU going again
+    InvalidOperand { addr: u16, opcode: u8 }, // OPCODES entry doesn't match what the instruction expects
+    StackOverflow { addr: u16, sp: u8 }, // Only reported in strict mode
+    StackUnderflow { addr: u16, sp: u8 } // Only reported in strict mode
 }
 
-#[derive(Debug)]
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecutionError::IllegalOpcode { addr, opcode } =>
+                write!(f, "illegal opcode 0x{:02x} at 0x{:04x}", opcode, addr),
+            ExecutionError::Jam { addr, opcode } =>
+                write!(f, "CPU jammed by opcode 0x{:02x} at 0x{:04x}", opcode, addr),
+            ExecutionError::Stopped { addr } =>
+                write!(f, "CPU stopped by STP at 0x{:04x}", addr),
+            ExecutionError::InvalidOperand { addr, opcode } =>
+                write!(f, "invalid operand for opcode 0x{:02x} at 0x{:04x}", opcode, addr),
+            ExecutionError::StackOverflow { addr, sp } =>
+                write!(f, "stack overflow at 0x{:04x} (SP = 0x{:02x})", addr, sp),
+            ExecutionError::StackUnderflow { addr, sp } =>
+                write!(f, "stack underflow at 0x{:04x} (SP = 0x{:02x})", addr, sp)
+        }
+    }
+}
+
+impl std::error::Error for ExecutionError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StepInfo {
+    pub addr: u16, // Address of the executed instruction
+    pub opcode: u8,
+    pub instruction: Instruction,
+    pub cycles: u16
+}
+
+#[derive(Clone, Copy, Debug)]
 struct DecodedOpcode {
+    byte: u8,
     instruction: Instruction,
     operand: Operand,
-    length: u16
+    length: u16,
+    cycles: u16
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 enum Operand {
     NoArg,
     Accumulator,
     Constant(u8),
-    Address(u16)
+    Address(u16),
+    ZeroPageRelative(u16, u16) // Zero page address to test and branch target
 }
 
-impl CPU {
+impl<B: Bus> Cpu<B> {
     pub fn print(&self) {
         print!("CPU {{ ");
-        print!("A = 0x{:02x}, ", self.A);
-        print!("X = 0x{:02x}, ", self.X);
-        print!("Y = 0x{:02x}, ", self.Y);
-
-        print!("PC = 0x{:04x}, ", self.PC);
-        print!("SP = 0x{:02x}, ", self.SP);
-        print!("status = 0b{:08b}", self.status);
+        print!("A = 0x{:02x}, ", self.a);
+        print!("X = 0x{:02x}, ", self.x);
+        print!("Y = 0x{:02x}, ", self.y);
+
+        print!("PC = 0x{:04x}, ", self.pc);
+        print!("SP = 0x{:02x}, ", self.sp);
+        print!("status = 0b{:08b}, ", self.status);
+        print!("cycles = {}", self.cycles);
         println!(" }} ");
     }
 
-    #[allow(dead_code)]
     pub fn print_memory(&self) {
-        for i in 0..=0xff {
-            print!("0x{:2x}00: ", i);
-            for j in 0..=0xff {
-                print!("{:2x} ", self.memory[i * 0x100 + j]);
+        self.print_memory_range(0x0000..=0xffff);
+    }
+
+    pub fn print_memory_range(&self, range: RangeInclusive<u16>) {
+        print!("{}", self.dump_memory(range));
+    }
+
+    // Hex dump in rows of 16 bytes. Rows are aligned to 16, bytes before the start of the range are left blank
+    pub fn dump_memory(&self, range: RangeInclusive<u16>) -> String {
+        let mut out = String::new();
+        let (start, end) = (*range.start() as u32, *range.end() as u32);
+        let mut row = start & !0xf;
+
+        while row <= end {
+            out += &format!("0x{:04x}:", row);
+            for addr in row..=(row + 0xf).min(end) {
+                if addr < start {
+                    out += "   ";
+                } else {
+                    out += &format!(" {:02x}", self.memory.peek(addr as u16));
+                }
             }
-            println!();
+            out += "\n";
+            row += 0x10;
         }
+
+        out
     }
 
-    fn get_flag(&self, flag: Flags) -> bool {
-        let i = flag as u8;
-        if self.status & (1 << i) != 0 { true } else { false }
+    pub fn registers(&self) -> Registers {
+        Registers { pc: self.pc, sp: self.sp, a: self.a, x: self.x, y: self.y, status: self.status() }
     }
 
-    fn set_flag(&mut self, flag: Flags, value: bool) {
-        let mask = 1u8 << (flag as u8);
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.pc = registers.pc;
+        self.sp = registers.sp;
+        self.a = registers.a;
+        self.x = registers.x;
+        self.y = registers.y;
+        self.set_status(registers.status);
+    }
+
+    pub fn pc(&self) -> u16 { self.pc }
+    pub fn sp(&self) -> u8 { self.sp }
+    pub fn a(&self) -> u8 { self.a }
+    pub fn x(&self) -> u8 { self.x }
+    pub fn y(&self) -> u8 { self.y }
+    pub fn status(&self) -> Flags { Flags::from_bits_retain(self.status) }
+    pub fn cycles(&self) -> u64 { self.cycles }
+    pub fn variant(&self) -> Variant { self.variant }
+
+    pub fn set_pc(&mut self, pc: u16) { self.pc = pc; }
+    pub fn set_sp(&mut self, sp: u8) { self.sp = sp; }
+    pub fn set_a(&mut self, a: u8) { self.a = a; }
+    pub fn set_x(&mut self, x: u8) { self.x = x; }
+    pub fn set_y(&mut self, y: u8) { self.y = y; }
+    // The stub flag can't be cleared on real hardware
+    pub fn set_status(&mut self, status: Flags) { self.status = (status | Flags::S).bits(); }
+
+    pub fn get_flag(&self, flag: Flags) -> bool {
+        self.status & flag.bits() != 0
+    }
+
+    pub fn set_flag(&mut self, flag: Flags, value: bool) {
         if value {
-            self.status |= mask;
+            self.status |= flag.bits();
         } else {
-            self.status &= !mask;
+            self.status &= !flag.bits();
         }
     }
 
-    fn get_byte(&self, addr: u16) -> u8 {
-        self.memory[addr as usize]
+    pub fn bus(&self) -> &B { &self.memory }
+    pub fn bus_mut(&mut self) -> &mut B { &mut self.memory }
+
+    pub fn get_byte(&mut self, addr: u16) -> u8 {
+        let val = self.memory.read(addr);
+        self.breakpoints.record(addr, val, Access::Read);
+        val
+    }
+
+    // Instruction fetches don't trigger read watchpoints, only the data an instruction works on does
+    fn fetch_byte(&mut self, addr: u16) -> u8 {
+        self.memory.read(addr)
+    }
+
+    // Words are read low byte first, the same order the real bus sees them in
+    fn fetch_word(&mut self, addr: u16) -> u16 {
+        self.fetch_byte(addr) as u16 | (self.fetch_byte(addr.wrapping_add(1)) as u16) << 8
+    }
+
+    pub fn get_word(&mut self, addr: u16) -> u16 {
+        self.get_byte(addr) as u16 | (self.get_byte(addr.wrapping_add(1)) as u16) << 8
     }
 
-    fn get_byte_as_i16(&self, addr: u16) -> i16 {
-        (self.memory[addr as usize] as i8) as i16
+    // NMOS parts never carry into the high byte of the pointer, so JMP ($10ff) reads $10ff and $1000
+    fn get_indirect_word(&mut self, ptr: u16) -> u16 {
+        let hi_addr = match self.variant {
+            Variant::NMOS | Variant::RP2A03 => (ptr & 0xff00) | (ptr.wrapping_add(1) & 0x00ff),
+            Variant::CMOS => ptr.wrapping_add(1)
+        };
+
+        self.get_byte(ptr) as u16 | (self.get_byte(hi_addr) as u16) << 8
     }
 
-    fn get_word(&self, addr: u16) -> u16 {
-        ((self.memory[addr as usize + 1] as u16) << 8) + (self.memory[addr as usize] as u16)
+    // Pointers stored in zero page wrap around to $00 instead of crossing into page one
+    fn get_zero_page_word(&mut self, ptr: u8) -> u16 {
+        self.get_byte(ptr as u16) as u16 | (self.get_byte(ptr.wrapping_add(1) as u16) as u16) << 8
     }
 
-    fn set_byte(&mut self, addr: u16, byte: u8) {
-        self.memory[addr as usize] = byte;
+    pub fn set_byte(&mut self, addr: u16, byte: u8) {
+        self.memory.write(addr, byte);
+        self.breakpoints.record(addr, byte, Access::Write);
     }
 
-    // TODO: handle overflow?
+    // The stack lives in page one and SP simply wraps around on overflow and underflow
     fn push_to_stack(&mut self, byte: u8) {
-        self.set_byte(0x100 + self.SP as u16, byte);
-        self.SP -= 1;
+        self.set_byte(0x100 + self.sp as u16, byte);
+        self.sp = self.sp.wrapping_sub(1);
     }
 
-    // TODO: handle underflow?
     fn pull_from_stack(&mut self) -> u8 {
-        self.SP += 1;
-        self.get_byte(0x100 + self.SP as u16)
+        self.sp = self.sp.wrapping_add(1);
+        self.get_byte(0x100 + self.sp as u16)
     }
 
     fn push_word_to_stack(&mut self, word: u16) {
@@ -114,520 +295,1311 @@ impl CPU {
         self.pull_from_stack() as u16 | (self.pull_from_stack() as u16) << 8
     }
 
-    fn fetch_and_decode(&self) -> DecodedOpcode {
-        let (instruction, mode, _cycles) = OPCODES[self.get_byte(self.PC) as usize];
+    fn decimal_mode(&self) -> bool {
+        self.variant.has_decimal_mode() && self.get_flag(Flags::D)
+    }
+
+    fn add_with_carry(&mut self, value: u8) {
+        let a = self.a as u16;
+        let v = value as u16;
+        let carry = self.get_flag(Flags::C) as u16;
+
+        if !self.decimal_mode() {
+            let sum = a + v + carry;
+            self.set_flag(Flags::C, sum > 0xff);
+            self.set_flag(Flags::V, (!(a ^ v) & (a ^ sum) & 0x80) != 0);
+            self.a = sum as u8;
+            self.set_flag(Flags::Z, self.a == 0);
+            self.set_flag(Flags::N, (self.a & 0b10000000) != 0);
+            return;
+        }
+
+        // NMOS decimal mode: Z comes from the binary sum, N and V from the sum
+        // after the low nibble has been adjusted but before the high nibble is
+        let mut lo = (a & 0x0f) + (v & 0x0f) + carry;
+        if lo > 0x09 {
+            lo += 0x06;
+        }
+        let mut sum = (a & 0xf0) + (v & 0xf0) + (lo & 0x0f);
+        if lo > 0x0f {
+            sum += 0x10;
+        }
+
+        self.set_flag(Flags::Z, ((a + v + carry) & 0xff) == 0);
+        self.set_flag(Flags::N, (sum & 0x80) != 0);
+        self.set_flag(Flags::V, (!(a ^ v) & (a ^ sum) & 0x80) != 0);
+
+        if (sum & 0x1f0) > 0x90 {
+            sum += 0x60;
+        }
+        self.set_flag(Flags::C, (sum & 0xff0) > 0xf0);
+        self.a = sum as u8;
+
+        // The 65C02 spends an extra cycle to get N and Z right
+        if self.variant == Variant::CMOS {
+            self.set_flag(Flags::Z, self.a == 0);
+            self.set_flag(Flags::N, (self.a & 0b10000000) != 0);
+        }
+    }
+
+    fn subtract_with_carry(&mut self, value: u8) {
+        let a = self.a as i16;
+        let v = value as i16;
+        let borrow = !self.get_flag(Flags::C) as i16;
+
+        // On NMOS parts all flags come from the binary difference, even in decimal mode
+        let diff = a - v - borrow;
+        self.set_flag(Flags::C, diff >= 0);
+        self.set_flag(Flags::V, ((a ^ v) & (a ^ diff) & 0x80) != 0);
+        self.set_flag(Flags::Z, (diff & 0xff) == 0);
+        self.set_flag(Flags::N, (diff & 0x80) != 0);
+
+        if !self.decimal_mode() {
+            self.a = diff as u8;
+            return;
+        }
+
+        if self.variant == Variant::CMOS {
+            let lo = (a & 0x0f) - (v & 0x0f) - borrow;
+            let mut result = diff;
+            if result < 0 {
+                result -= 0x60;
+            }
+            if lo < 0 {
+                result -= 0x06;
+            }
+            self.a = result as u8;
+            self.set_flag(Flags::Z, self.a == 0);
+            self.set_flag(Flags::N, (self.a & 0b10000000) != 0);
+            return;
+        }
+
+        let mut lo = (a & 0x0f) - (v & 0x0f) - borrow;
+        let mut hi = (a >> 4) - (v >> 4);
+        if lo < 0 {
+            lo -= 0x06;
+            hi -= 1;
+        }
+        if hi < 0 {
+            hi -= 0x06;
+        }
+        self.a = ((hi << 4) | (lo & 0x0f)) as u8;
+    }
+
+    // ASL, LSR, ROL and ROR on a single value. Sets N, Z and C
+    fn shift(&mut self, instruction: Instruction, c: u8) -> u8 {
+        let carry = self.get_flag(Flags::C) as u8;
+        let result = match instruction {
+            Instruction::ASL => {
+                self.set_flag(Flags::C, (c & 0b10000000) != 0);
+                c << 1
+            }
+            Instruction::LSR => {
+                self.set_flag(Flags::C, (c & 0b00000001) != 0);
+                c >> 1
+            }
+            Instruction::ROL => {
+                self.set_flag(Flags::C, (c & 0b10000000) != 0);
+                (c << 1) | carry
+            }
+            Instruction::ROR => {
+                self.set_flag(Flags::C, (c & 0b00000001) != 0);
+                (c >> 1) | (carry << 7)
+            }
+            _ => { unreachable!() }
+        };
+
+        self.set_flag(Flags::Z, result == 0);
+        self.set_flag(Flags::N, (result & 0b10000000) != 0);
+        result
+    }
+
+    fn compare(&mut self, lhs: u8, rhs: u8) {
+        let cmp = lhs.wrapping_sub(rhs);
+        self.set_flag(Flags::C, lhs >= rhs);
+        self.set_flag(Flags::Z, lhs == rhs);
+        self.set_flag(Flags::N, (cmp & 0b10000000) != 0);
+    }
+
+    // Undocumented ARR: AND and ROR at the same time, with flags coming from the adder
+    fn and_rotate_right(&mut self, value: u8) {
+        let t = self.a & value;
+        let carry = self.get_flag(Flags::C) as u8;
+        let mut result = (t >> 1) | (carry << 7);
+
+        if !self.decimal_mode() {
+            self.set_flag(Flags::Z, result == 0);
+            self.set_flag(Flags::N, (result & 0b10000000) != 0);
+            self.set_flag(Flags::C, (result & 0b01000000) != 0);
+            self.set_flag(Flags::V, ((result >> 6) ^ (result >> 5)) & 1 != 0);
+            self.a = result;
+            return;
+        }
+
+        // NMOS decimal mode: N and Z come from the rotated value, then each nibble is BCD-fixed
+        self.set_flag(Flags::N, carry != 0);
+        self.set_flag(Flags::Z, result == 0);
+        self.set_flag(Flags::V, ((t ^ result) & 0b01000000) != 0);
+        if (t & 0x0f) + (t & 0x01) > 0x05 {
+            result = (result & 0xf0) | (result.wrapping_add(0x06) & 0x0f);
+        }
+        if (t as u16 & 0xf0) + (t as u16 & 0x10) > 0x50 {
+            self.set_flag(Flags::C, true);
+            result = result.wrapping_add(0x60);
+        } else {
+            self.set_flag(Flags::C, false);
+        }
+        self.a = result;
+    }
+
+    fn pull_status_from_stack(&mut self) {
+        let status = self.pull_from_stack();
+        self.status = (status & !Flags::B.bits()) | Flags::S.bits();
+    }
+
+    fn interrupt(&mut self, vector: u16, return_addr: u16, brk: bool) {
+        self.push_word_to_stack(return_addr);
+        let mut status = self.status & !Flags::B.bits();
+        if brk {
+            status |= Flags::B.bits();
+        }
+        self.push_to_stack(status);
+        self.set_flag(Flags::I, true);
+        if self.variant == Variant::CMOS {
+            self.set_flag(Flags::D, false);
+        }
+        self.pc = self.get_word(vector);
+    }
+
+    // Interrupt sequence takes 7 cycles, same as BRK
+    fn service_interrupt(&mut self, vector: u16) -> u16 {
+        self.waiting = false;
+        self.interrupt(vector, self.pc, false);
+        self.cycles += 7;
+        self.memory.tick(7);
+        7
+    }
+
+    // Checks the interrupt lines of the bus, returns the amount of cycles spent entering an interrupt
+    fn poll_interrupts(&mut self) -> u16 {
+        let nmi = self.memory.nmi();
+        let nmi_edge = nmi && !self.nmi_line;
+        self.nmi_line = nmi;
+
+        if nmi_edge {
+            self.service_interrupt(NMI_VECTOR)
+        } else if self.memory.irq() && !self.get_flag(Flags::I) {
+            self.service_interrupt(IRQ_VECTOR)
+        } else {
+            0
+        }
+    }
+
+    /// Services a maskable interrupt request. Does nothing if the I flag is set
+    pub fn irq(&mut self) {
+        // WAI is released by an IRQ even when it's masked
+        self.waiting = false;
+        if !self.get_flag(Flags::I) {
+            self.service_interrupt(IRQ_VECTOR);
+        }
+    }
+
+    /// Services a non-maskable interrupt
+    pub fn nmi(&mut self) {
+        self.service_interrupt(NMI_VECTOR);
+    }
+
+    /// Emulates the RESET line: nothing is written to the stack, but SP still moves down by 3,
+    /// interrupts get disabled and execution continues from the reset vector
+    pub fn reset(&mut self) {
+        self.sp = self.sp.wrapping_sub(3);
+        self.set_flag(Flags::I, true);
+        if self.variant == Variant::CMOS {
+            self.set_flag(Flags::D, false);
+        }
+        self.waiting = false;
+        // Edges on the NMI line from before the reset don't count, only ones after it
+        self.nmi_line = self.memory.nmi();
+        self.pc = self.get_word(RESET_VECTOR);
+        self.cycles += 7;
+        self.memory.tick(7);
+    }
+
+    fn fetch_and_decode(&mut self) -> DecodedOpcode {
+        let byte = self.fetch_byte(self.pc);
+        let (instruction, mode, cycles) = self.variant.opcodes()[byte as usize];
+        let mut page_crossed = false;
         let operand =  match mode {
             AddressingMode::Implied => Operand::NoArg,
             AddressingMode::Accumulator => Operand::Accumulator,
             AddressingMode::Immediate =>
-                Operand::Constant(self.get_byte(self.PC + 1)),
+                Operand::Constant(self.fetch_byte(self.pc.wrapping_add(1))),
             AddressingMode::ZeroPage =>
-                Operand::Address(self.get_byte(self.PC + 1) as u16),
+                Operand::Address(self.fetch_byte(self.pc.wrapping_add(1)) as u16),
             AddressingMode::ZeroPageX =>
-                Operand::Address(self.get_byte(self.PC + 1).wrapping_add(self.X) as u16),
+                Operand::Address(self.fetch_byte(self.pc.wrapping_add(1)).wrapping_add(self.x) as u16),
             AddressingMode::ZeroPageY =>
-                Operand::Address(self.get_byte(self.PC + 1).wrapping_add(self.Y) as u16),
+                Operand::Address(self.fetch_byte(self.pc.wrapping_add(1)).wrapping_add(self.y) as u16),
             AddressingMode::Relative => {
-                let addr = self.PC.checked_add_signed(self.get_byte_as_i16(self.PC + 1)).unwrap();
-                Operand::Address(addr + 2)
+                let offset = self.fetch_byte(self.pc.wrapping_add(1)) as i8 as i16;
+                Operand::Address(self.pc.wrapping_add(2).wrapping_add_signed(offset))
             },
+            // JSR pushes the return address before it reads the high byte of the target, see its arm in execute
+            AddressingMode::Absolute if instruction == Instruction::JSR =>
+                Operand::Constant(self.fetch_byte(self.pc.wrapping_add(1))),
             AddressingMode::Absolute =>
-                Operand::Address(self.get_word(self.PC + 1)),
-            AddressingMode::AbsoluteX =>
-                Operand::Address(self.get_word(self.PC + 1) + self.X as u16),
-            AddressingMode::AbsoluteY =>
-                Operand::Address(self.get_word(self.PC + 1) + self.Y as u16),
-            AddressingMode::Indirect =>
-                Operand::Address(self.get_word(self.get_word(self.PC + 1))),
-            AddressingMode::IndirectX => {
-                let addr = self.get_byte(self.PC + 1).wrapping_add(self.X) as u16;
+                Operand::Address(self.fetch_word(self.pc.wrapping_add(1))),
+            AddressingMode::AbsoluteX => {
+                let base = self.fetch_word(self.pc.wrapping_add(1));
+                let addr = base.wrapping_add(self.x as u16);
+                page_crossed = (base & 0xff00) != (addr & 0xff00);
+                Operand::Address(addr)
+            },
+            AddressingMode::AbsoluteY => {
+                let base = self.fetch_word(self.pc.wrapping_add(1));
+                let addr = base.wrapping_add(self.y as u16);
+                page_crossed = (base & 0xff00) != (addr & 0xff00);
                 Operand::Address(addr)
             },
+            AddressingMode::Indirect => {
+                let ptr = self.fetch_word(self.pc.wrapping_add(1));
+                Operand::Address(self.get_indirect_word(ptr))
+            },
+            AddressingMode::IndirectX => {
+                let ptr = self.fetch_byte(self.pc.wrapping_add(1)).wrapping_add(self.x);
+                Operand::Address(self.get_zero_page_word(ptr))
+            },
             AddressingMode::IndirectY => {
-                let addr = self.get_word(self.PC + 1) + self.Y as u16;
+                let ptr = self.fetch_byte(self.pc.wrapping_add(1));
+                let base = self.get_zero_page_word(ptr);
+                let addr = base.wrapping_add(self.y as u16);
+                page_crossed = (base & 0xff00) != (addr & 0xff00);
                 Operand::Address(addr)
+            },
+            AddressingMode::ZeroPageIndirect => {
+                let ptr = self.fetch_byte(self.pc.wrapping_add(1));
+                Operand::Address(self.get_zero_page_word(ptr))
+            },
+            AddressingMode::AbsoluteIndirectX => {
+                let ptr = self.fetch_word(self.pc.wrapping_add(1)).wrapping_add(self.x as u16);
+                Operand::Address(self.get_word(ptr))
+            },
+            AddressingMode::ZeroPageRelative => {
+                let addr = self.fetch_byte(self.pc.wrapping_add(1)) as u16;
+                let offset = self.fetch_byte(self.pc.wrapping_add(2)) as i8 as i16;
+                Operand::ZeroPageRelative(addr, self.pc.wrapping_add(3).wrapping_add_signed(offset))
             }
         };
 
-        DecodedOpcode {instruction, operand, length: 1 + mode.operand_bytes() }
+        // Taken branches are accounted for in execute, since only it knows whether the branch is taken
+        let cycles = match cycles {
+            Cycles::Exact(n) => n,
+            Cycles::PageBoundary(n) => n + page_crossed as u16,
+            Cycles::Branching => 2
+        };
+
+        DecodedOpcode { byte, instruction, operand, length: 1 + mode.operand_bytes(), cycles }
     }
 
-    pub fn execute(&mut self) {
+    // In strict mode SP wrapping around is reported as an error instead of being silently allowed
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    // Undocumented opcodes are executed like on real NMOS parts unless disabled,
+    // in which case they are reported as IllegalOpcode. JAM is reported as Jam either way
+    pub fn set_undocumented_opcodes(&mut self, enabled: bool) {
+        self.undocumented_opcodes = enabled;
+    }
+
+    // Disassembler that decodes the same way this CPU does
+    pub fn disassembler(&self) -> Disassembler {
+        Disassembler::new(self.variant).undocumented(self.undocumented_opcodes)
+    }
+
+    // How many bytes an instruction pushes (positive) or pulls (negative)
+    fn stack_usage(instruction: Instruction) -> i16 {
+        match instruction {
+            Instruction::PHA | Instruction::PHP | Instruction::PHX | Instruction::PHY => 1,
+            Instruction::JSR => 2,
+            Instruction::BRK => 3,
+            Instruction::PLA | Instruction::PLP | Instruction::PLX | Instruction::PLY => -1,
+            Instruction::RTS => -2,
+            Instruction::RTI => -3,
+            _ => 0
+        }
+    }
+
+    // Executes a single instruction. On error the registers are left untouched and nothing but the opcode
+    // has been read from the bus
+    pub fn execute(&mut self) -> Result<StepInfo, ExecutionError> {
+        self.breakpoints.clear_hits();
+        if self.waiting {
+            return Ok(self.wait());
+        }
+        if self.tracing {
+            self.write_trace();
+        }
+
+        // These only depend on the opcode, so they're checked before decoding reads any operands
+        let addr = self.pc;
+        let byte = self.memory.peek(addr);
+        let instruction = self.variant.opcodes()[byte as usize].0;
+        if !self.undocumented_opcodes && self.variant.is_undocumented(byte) && instruction != Instruction::JAM {
+            return Err(ExecutionError::IllegalOpcode { addr, opcode: byte });
+        }
+
+        if self.strict {
+            let usage = Self::stack_usage(instruction);
+            if usage > 0 && (self.sp as i16) < usage {
+                return Err(ExecutionError::StackOverflow { addr, sp: self.sp });
+            }
+            if usage < 0 && (self.sp as i16) - usage > 0xff {
+                return Err(ExecutionError::StackUnderflow { addr, sp: self.sp });
+            }
+        }
+
         let opcode = self.fetch_and_decode();
+        let byte = opcode.byte;
+        let mut cycles = opcode.cycles;
+        let invalid_operand = ExecutionError::InvalidOperand { addr, opcode: byte };
 
         match opcode {
-            // ASL
-            DecodedOpcode { instruction: Instruction::ASL, operand, length } => {
-                let mut c = match operand {
-                    Operand::Accumulator => self.A,
+            // ADC, SBC
+            DecodedOpcode { instruction: Instruction::ADC, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::SBC, operand, length, .. } => {
+                let c = match operand {
+                    Operand::Constant(c) => c,
                     Operand::Address(addr) => self.get_byte(addr),
-                    _ => { panic!("Unknown operand type for ASL: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 };
 
-                self.set_flag(Flags::C, (c & 0b10000000) != 0);
-                c = c << 1;
-                self.set_flag(Flags::Z, c == 0);
-                self.set_flag(Flags::N, (c & 0b10000000) != 0);
+                match opcode.instruction {
+                    Instruction::ADC => self.add_with_carry(c),
+                    Instruction::SBC => self.subtract_with_carry(c),
+                    _ => { unreachable!() }
+                }
+                if self.variant == Variant::CMOS && self.decimal_mode() {
+                    cycles += 1;
+                }
+
+                self.pc = self.pc.wrapping_add(length);
+            }
+
+            // AND, ORA, EOR
+            DecodedOpcode { instruction: Instruction::AND, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::ORA, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::EOR, operand, length, .. } => {
+                let c = match operand {
+                    Operand::Constant(c) => c,
+                    Operand::Address(addr) => self.get_byte(addr),
+                    _ => { return Err(invalid_operand); }
+                };
+
+                self.a = match opcode.instruction {
+                    Instruction::AND => self.a & c,
+                    Instruction::ORA => self.a | c,
+                    Instruction::EOR => self.a ^ c,
+                    _ => { unreachable!() }
+                };
+                self.set_flag(Flags::Z, self.a == 0);
+                self.set_flag(Flags::N, (self.a & 0b10000000) != 0);
+
+                self.pc = self.pc.wrapping_add(length);
+            }
+
+            // BIT
+            DecodedOpcode { instruction: Instruction::BIT, operand, length, .. } => {
+                let c = match operand {
+                    Operand::Constant(c) => c,
+                    Operand::Address(addr) => self.get_byte(addr),
+                    _ => { return Err(invalid_operand); }
+                };
+
+                self.set_flag(Flags::Z, (self.a & c) == 0);
+                // 65C02 BIT #imm only affects Z
+                if let Operand::Address(_) = operand {
+                    self.set_flag(Flags::V, (c & 0b01000000) != 0);
+                    self.set_flag(Flags::N, (c & 0b10000000) != 0);
+                }
+
+                self.pc = self.pc.wrapping_add(length);
+            }
+
+            // ASL, LSR, ROL, ROR
+            DecodedOpcode { instruction: Instruction::ASL, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::LSR, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::ROL, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::ROR, operand, length, .. } => {
+                let c = match operand {
+                    Operand::Accumulator => self.a,
+                    Operand::Address(addr) => self.get_byte(addr),
+                    _ => { return Err(invalid_operand); }
+                };
+
+                let c = self.shift(opcode.instruction, c);
 
                 match operand {
-                    Operand::Accumulator => { self.A = c; }
+                    Operand::Accumulator => { self.a = c; }
                     Operand::Address(addr) => { self.set_byte(addr, c); }
-                    _ => { panic!("Unknown operand type for ASL: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 };
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // CMP, CPX, CPY
-            DecodedOpcode { instruction: Instruction::CMP, operand, length }
-            | DecodedOpcode { instruction: Instruction::CPX, operand, length }
-            | DecodedOpcode { instruction: Instruction::CPY, operand, length }=> {
+            DecodedOpcode { instruction: Instruction::CMP, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::CPX, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::CPY, operand, length, .. }=> {
                 let lhs = match opcode.instruction {
-                    Instruction::CMP => self.A,
-                    Instruction::CPX => self.X,
-                    Instruction::CPY => self.Y,
-                    _ => { panic!() }
+                    Instruction::CMP => self.a,
+                    Instruction::CPX => self.x,
+                    Instruction::CPY => self.y,
+                    _ => { unreachable!() }
                 };
 
                 let rhs = match operand {
                     Operand::Constant(c) => c,
                     Operand::Address(addr) => self.get_byte(addr),
-                    _ => { panic!("Unknown operand type for CMP: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 };
 
-                let cmp = lhs.wrapping_sub(rhs);
-                self.set_flag(Flags::C, lhs >= rhs);
-                self.set_flag(Flags::Z, lhs == rhs);
-                self.set_flag(Flags::N, (cmp & 0b10000000) != 0);
+                self.compare(lhs, rhs);
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
 
             // LDA
-            DecodedOpcode { instruction: Instruction::LDA, operand, length } => {
+            DecodedOpcode { instruction: Instruction::LDA, operand, length, .. } => {
                 let c = match operand {
                     Operand::Constant(c) => c,
                     Operand::Address(addr) => self.get_byte(addr),
-                    _ => { panic!("Unknown operand type for LDA: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 };
 
                 self.set_flag(Flags::Z, c == 0);
                 self.set_flag(Flags::N, (c & 0b10000000) != 0);
-                self.A = c;
+                self.a = c;
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // LDX
-            DecodedOpcode { instruction: Instruction::LDX, operand, length } => {
+            DecodedOpcode { instruction: Instruction::LDX, operand, length, .. } => {
                 let c = match operand {
                     Operand::Constant(c) => c,
                     Operand::Address(addr) => self.get_byte(addr),
-                    _ => { panic!("Unknown operand type for LDX: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 };
 
                 self.set_flag(Flags::Z, c == 0);
                 self.set_flag(Flags::N, (c & 0b10000000) != 0);
-                self.X = c;
+                self.x = c;
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // LDY
-            DecodedOpcode { instruction: Instruction::LDY, operand, length } => {
+            DecodedOpcode { instruction: Instruction::LDY, operand, length, .. } => {
                 let c = match operand {
                     Operand::Constant(c) => c,
                     Operand::Address(addr) => self.get_byte(addr),
-                    _ => { panic!("Unknown operand type for LDY: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 };
 
                 self.set_flag(Flags::Z, c == 0);
                 self.set_flag(Flags::N, (c & 0b10000000) != 0);
-                self.Y = c;
+                self.y = c;
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // STA
-            DecodedOpcode { instruction: Instruction::STA, operand, length} => {
+            DecodedOpcode { instruction: Instruction::STA, operand, length, .. } => {
                 let addr = match operand {
                     Operand::Address(addr) => addr,
-                    _ => { panic!("Unknown operand type for STA: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 };
 
-                self.memory[addr as usize] = self.A;
+                self.set_byte(addr, self.a);
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // STX
-            DecodedOpcode { instruction: Instruction::STX, operand, length } => {
+            DecodedOpcode { instruction: Instruction::STX, operand, length, .. } => {
                 let addr = match operand {
                     Operand::Address(addr) => addr,
-                    _ => { panic!("Unknown operand type for STX: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 };
 
-                self.memory[addr as usize] = self.X;
+                self.set_byte(addr, self.x);
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // STY
-            DecodedOpcode { instruction: Instruction::STY, operand, length } => {
+            DecodedOpcode { instruction: Instruction::STY, operand, length, .. } => {
                 let addr = match operand {
                     Operand::Address(addr) => addr,
-                    _ => { panic!("Unknown operand type for STY: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 };
 
-                self.memory[addr as usize] = self.Y;
+                self.set_byte(addr, self.y);
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // TAX
-            DecodedOpcode { instruction: Instruction::TAX, operand, length } => {
+            DecodedOpcode { instruction: Instruction::TAX, operand, length, .. } => {
                 match operand {
                     Operand::NoArg => {},
-                    _ => { panic!("Unknown operand type for TAX: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 }
 
-                self.set_flag(Flags::Z, self.A == 0);
-                self.set_flag(Flags::N, (self.A & 0b10000000) != 0);
-                self.X = self.A;
+                self.set_flag(Flags::Z, self.a == 0);
+                self.set_flag(Flags::N, (self.a & 0b10000000) != 0);
+                self.x = self.a;
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // TAY
-            DecodedOpcode { instruction: Instruction::TAY, operand, length } => {
+            DecodedOpcode { instruction: Instruction::TAY, operand, length, .. } => {
                 match operand {
                     Operand::NoArg => {},
-                    _ => { panic!("Unknown operand type for TAY: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 }
 
-                self.set_flag(Flags::Z, self.A == 0);
-                self.set_flag(Flags::N, (self.A & 0b10000000) != 0);
-                self.Y = self.A;
+                self.set_flag(Flags::Z, self.a == 0);
+                self.set_flag(Flags::N, (self.a & 0b10000000) != 0);
+                self.y = self.a;
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // TXA
-            DecodedOpcode { instruction: Instruction::TXA, operand, length } => {
+            DecodedOpcode { instruction: Instruction::TXA, operand, length, .. } => {
                 match operand {
                     Operand::NoArg => {},
-                    _ => { panic!("Unknown operand type for TXA: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 }
 
-                self.set_flag(Flags::Z, self.X == 0);
-                self.set_flag(Flags::N, (self.X & 0b10000000) != 0);
-                self.A = self.X;
+                self.set_flag(Flags::Z, self.x == 0);
+                self.set_flag(Flags::N, (self.x & 0b10000000) != 0);
+                self.a = self.x;
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // TYA
-            DecodedOpcode { instruction: Instruction::TYA, operand, length } => {
+            DecodedOpcode { instruction: Instruction::TYA, operand, length, .. } => {
                 match operand {
                     Operand::NoArg => {},
-                    _ => { panic!("Unknown operand type for TYA: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 }
 
-                self.set_flag(Flags::Z, self.Y == 0);
-                self.set_flag(Flags::N, (self.Y & 0b10000000) != 0);
-                self.A = self.Y;
+                self.set_flag(Flags::Z, self.y == 0);
+                self.set_flag(Flags::N, (self.y & 0b10000000) != 0);
+                self.a = self.y;
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // DEX
-            DecodedOpcode { instruction: Instruction::DEX, operand, length } => {
+            DecodedOpcode { instruction: Instruction::DEX, operand, length, .. } => {
                 match operand {
                     Operand::NoArg => {},
-                    _ => { panic!("Unknown operand type for DEX: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 }
 
-                let c = self.X.wrapping_sub(1);
+                let c = self.x.wrapping_sub(1);
                 self.set_flag(Flags::Z, c == 0);
                 self.set_flag(Flags::N, (c & 0b10000000) != 0);
-                self.X = c;
+                self.x = c;
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // DEY
-            DecodedOpcode { instruction: Instruction::DEY, operand, length } => {
+            DecodedOpcode { instruction: Instruction::DEY, operand, length, .. } => {
                 match operand {
                     Operand::NoArg => {},
-                    _ => { panic!("Unknown operand type for DEY: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 }
 
-                let c = self.Y.wrapping_sub(1);
+                let c = self.y.wrapping_sub(1);
                 self.set_flag(Flags::Z, c == 0);
                 self.set_flag(Flags::N, (c & 0b10000000) != 0);
-                self.Y = c;
+                self.y = c;
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // DEC
-            DecodedOpcode { instruction: Instruction::DEC, operand, length} => {
-                let addr = match operand {
-                    Operand::Address(addr) => addr,
-                    _ => { panic!("Unknown operand type for DEC: {:?}", operand); }
+            DecodedOpcode { instruction: Instruction::DEC, operand, length, .. } => {
+                // 65C02 adds DEC A
+                let mut c = match operand {
+                    Operand::Accumulator => self.a,
+                    Operand::Address(addr) => self.get_byte(addr),
+                    _ => { return Err(invalid_operand); }
                 };
-                let mut c = self.get_byte(addr);
 
                 c = c.wrapping_sub(1);
                 self.set_flag(Flags::Z, c == 0);
                 self.set_flag(Flags::N, (c & 0b10000000) != 0);
-                self.set_byte(addr, c);
+                match operand {
+                    Operand::Accumulator => { self.a = c; }
+                    Operand::Address(addr) => { self.set_byte(addr, c); }
+                    _ => { return Err(invalid_operand); }
+                };
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // INX
-            DecodedOpcode { instruction: Instruction::INX, operand, length } => {
+            DecodedOpcode { instruction: Instruction::INX, operand, length, .. } => {
                 match operand {
                     Operand::NoArg => {},
-                    _ => { panic!("Unknown operand type for INX: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 }
 
-                let c = self.X.wrapping_add(1);
+                let c = self.x.wrapping_add(1);
                 self.set_flag(Flags::Z, c == 0);
                 self.set_flag(Flags::N, (c & 0b10000000) != 0);
-                self.X = c;
+                self.x = c;
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // INY
-            DecodedOpcode { instruction: Instruction::INY, operand, length } => {
+            DecodedOpcode { instruction: Instruction::INY, operand, length, .. } => {
                 match operand {
                     Operand::NoArg => {},
-                    _ => { panic!("Unknown operand type for INY: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 }
 
-                let c = self.Y.wrapping_add(1);
+                let c = self.y.wrapping_add(1);
                 self.set_flag(Flags::Z, c == 0);
                 self.set_flag(Flags::N, (c & 0b10000000) != 0);
-                self.Y = c;
+                self.y = c;
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // INC
-            DecodedOpcode { instruction: Instruction::INC, operand, length} => {
-                let addr = match operand {
-                    Operand::Address(addr) => addr,
-                    _ => { panic!("Unknown operand type for INC: {:?}", operand); }
+            DecodedOpcode { instruction: Instruction::INC, operand, length, .. } => {
+                // 65C02 adds INC A
+                let mut c = match operand {
+                    Operand::Accumulator => self.a,
+                    Operand::Address(addr) => self.get_byte(addr),
+                    _ => { return Err(invalid_operand); }
                 };
-                let mut c = self.get_byte(addr);
 
                 c = c.wrapping_add(1);
                 self.set_flag(Flags::Z, c == 0);
                 self.set_flag(Flags::N, (c & 0b10000000) != 0);
-                self.set_byte(addr, c);
+                match operand {
+                    Operand::Accumulator => { self.a = c; }
+                    Operand::Address(addr) => { self.set_byte(addr, c); }
+                    _ => { return Err(invalid_operand); }
+                };
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // TSX
-            DecodedOpcode { instruction: Instruction::TSX, operand, length } => {
+            DecodedOpcode { instruction: Instruction::TSX, operand, length, .. } => {
                 match operand {
                     Operand::NoArg => {},
-                    _ => { panic!("Unknown operand type for TSX: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 }
 
-                self.set_flag(Flags::Z, self.SP == 0);
-                self.set_flag(Flags::N, (self.SP & 0b10000000) != 0);
-                self.X = self.SP;
+                self.set_flag(Flags::Z, self.sp == 0);
+                self.set_flag(Flags::N, (self.sp & 0b10000000) != 0);
+                self.x = self.sp;
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // TXS
-            DecodedOpcode { instruction: Instruction::TXS, operand, length } => {
+            DecodedOpcode { instruction: Instruction::TXS, operand, length, .. } => {
                 match operand {
                     Operand::NoArg => {},
-                    _ => { panic!("Unknown operand type for TSX: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 }
 
-                self.SP = self.X;
+                self.sp = self.x;
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // CLC, CLD, CLI, CLV,
-            DecodedOpcode { instruction: Instruction::CLC, operand: _, length }
-            | DecodedOpcode { instruction: Instruction::CLD, operand: _, length }
-            | DecodedOpcode { instruction: Instruction::CLI, operand: _, length }
-            | DecodedOpcode { instruction: Instruction::CLV, operand: _, length }
-            | DecodedOpcode { instruction: Instruction::SEC, operand: _, length }
-            | DecodedOpcode { instruction: Instruction::SED, operand: _, length }
-            | DecodedOpcode { instruction: Instruction::SEI, operand: _, length } => {
+            DecodedOpcode { instruction: Instruction::CLC, operand: _, length, .. }
+            | DecodedOpcode { instruction: Instruction::CLD, operand: _, length, .. }
+            | DecodedOpcode { instruction: Instruction::CLI, operand: _, length, .. }
+            | DecodedOpcode { instruction: Instruction::CLV, operand: _, length, .. }
+            | DecodedOpcode { instruction: Instruction::SEC, operand: _, length, .. }
+            | DecodedOpcode { instruction: Instruction::SED, operand: _, length, .. }
+            | DecodedOpcode { instruction: Instruction::SEI, operand: _, length, .. } => {
                 let flag = match opcode.instruction {
                     Instruction::CLC | Instruction::SEC => Flags::C,
                     Instruction::CLD | Instruction::SED => Flags::D,
                     Instruction::CLI | Instruction::SEI => Flags::I,
                     Instruction::CLV => Flags::V,
-                    _ => { panic!(); }
+                    _ => { unreachable!(); }
                 };
 
                 let value = match opcode.instruction {
                     Instruction::CLC | Instruction::CLD | Instruction::CLI | Instruction::CLV => false,
                     Instruction::SEC | Instruction::SED | Instruction::SEI => true,
-                    _ => { panic!(); }
+                    _ => { unreachable!(); }
                 };
 
                 self.set_flag(flag, value);
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // PHA
-            DecodedOpcode { instruction: Instruction::PHA, operand, length } => {
+            DecodedOpcode { instruction: Instruction::PHA, operand, length, .. } => {
                 match operand {
                     Operand::NoArg => {},
-                    _ => { panic!("Unknown operand type for PHA: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 }
 
-                self.push_to_stack(self.A);
+                self.push_to_stack(self.a);
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // PHP
-            DecodedOpcode { instruction: Instruction::PHP, operand, length } => {
+            DecodedOpcode { instruction: Instruction::PHP, operand, length, .. } => {
                 match operand {
                     Operand::NoArg => {},
-                    _ => { panic!("Unknown operand type for PHP: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 }
 
-                // TODO: one source says something about break flag being set to 1. Figure it out
-                self.push_to_stack(self.status);
+                // B only exists on the stack: it's pushed as 1 by PHP and BRK and as 0 by IRQ and NMI
+                self.push_to_stack(self.status | Flags::B.bits());
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // PLA
-            DecodedOpcode { instruction: Instruction::PLA, operand, length } => {
+            DecodedOpcode { instruction: Instruction::PLA, operand, length, .. } => {
                 match operand {
                     Operand::NoArg => {},
-                    _ => { panic!("Unknown operand type for PLA: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 }
 
-                self.A = self.pull_from_stack();
-                self.set_flag(Flags::Z, self.A == 0);
-                self.set_flag(Flags::N, (self.A & 0b10000000) != 0);
+                self.a = self.pull_from_stack();
+                self.set_flag(Flags::Z, self.a == 0);
+                self.set_flag(Flags::N, (self.a & 0b10000000) != 0);
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // PLP
-            DecodedOpcode { instruction: Instruction::PLP, operand, length } => {
+            DecodedOpcode { instruction: Instruction::PLP, operand, length, .. } => {
                 match operand {
                     Operand::NoArg => {},
-                    _ => { panic!("Unknown operand type for PLP: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 }
 
-                self.status = self.pull_from_stack();
+                self.pull_status_from_stack();
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
 
             // NOP
-            DecodedOpcode { instruction: Instruction::NOP, operand, length } => {
+            DecodedOpcode { instruction: Instruction::NOP, operand, length, .. } => {
+                // Undocumented NOPs with an operand still perform the read
                 match operand {
-                Operand::NoArg => {},
-                _ => { panic!("Unknown operand type for MOP: {:?}", operand); }
+                    Operand::NoArg | Operand::Constant(_) => {},
+                    Operand::Address(addr) => { self.get_byte(addr); },
+                    _ => { return Err(invalid_operand); }
                 }
 
-                self.PC += length;
+                self.pc = self.pc.wrapping_add(length);
             }
 
             // BCC/BCS, BNE/BEQ, BPL/BMI, BVC/BVS
-            DecodedOpcode { instruction: Instruction::BCC, operand, length }
-            | DecodedOpcode { instruction: Instruction::BCS, operand, length }
-            | DecodedOpcode { instruction: Instruction::BNE, operand, length }
-            | DecodedOpcode { instruction: Instruction::BEQ, operand, length }
-            | DecodedOpcode { instruction: Instruction::BPL, operand, length }
-            | DecodedOpcode { instruction: Instruction::BMI, operand, length }
-            | DecodedOpcode { instruction: Instruction::BVC, operand, length }
-            | DecodedOpcode { instruction: Instruction::BVS, operand, length }=> {
+            DecodedOpcode { instruction: Instruction::BCC, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::BCS, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::BNE, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::BEQ, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::BPL, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::BMI, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::BVC, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::BVS, operand, length, .. }=> {
                 let flag = match opcode.instruction {
                     Instruction::BCC | Instruction::BCS => Flags::C,
                     Instruction::BNE | Instruction::BEQ => Flags::Z,
                     Instruction::BPL | Instruction::BMI => Flags::N,
                     Instruction::BVC | Instruction::BVS => Flags::V,
-                    _ => { panic!(); }
+                    _ => { unreachable!(); }
                 };
 
                 let value = match opcode.instruction {
                     Instruction::BCC | Instruction::BNE | Instruction::BPL | Instruction::BVC => false,
                     Instruction::BCS | Instruction::BEQ | Instruction::BMI | Instruction::BVS => true,
-                    _ => { panic!(); }
+                    _ => { unreachable!(); }
                 };
 
                 let addr = match operand {
                     Operand::Address(addr) => addr,
-                    _ => { panic!(); }
+                    _ => { return Err(invalid_operand); }
                 };
 
                 if self.get_flag(flag) == value { // take the branch
-                    self.PC = addr;
+                    let next = self.pc.wrapping_add(length);
+                    cycles += if (next & 0xff00) != (addr & 0xff00) { 2 } else { 1 };
+                    self.pc = addr;
                 } else {
-                    self.PC += length;
+                    self.pc = self.pc.wrapping_add(length);
                 }
             }
 
             // JSR
-            DecodedOpcode { instruction: Instruction::JSR, operand, length } => {
-                let addr = match operand {
-                    Operand::Address(addr) => addr,
-                    _ => { panic!("Unknown operand type for JSR: {:?}", operand); }
+            DecodedOpcode { instruction: Instruction::JSR, operand, length, .. } => {
+                let lo = match operand {
+                    Operand::Constant(lo) => lo,
+                    _ => { return Err(invalid_operand); }
                 };
 
-                self.push_word_to_stack(self.PC + length - 1);
-                self.PC = addr;
+                // A push that lands on the high byte of the target changes where JSR goes
+                self.push_word_to_stack(self.pc.wrapping_add(length - 1));
+                let hi = self.fetch_byte(self.pc.wrapping_add(2));
+                self.pc = (hi as u16) << 8 | lo as u16;
             }
 
             // RTS
             DecodedOpcode { instruction: Instruction::RTS, operand, .. } => {
                 match operand {
                     Operand::NoArg => {},
-                    _ => { panic!("Unknown operand type for RTS: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
                 };
 
                 let addr = self.pull_word_from_stack();
-                self.PC = addr + 1;
+                self.pc = addr.wrapping_add(1);
+            }
+
+            // BRK
+            DecodedOpcode { instruction: Instruction::BRK, operand, .. } => {
+                match operand {
+                    Operand::NoArg => {},
+                    _ => { return Err(invalid_operand); }
+                };
+
+                // BRK skips a padding byte, so the return address is PC + 2
+                self.interrupt(IRQ_VECTOR, self.pc.wrapping_add(2), true);
+            }
+
+            // RTI
+            DecodedOpcode { instruction: Instruction::RTI, operand, .. } => {
+                match operand {
+                    Operand::NoArg => {},
+                    _ => { return Err(invalid_operand); }
+                };
+
+                self.pull_status_from_stack();
+                self.pc = self.pull_word_from_stack();
             }
 
             // JMP
             DecodedOpcode { instruction: Instruction::JMP, operand, .. } => {
                 let addr = match operand {
                     Operand::Address(addr) => addr,
-                    _ => { panic!("Unknown operand type for JMP: {:?}", operand); }
+                    _ => { return Err(invalid_operand); }
+                };
+
+                self.pc = addr;
+            }
+
+            // SLO, RLA, SRE, RRA
+            DecodedOpcode { instruction: Instruction::SLO, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::RLA, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::SRE, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::RRA, operand, length, .. } => {
+                let addr = match operand {
+                    Operand::Address(addr) => addr,
+                    _ => { return Err(invalid_operand); }
+                };
+
+                let shift = match opcode.instruction {
+                    Instruction::SLO => Instruction::ASL,
+                    Instruction::RLA => Instruction::ROL,
+                    Instruction::SRE => Instruction::LSR,
+                    Instruction::RRA => Instruction::ROR,
+                    _ => { unreachable!() }
+                };
+                let c = self.get_byte(addr);
+                let c = self.shift(shift, c);
+                self.set_byte(addr, c);
+
+                match opcode.instruction {
+                    Instruction::SLO => { self.a |= c; }
+                    Instruction::RLA => { self.a &= c; }
+                    Instruction::SRE => { self.a ^= c; }
+                    // ADC sets its own flags, which differ from A in decimal mode
+                    Instruction::RRA => { self.add_with_carry(c); }
+                    _ => { unreachable!() }
+                }
+                if opcode.instruction != Instruction::RRA {
+                    self.set_flag(Flags::Z, self.a == 0);
+                    self.set_flag(Flags::N, (self.a & 0b10000000) != 0);
+                }
+
+                self.pc = self.pc.wrapping_add(length);
+            }
+
+            // DCP, ISC
+            DecodedOpcode { instruction: Instruction::DCP, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::ISC, operand, length, .. } => {
+                let addr = match operand {
+                    Operand::Address(addr) => addr,
+                    _ => { return Err(invalid_operand); }
+                };
+
+                let c = self.get_byte(addr);
+                if opcode.instruction == Instruction::DCP {
+                    let c = c.wrapping_sub(1);
+                    self.set_byte(addr, c);
+                    self.compare(self.a, c);
+                } else {
+                    let c = c.wrapping_add(1);
+                    self.set_byte(addr, c);
+                    self.subtract_with_carry(c);
+                }
+
+                self.pc = self.pc.wrapping_add(length);
+            }
+
+            // SAX
+            DecodedOpcode { instruction: Instruction::SAX, operand, length, .. } => {
+                let addr = match operand {
+                    Operand::Address(addr) => addr,
+                    _ => { return Err(invalid_operand); }
                 };
 
-                self.PC = addr;
+                self.set_byte(addr, self.a & self.x);
+
+                self.pc = self.pc.wrapping_add(length);
+            }
+
+            // LAX
+            DecodedOpcode { instruction: Instruction::LAX, operand, length, .. } => {
+                let c = match operand {
+                    // Immediate LAX (also known as LXA) is unstable; 0xee is the most common magic constant
+                    Operand::Constant(c) => (self.a | 0xee) & c,
+                    Operand::Address(addr) => self.get_byte(addr),
+                    _ => { return Err(invalid_operand); }
+                };
+
+                self.set_flag(Flags::Z, c == 0);
+                self.set_flag(Flags::N, (c & 0b10000000) != 0);
+                self.a = c;
+                self.x = c;
+
+                self.pc = self.pc.wrapping_add(length);
             }
 
-            _ => println!("Unknown opcode {:?}", opcode)
+            // ANC, ALR, ARR, SBX, ANE
+            DecodedOpcode { instruction: Instruction::ANC, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::ALR, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::ARR, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::SBX, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::ANE, operand, length, .. } => {
+                let c = match operand {
+                    Operand::Constant(c) => c,
+                    _ => { return Err(invalid_operand); }
+                };
+
+                match opcode.instruction {
+                    Instruction::ANC => {
+                        self.a &= c;
+                        self.set_flag(Flags::Z, self.a == 0);
+                        self.set_flag(Flags::N, (self.a & 0b10000000) != 0);
+                        self.set_flag(Flags::C, (self.a & 0b10000000) != 0);
+                    }
+                    Instruction::ALR => {
+                        self.a = self.shift(Instruction::LSR, self.a & c);
+                    }
+                    Instruction::ARR => {
+                        self.and_rotate_right(c);
+                    }
+                    Instruction::SBX => {
+                        let lhs = self.a & self.x;
+                        self.compare(lhs, c);
+                        self.x = lhs.wrapping_sub(c);
+                    }
+                    Instruction::ANE => {
+                        // Unstable as well, uses the same magic constant as LAX #imm
+                        self.a = (self.a | 0xee) & self.x & c;
+                        self.set_flag(Flags::Z, self.a == 0);
+                        self.set_flag(Flags::N, (self.a & 0b10000000) != 0);
+                    }
+                    _ => { unreachable!() }
+                }
+
+                self.pc = self.pc.wrapping_add(length);
+            }
+
+            // SHA, SHX, SHY, TAS
+            DecodedOpcode { instruction: Instruction::SHA, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::SHX, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::SHY, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::TAS, operand, length, .. } => {
+                let addr = match operand {
+                    Operand::Address(addr) => addr,
+                    _ => { return Err(invalid_operand); }
+                };
+
+                let (index, value) = match opcode.instruction {
+                    Instruction::SHA => (self.y, self.a & self.x),
+                    Instruction::SHX => (self.y, self.x),
+                    Instruction::SHY => (self.x, self.y),
+                    Instruction::TAS => {
+                        self.sp = self.a & self.x;
+                        (self.y, self.sp)
+                    }
+                    _ => { unreachable!() }
+                };
+
+                // The value gets ANDed with the high byte of the base address plus one. When indexing
+                // crosses a page, the result also replaces the high byte of the address
+                let base = addr.wrapping_sub(index as u16);
+                let value = value & ((base >> 8) as u8).wrapping_add(1);
+                let addr = if (base & 0xff00) != (addr & 0xff00) {
+                    ((value as u16) << 8) | (addr & 0x00ff)
+                } else {
+                    addr
+                };
+                self.set_byte(addr, value);
+
+                self.pc = self.pc.wrapping_add(length);
+            }
+
+            // LAS
+            DecodedOpcode { instruction: Instruction::LAS, operand, length, .. } => {
+                let c = match operand {
+                    Operand::Address(addr) => self.get_byte(addr),
+                    _ => { return Err(invalid_operand); }
+                };
+
+                let c = c & self.sp;
+                self.set_flag(Flags::Z, c == 0);
+                self.set_flag(Flags::N, (c & 0b10000000) != 0);
+                self.a = c;
+                self.x = c;
+                self.sp = c;
+
+                self.pc = self.pc.wrapping_add(length);
+            }
+
+            // BRA
+            DecodedOpcode { instruction: Instruction::BRA, operand, length, .. } => {
+                let addr = match operand {
+                    Operand::Address(addr) => addr,
+                    _ => { return Err(invalid_operand); }
+                };
+
+                let next = self.pc.wrapping_add(length);
+                cycles += if (next & 0xff00) != (addr & 0xff00) { 2 } else { 1 };
+                self.pc = addr;
+            }
+
+            // PHX, PHY
+            DecodedOpcode { instruction: Instruction::PHX, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::PHY, operand, length, .. } => {
+                match operand {
+                    Operand::NoArg => {},
+                    _ => { return Err(invalid_operand); }
+                }
+
+                let c = if opcode.instruction == Instruction::PHX { self.x } else { self.y };
+                self.push_to_stack(c);
+
+                self.pc = self.pc.wrapping_add(length);
+            }
+
+            // PLX, PLY
+            DecodedOpcode { instruction: Instruction::PLX, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::PLY, operand, length, .. } => {
+                match operand {
+                    Operand::NoArg => {},
+                    _ => { return Err(invalid_operand); }
+                }
+
+                let c = self.pull_from_stack();
+                self.set_flag(Flags::Z, c == 0);
+                self.set_flag(Flags::N, (c & 0b10000000) != 0);
+                if opcode.instruction == Instruction::PLX {
+                    self.x = c;
+                } else {
+                    self.y = c;
+                }
+
+                self.pc = self.pc.wrapping_add(length);
+            }
+
+            // STZ
+            DecodedOpcode { instruction: Instruction::STZ, operand, length, .. } => {
+                let addr = match operand {
+                    Operand::Address(addr) => addr,
+                    _ => { return Err(invalid_operand); }
+                };
+
+                self.set_byte(addr, 0);
+
+                self.pc = self.pc.wrapping_add(length);
+            }
+
+            // TRB, TSB
+            DecodedOpcode { instruction: Instruction::TRB, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::TSB, operand, length, .. } => {
+                let addr = match operand {
+                    Operand::Address(addr) => addr,
+                    _ => { return Err(invalid_operand); }
+                };
+
+                let c = self.get_byte(addr);
+                self.set_flag(Flags::Z, (self.a & c) == 0);
+                let c = match opcode.instruction {
+                    Instruction::TRB => c & !self.a,
+                    Instruction::TSB => c | self.a,
+                    _ => { unreachable!() }
+                };
+                self.set_byte(addr, c);
+
+                self.pc = self.pc.wrapping_add(length);
+            }
+
+            // RMB, SMB
+            DecodedOpcode { instruction: Instruction::RMB, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::SMB, operand, length, .. } => {
+                let addr = match operand {
+                    Operand::Address(addr) => addr,
+                    _ => { return Err(invalid_operand); }
+                };
+
+                let bit = 1 << ((byte >> 4) & 0b111);
+                let c = self.get_byte(addr);
+                let c = match opcode.instruction {
+                    Instruction::RMB => c & !bit,
+                    Instruction::SMB => c | bit,
+                    _ => { unreachable!() }
+                };
+                self.set_byte(addr, c);
+
+                self.pc = self.pc.wrapping_add(length);
+            }
+
+            // BBR, BBS
+            DecodedOpcode { instruction: Instruction::BBR, operand, length, .. }
+            | DecodedOpcode { instruction: Instruction::BBS, operand, length, .. } => {
+                let (addr, target) = match operand {
+                    Operand::ZeroPageRelative(addr, target) => (addr, target),
+                    _ => { return Err(invalid_operand); }
+                };
+
+                let bit = 1 << ((byte >> 4) & 0b111);
+                let set = (self.get_byte(addr) & bit) != 0;
+                if set == (opcode.instruction == Instruction::BBS) { // take the branch
+                    let next = self.pc.wrapping_add(length);
+                    cycles += if (next & 0xff00) != (target & 0xff00) { 2 } else { 1 };
+                    self.pc = target;
+                } else {
+                    self.pc = self.pc.wrapping_add(length);
+                }
+            }
+
+            // WAI
+            DecodedOpcode { instruction: Instruction::WAI, length, .. } => {
+                self.waiting = true;
+
+                self.pc = self.pc.wrapping_add(length);
+            }
+
+            // STP
+            DecodedOpcode { instruction: Instruction::STP, .. } => {
+                return Err(ExecutionError::Stopped { addr });
+            }
+
+            DecodedOpcode { instruction: Instruction::JAM, .. } => {
+                return Err(ExecutionError::Jam { addr, opcode: byte });
+            }
+
+            _ => { return Err(ExecutionError::IllegalOpcode { addr, opcode: byte }); }
         }
+
+        self.cycles += cycles as u64;
+        self.memory.tick(cycles);
+        cycles += self.poll_interrupts();
+
+        Ok(StepInfo { addr, opcode: byte, instruction: opcode.instruction, cycles })
+    }
+
+    // A CPU halted by WAI burns one cycle per step until an interrupt line gets asserted.
+    // An IRQ wakes it up even when I is set, it just doesn't get serviced then
+    fn wait(&mut self) -> StepInfo {
+        let addr = self.pc.wrapping_sub(1);
+        self.cycles += 1;
+        self.memory.tick(1);
+
+        if self.memory.irq() || self.memory.nmi() {
+            self.waiting = false;
+        }
+        let cycles = 1 + self.poll_interrupts();
+
+        StepInfo { addr, opcode: self.memory.peek(addr), instruction: Instruction::WAI, cycles }
     }
 
     pub fn load_at(&mut self, at: usize, data: &[u8]) {
-        for (i, byte) in data.into_iter().enumerate() {
-            self.memory[at + i] = *byte;
+        for (i, byte) in data.iter().enumerate() {
+            self.memory.write(((at + i) & 0xffff) as u16, *byte);
         }
     }
 
-    pub fn new() -> CPU {
-        CPU { PC: 0x600, SP: 0xff, A: 0, X: 0, Y: 0, status: 0b00100000, memory: [0; 0x10000] }
+    pub fn with_bus(bus: B, variant: Variant) -> Cpu<B> {
+        Cpu {
+            pc: 0x600, sp: 0xff, a: 0, x: 0, y: 0, status: 0b00100000,
+            memory: bus,
+            variant,
+            cycles: 0,
+            strict: false,
+            undocumented_opcodes: true,
+            waiting: false,
+            nmi_line: false,
+            stop_conditions: StopConditions::default(),
+            trace: None,
+            tracing: false,
+            breakpoints: Breakpoints::new()
+        }
+    }
+}
+
+impl Cpu<Ram> {
+    pub fn new() -> Cpu {
+        Cpu::with_variant(Variant::NMOS)
+    }
+
+    pub fn with_variant(variant: Variant) -> Cpu {
+        Cpu::with_bus(Ram::new(), variant)
+    }
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Cpu::new()
     }
 }
 
+mod run;
+mod state;
+mod trace;
 mod test;
\ No newline at end of file
@@ -0,0 +1,135 @@
+use crate::breakpoints::{Access, BreakpointId, BreakpointKind, Breakpoints};
+use crate::bus::Bus;
+use crate::cpu::{Cpu, ExecutionError};
+
+// Extra reasons for a run loop to stop, on top of the ones each run_* function has
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StopConditions {
+    pub brk: bool, // Stop before executing BRK
+    pub trap: bool // Stop after an instruction that jumps or branches to itself
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    CycleLimit,
+    Condition, // Predicate of run_until or the address of run_until_pc was reached
+    Brk { addr: u16 },
+    Trap { addr: u16 },
+    Breakpoint { id: BreakpointId, addr: u16 }, // The instruction at addr hasn't run yet
+    Watchpoint { id: BreakpointId, access: Access, addr: u16, value: u8, pc: u16 }, // Instruction at pc made the access
+    Error(ExecutionError)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RunSummary {
+    pub instructions: u64,
+    pub cycles: u64,
+    pub stop: StopReason
+}
+
+impl<B: Bus> Cpu<B> {
+    pub fn stop_conditions(&self) -> StopConditions {
+        self.stop_conditions
+    }
+
+    pub fn set_stop_conditions(&mut self, stop_conditions: StopConditions) {
+        self.stop_conditions = stop_conditions;
+    }
+
+    // Breakpoints and watchpoints stop every run_* function
+    pub fn breakpoints(&self) -> &Breakpoints {
+        &self.breakpoints
+    }
+
+    pub fn breakpoints_mut(&mut self) -> &mut Breakpoints {
+        &mut self.breakpoints
+    }
+
+    // Runs until at least `cycles` cycles have passed. The last instruction may overshoot the limit
+    pub fn run_for_cycles(&mut self, cycles: u64) -> RunSummary {
+        self.run(Some(cycles), self.stop_conditions, |_| false)
+    }
+
+    // Runs until the predicate holds. It's checked before every instruction, including the first one
+    pub fn run_until<F: FnMut(&Cpu<B>) -> bool>(&mut self, predicate: F) -> RunSummary {
+        self.run(None, self.stop_conditions, predicate)
+    }
+
+    // Same as run_until, except that the instruction at PC always runs, even when it's BRK or the predicate
+    // already holds. Front ends use it to continue from wherever the last run stopped
+    pub fn resume_until<F: FnMut(&Cpu<B>) -> bool>(&mut self, predicate: F) -> RunSummary {
+        self.run_from(None, self.stop_conditions, true, predicate)
+    }
+
+    pub fn run_until_pc(&mut self, addr: u16) -> RunSummary {
+        self.run(None, self.stop_conditions, |cpu| cpu.pc == addr)
+    }
+
+    // Runs until the program hits BRK or gets stuck in a trap, regardless of the stop conditions
+    pub fn run_until_brk(&mut self) -> RunSummary {
+        self.run(None, StopConditions { brk: true, trap: true }, |_| false)
+    }
+
+    fn run<F: FnMut(&Cpu<B>) -> bool>(&mut self, max_cycles: Option<u64>, stop: StopConditions, predicate: F) -> RunSummary {
+        self.run_from(max_cycles, stop, false, predicate)
+    }
+
+    fn run_from<F: FnMut(&Cpu<B>) -> bool>(&mut self, max_cycles: Option<u64>, stop: StopConditions, resume: bool,
+                                           mut predicate: F) -> RunSummary {
+        let start_cycles = self.cycles;
+        let mut instructions = 0;
+        let summary = |cpu: &Cpu<B>, instructions, stop| RunSummary { instructions, cycles: cpu.cycles - start_cycles, stop };
+
+        loop {
+            if max_cycles.is_some_and(|max| self.cycles - start_cycles >= max) {
+                return summary(self, instructions, StopReason::CycleLimit);
+            }
+            if !(resume && instructions == 0) {
+                if predicate(self) {
+                    return summary(self, instructions, StopReason::Condition);
+                }
+                if stop.brk && !self.waiting && self.memory.peek(self.pc) == 0x00 {
+                    return summary(self, instructions, StopReason::Brk { addr: self.pc });
+                }
+            }
+            // A run that starts on a breakpoint gets past it, so running again resumes the program
+            if instructions > 0 {
+                if let Some(id) = self.breakpoint_at_pc() {
+                    return summary(self, instructions, StopReason::Breakpoint { id, addr: self.pc });
+                }
+            }
+
+            match self.execute() {
+                Ok(info) => {
+                    instructions += 1;
+                    if let Some(reason) = self.watchpoint_hit(info.addr) {
+                        return summary(self, instructions, reason);
+                    }
+                    if stop.trap && self.pc == info.addr {
+                        return summary(self, instructions, StopReason::Trap { addr: info.addr });
+                    }
+                }
+                Err(e) => return summary(self, instructions, StopReason::Error(e))
+            }
+        }
+    }
+
+    fn breakpoint_at_pc(&mut self) -> Option<BreakpointId> {
+        let id = self.breakpoints.iter().find(|breakpoint| {
+            breakpoint.enabled && breakpoint.kind == BreakpointKind::Execute(self.pc)
+                && breakpoint.condition.as_ref().is_none_or(|condition| condition.evaluate(self))
+        })?.id;
+        self.breakpoints.count_hit(id);
+        Some(id)
+    }
+
+    // Conditions of watchpoints are checked once the instruction that triggered them is done
+    fn watchpoint_hit(&mut self, pc: u16) -> Option<StopReason> {
+        let hit = self.breakpoints.take_hits().into_iter().find(|hit| {
+            let condition = self.breakpoints.get(hit.id).and_then(|breakpoint| breakpoint.condition.as_ref());
+            condition.is_none_or(|condition| condition.evaluate(self))
+        })?;
+        self.breakpoints.count_hit(hit.id);
+        Some(StopReason::Watchpoint { id: hit.id, access: hit.access, addr: hit.addr, value: hit.value, pc })
+    }
+}
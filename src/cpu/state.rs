@@ -0,0 +1,128 @@
+use crate::bus::Bus;
+use crate::cpu::{Cpu, Variant};
+use crate::state::{StateError, StateReader, StateWriter, STATE_MAGIC, STATE_VERSION};
+
+// Everything needed to resume emulation later. Settings like strict mode and undocumented opcodes aren't part
+// of it, they belong to whoever builds the CPU
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CpuState {
+    pub variant: Variant,
+    pub pc: u16,
+    pub sp: u8,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub status: u8,
+    pub cycles: u64,
+    pub waiting: bool, // Halted by WAI until an interrupt comes in
+    pub nmi_line: bool, // An NMI that was already asserted isn't serviced again after loading
+    pub bus: Vec<u8> // Whatever the bus saved, in its own format
+}
+
+impl CpuState {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = StateWriter::new();
+        STATE_MAGIC.iter().for_each(|byte| out.u8(*byte));
+        out.u16(STATE_VERSION);
+        out.u8(match self.variant {
+            Variant::NMOS => 0,
+            Variant::CMOS => 1,
+            Variant::RP2A03 => 2
+        });
+        out.u16(self.pc);
+        out.u8(self.sp);
+        out.u8(self.a);
+        out.u8(self.x);
+        out.u8(self.y);
+        out.u8(self.status);
+        out.u64(self.cycles);
+        out.bool(self.waiting);
+        out.bool(self.nmi_line);
+        out.bytes(&self.bus);
+        out.into_bytes()
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<CpuState, StateError> {
+        let mut data = StateReader::new(data);
+        for byte in STATE_MAGIC {
+            if data.u8().map_err(|_| StateError::BadMagic)? != byte {
+                return Err(StateError::BadMagic);
+            }
+        }
+        let version = data.u16()?;
+        if version != STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let state = CpuState {
+            variant: match data.u8()? {
+                0 => Variant::NMOS,
+                1 => Variant::CMOS,
+                2 => Variant::RP2A03,
+                _ => return Err(StateError::Invalid("variant"))
+            },
+            pc: data.u16()?,
+            sp: data.u8()?,
+            a: data.u8()?,
+            x: data.u8()?,
+            y: data.u8()?,
+            status: data.u8()?,
+            cycles: data.u64()?,
+            waiting: data.bool()?,
+            nmi_line: data.bool()?,
+            bus: data.bytes()?.to_vec()
+        };
+        data.finish()?;
+        Ok(state)
+    }
+}
+
+impl<B: Bus> Cpu<B> {
+    pub fn state(&self) -> CpuState {
+        let mut bus = StateWriter::new();
+        self.memory.save_state(&mut bus);
+        CpuState {
+            variant: self.variant,
+            pc: self.pc,
+            sp: self.sp,
+            a: self.a,
+            x: self.x,
+            y: self.y,
+            status: self.status,
+            cycles: self.cycles,
+            waiting: self.waiting,
+            nmi_line: self.nmi_line,
+            bus: bus.into_bytes()
+        }
+    }
+
+    // The bus is restored first, so on error the registers are left alone.
+    // The bus itself may be half restored if its part of the state is broken
+    pub fn restore_state(&mut self, state: &CpuState) -> Result<(), StateError> {
+        let mut bus = StateReader::new(&state.bus);
+        self.memory.load_state(&mut bus)?;
+        bus.finish()?;
+
+        self.variant = state.variant;
+        self.pc = state.pc;
+        self.sp = state.sp;
+        self.a = state.a;
+        self.x = state.x;
+        self.y = state.y;
+        self.status = state.status;
+        self.cycles = state.cycles;
+        self.waiting = state.waiting;
+        self.nmi_line = state.nmi_line;
+        Ok(())
+    }
+
+    // Versioned binary snapshot of the CPU and everything on the bus
+    pub fn save_state(&self) -> Vec<u8> {
+        self.state().to_bytes()
+    }
+
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        self.restore_state(&CpuState::from_bytes(data)?)
+    }
+}
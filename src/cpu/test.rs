@@ -1,160 +1,270 @@
+#![allow(clippy::module_inception)]
+
 #[cfg(test)]
 mod test {
     use super::super::*;
+    use crate::bus::Bus;
+    use crate::memory_map::MemoryMap;
+    use crate::state::StateError;
 
     #[test]
     fn test_set_flag() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.set_flag(Flags::S, false);
         assert!(!cpu.get_flag(Flags::S));
         cpu.set_flag(Flags::S, true);
         assert!(cpu.get_flag(Flags::S));
     }
 
-    // TODO: cover LDA by unit tests
-    // TODO: cover STA by unit tests
+    #[test]
+    fn test_registers() {
+        let mut cpu = Cpu::new();
+        cpu.set_registers(Registers { pc: 0x1234, sp: 0xf0, a: 0x01, x: 0x02, y: 0x03, status: Flags::C | Flags::N });
+        assert_eq!(cpu.pc(), 0x1234);
+        assert_eq!(cpu.sp(), 0xf0);
+        assert_eq!((cpu.a(), cpu.x(), cpu.y()), (0x01, 0x02, 0x03));
+        assert_eq!(cpu.status(), Flags::C | Flags::S | Flags::N);
+        assert_eq!(cpu.status, 0b10100001);
+
+        cpu.set_a(0x55);
+        cpu.set_status(Flags::empty());
+        let registers = cpu.registers();
+        assert_eq!(registers.a, 0x55);
+        assert_eq!(registers.status, Flags::S);
+    }
+
+    // TODO: cover the rest of LDA by unit tests
+    // TODO: cover the rest of STA by unit tests
+
+    // LDA
+    #[test]
+    fn test_lda_indirect_x() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0xa1, 0x20]);
+        cpu.x = 0x04;
+        cpu.load_at(0x24, &[0x74, 0x20]);
+        cpu.memory[0x2074] = 0x8f;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x8f);
+        assert_eq!(cpu.status, 0b10100000);
+    }
+
+    #[test]
+    fn test_lda_indirect_x_zeropage_wrap() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0xa1, 0xfe]);
+        cpu.x = 0x01;
+        // the pointer sits at $ff and its high byte is read from $00, not $100
+        cpu.memory[0xff] = 0x74;
+        cpu.memory[0x00] = 0x20;
+        cpu.memory[0x100] = 0x30;
+        cpu.memory[0x2074] = 0x12;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x12);
+
+        // the sum of the operand and X wraps inside zero page as well
+        cpu.load_at(0x602, &[0xa1, 0xff]);
+        cpu.x = 0x05;
+        cpu.load_at(0x04, &[0x00, 0x30]);
+        cpu.memory[0x3000] = 0x34;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x34);
+    }
+
+    #[test]
+    fn test_lda_indirect_y() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0xb1, 0x86]);
+        cpu.y = 0x10;
+        cpu.load_at(0x86, &[0x28, 0x40]);
+        cpu.memory[0x4038] = 0x00;
+        cpu.memory[0x4028] = 0xff;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x00);
+        assert_eq!(cpu.status, 0b00100010);
+    }
+
+    #[test]
+    fn test_lda_indirect_y_zeropage_wrap() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0xb1, 0xff]);
+        cpu.y = 0x01;
+        cpu.memory[0xff] = 0xff;
+        cpu.memory[0x00] = 0x20;
+        cpu.memory[0x100] = 0x30;
+        cpu.memory[0x2100] = 0x56;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x56);
+    }
+
+    // STA
+    #[test]
+    fn test_sta_indirect_x() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x81, 0x20]);
+        cpu.a = 0x5a;
+        cpu.x = 0x04;
+        cpu.load_at(0x24, &[0x74, 0x20]);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x2074], 0x5a);
+        assert_eq!(cpu.memory[0x24], 0x74);
+        assert_eq!(cpu.status, 0b00100000);
+    }
+
+    #[test]
+    fn test_sta_indirect_y() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x91, 0x86]);
+        cpu.a = 0x5a;
+        cpu.y = 0x10;
+        cpu.load_at(0x86, &[0x28, 0x40]);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x4038], 0x5a);
+        assert_eq!(cpu.memory[0x86], 0x28);
+        assert_eq!(cpu.status, 0b00100000);
+    }
 
     // LDX
     #[test]
     fn test_ldx_immediate() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x600, &[0xa2, 0xff]);
-        cpu.execute();
-        assert_eq!(cpu.X, 0xff);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.x, 0xff);
         assert_eq!(cpu.status, 0b10100000);
     }
 
     #[test]
     fn test_ldx_zeropage() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0xa6, 0x02, 0xde]);
-        cpu.PC = 0;
-        cpu.execute();
-        assert_eq!(cpu.X, 0xde);
+        cpu.pc = 0;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.x, 0xde);
         assert_eq!(cpu.status, 0b10100000);
     }
 
     #[test]
     fn test_ldx_zeropage_y() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0xb6, 0x02, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x14]);
-        cpu.PC = 0;
-        cpu.Y = 0x8;
-        cpu.execute();
-        assert_eq!(cpu.X, 0x14);
+        cpu.pc = 0;
+        cpu.y = 0x8;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.x, 0x14);
         assert_eq!(cpu.status, 0b00100000);
     }
 
     #[test]
     fn test_ldx_absolute() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x600, &[0xae, 0x33, 0x20]);
-        cpu.PC = 0x600;
+        cpu.pc = 0x600;
         cpu.memory[0x2033] = 0xef;
-        cpu.execute();
-        assert_eq!(cpu.X, 0xef);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.x, 0xef);
         assert_eq!(cpu.status, 0b10100000);
     }
 
     #[test]
     fn test_ldx_absolute_y() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x600, &[0xbe, 0x33, 0x20]);
-        cpu.PC = 0x600;
-        cpu.Y = 0x10;
+        cpu.pc = 0x600;
+        cpu.y = 0x10;
         cpu.memory[0x2033] = 0xef;
         cpu.memory[0x2043] = 0x14;
-        cpu.execute();
-        assert_eq!(cpu.X, 0x14);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.x, 0x14);
         assert_eq!(cpu.status, 0b00100000);
     }
 
     // LDY
     #[test]
     fn test_ldy_immediate() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x600, &[0xa0, 0x00]);
-        cpu.execute();
-        assert_eq!(cpu.Y, 0x00);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.y, 0x00);
         assert_eq!(cpu.status, 0b00100010);
     }
 
     #[test]
     fn test_ldy_zeropage() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0xa4, 0x02, 0xde]);
-        cpu.PC = 0;
-        cpu.execute();
-        assert_eq!(cpu.Y, 0xde);
+        cpu.pc = 0;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.y, 0xde);
         assert_eq!(cpu.status, 0b10100000);
     }
 
     #[test]
     fn test_ldy_zeropage_x() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0xb4, 0x02, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x14]);
-        cpu.PC = 0;
-        cpu.X = 0x8;
-        cpu.execute();
-        assert_eq!(cpu.Y, 0x14);
+        cpu.pc = 0;
+        cpu.x = 0x8;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.y, 0x14);
         assert_eq!(cpu.status, 0b00100000);
     }
 
     #[test]
     fn test_ldy_absolute() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x600, &[0xac, 0x33, 0x20]);
-        cpu.PC = 0x600;
+        cpu.pc = 0x600;
         cpu.memory[0x2033] = 0xef;
-        cpu.execute();
-        assert_eq!(cpu.Y, 0xef);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.y, 0xef);
         assert_eq!(cpu.status, 0b10100000);
     }
 
     #[test]
     fn test_ldy_absolute_x() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x600, &[0xbc, 0x33, 0x20]);
-        cpu.PC = 0x600;
-        cpu.X = 0x10;
+        cpu.pc = 0x600;
+        cpu.x = 0x10;
         cpu.memory[0x2033] = 0xef;
         cpu.memory[0x2043] = 0x14;
-        cpu.execute();
-        assert_eq!(cpu.Y, 0x14);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.y, 0x14);
         assert_eq!(cpu.status, 0b00100000);
     }
 
     // STX
     #[test]
     fn test_stx_zeropage() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x0, &[0x86, 0x33]);
-        cpu.PC = 0x0;
-        cpu.X = 0xbb;
-        cpu.execute();
+        cpu.pc = 0x0;
+        cpu.x = 0xbb;
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x0033], 0xbb);
         assert_eq!(cpu.status, 0b00100000);
     }
 
     #[test]
     fn test_stx_zeropage_y() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x0, &[0x96, 0x33]);
-        cpu.PC = 0x0;
-        cpu.X = 0x71;
-        cpu.Y = 0x22;
-        cpu.execute();
+        cpu.pc = 0x0;
+        cpu.x = 0x71;
+        cpu.y = 0x22;
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x0055], 0x71);
         assert_eq!(cpu.status, 0b00100000);
     }
 
     #[test]
     fn test_stx_absolute() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x600, &[0x8e, 0x64, 0x65]);
-        cpu.PC = 0x600;
-        cpu.X = 0x71;
-        cpu.execute();
+        cpu.pc = 0x600;
+        cpu.x = 0x71;
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x6564], 0x71);
         assert_eq!(cpu.status, 0b00100000);
     }
@@ -162,34 +272,34 @@ mod test {
     // STY
     #[test]
     fn test_sty_zeropage() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x0, &[0x84, 0x33]);
-        cpu.PC = 0x0;
-        cpu.Y = 0xbb;
-        cpu.execute();
+        cpu.pc = 0x0;
+        cpu.y = 0xbb;
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x0033], 0xbb);
         assert_eq!(cpu.status, 0b00100000);
     }
 
     #[test]
     fn test_sty_zeropage_x() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x0, &[0x94, 0x33]);
-        cpu.PC = 0x0;
-        cpu.Y = 0x71;
-        cpu.X = 0x22;
-        cpu.execute();
+        cpu.pc = 0x0;
+        cpu.y = 0x71;
+        cpu.x = 0x22;
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x0055], 0x71);
         assert_eq!(cpu.status, 0b00100000);
     }
 
     #[test]
     fn test_sty_absolute() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x600, &[0x8c, 0x64, 0x65]);
-        cpu.PC = 0x600;
-        cpu.Y = 0x71;
-        cpu.execute();
+        cpu.pc = 0x600;
+        cpu.y = 0x71;
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x6564], 0x71);
         assert_eq!(cpu.status, 0b00100000);
     }
@@ -197,165 +307,165 @@ mod test {
     // TAX
     #[test]
     fn test_tax() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x600, &[0xaa, 0xaa]);
-        cpu.PC = 0x600;
-        cpu.A = 0xde;
-        cpu.X = 0xad;
-        cpu.execute();
-        assert_eq!(cpu.X, 0xde);
+        cpu.pc = 0x600;
+        cpu.a = 0xde;
+        cpu.x = 0xad;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.x, 0xde);
         assert_eq!(cpu.status, 0b10100000);
 
-        cpu.A = 0x00;
-        cpu.X = 0xad;
-        cpu.execute();
-        assert_eq!(cpu.X, 0);
+        cpu.a = 0x00;
+        cpu.x = 0xad;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.x, 0);
         assert_eq!(cpu.status, 0b00100010);
     }
 
     // TAY
     #[test]
     fn test_tay() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x600, &[0xa8, 0xa8]);
-        cpu.PC = 0x600;
-        cpu.A = 0xde;
-        cpu.Y = 0xad;
-        cpu.execute();
-        assert_eq!(cpu.Y, 0xde);
+        cpu.pc = 0x600;
+        cpu.a = 0xde;
+        cpu.y = 0xad;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.y, 0xde);
         assert_eq!(cpu.status, 0b10100000);
 
-        cpu.A = 0x00;
-        cpu.Y = 0xad;
-        cpu.execute();
-        assert_eq!(cpu.Y, 0);
+        cpu.a = 0x00;
+        cpu.y = 0xad;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.y, 0);
         assert_eq!(cpu.status, 0b00100010);
     }
 
     // TXA
     #[test]
     fn test_txa() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x600, &[0x8a, 0x8a]);
-        cpu.PC = 0x600;
-        cpu.A = 0xde;
-        cpu.X = 0xad;
-        cpu.execute();
-        assert_eq!(cpu.A, 0xad);
+        cpu.pc = 0x600;
+        cpu.a = 0xde;
+        cpu.x = 0xad;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0xad);
         assert_eq!(cpu.status, 0b10100000);
 
-        cpu.A = 0x00;
-        cpu.X = 0xad;
-        cpu.execute();
-        assert_eq!(cpu.A, 0xad);
+        cpu.a = 0x00;
+        cpu.x = 0xad;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0xad);
         assert_eq!(cpu.status, 0b10100000);
     }
 
     // TYA
     #[test]
     fn test_tya() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x600, &[0x98, 0x98]);
-        cpu.PC = 0x600;
-        cpu.A = 0xde;
-        cpu.Y = 0xad;
-        cpu.execute();
-        assert_eq!(cpu.A, 0xad);
+        cpu.pc = 0x600;
+        cpu.a = 0xde;
+        cpu.y = 0xad;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0xad);
         assert_eq!(cpu.status, 0b10100000);
 
-        cpu.A = 0x00;
-        cpu.Y = 0xad;
-        cpu.execute();
-        assert_eq!(cpu.A, 0xad);
+        cpu.a = 0x00;
+        cpu.y = 0xad;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0xad);
         assert_eq!(cpu.status, 0b10100000);
     }
 
     // DEX
     #[test]
     fn test_dex() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0xca, 0xca]);
-        cpu.X = 0x01;
-        cpu.PC = 0x0;
-        cpu.execute();
-        assert_eq!(cpu.X, 0x00);
+        cpu.x = 0x01;
+        cpu.pc = 0x0;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.x, 0x00);
         assert_eq!(cpu.status, 0b00100010);
-        cpu.execute();
-        assert_eq!(cpu.X, 0xff);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.x, 0xff);
         assert_eq!(cpu.status, 0b10100000);
     }
 
     // DEY
     #[test]
     fn test_dey() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0x88, 0x88]);
-        cpu.Y = 0x01;
-        cpu.PC = 0x0;
-        cpu.execute();
-        assert_eq!(cpu.Y, 0x00);
+        cpu.y = 0x01;
+        cpu.pc = 0x0;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.y, 0x00);
         assert_eq!(cpu.status, 0b00100010);
-        cpu.execute();
-        assert_eq!(cpu.Y, 0xff);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.y, 0xff);
         assert_eq!(cpu.status, 0b10100000);
     }
 
     // DEC
     #[test]
     fn test_dec_zeropage() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0xc6, 0x10, 0xc6, 0x10]);
-        cpu.PC = 0;
+        cpu.pc = 0;
         cpu.memory[0x10] = 0x01;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x10], 0x00);
         assert_eq!(cpu.status, 0b00100010);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x10], 0xff);
         assert_eq!(cpu.status, 0b10100000);
     }
 
     #[test]
     fn test_dec_zeropage_x() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0xd6, 0xb, 0xd6, 0xb]);
-        cpu.PC = 0;
-        cpu.X = 0x5;
+        cpu.pc = 0;
+        cpu.x = 0x5;
         cpu.memory[0xb] = 0xdd;
         cpu.memory[0x10] = 0x01;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x10], 0x00);
         assert_eq!(cpu.status, 0b00100010);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x10], 0xff);
         assert_eq!(cpu.status, 0b10100000);
     }
 
     #[test]
     fn test_dec_absolute() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0xce, 0x33, 0x34, 0xce, 0x33, 0x34]);
-        cpu.PC = 0;
+        cpu.pc = 0;
         cpu.memory[0x3433] = 0x01;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x3433], 0x00);
         assert_eq!(cpu.status, 0b00100010);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x3433], 0xff);
         assert_eq!(cpu.status, 0b10100000);
     }
 
     #[test]
     fn test_dec_absolute_x() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0xde, 0x32, 0x34, 0xde, 0x32, 0x34]);
-        cpu.PC = 0;
-        cpu.X = 0x1;
+        cpu.pc = 0;
+        cpu.x = 0x1;
         cpu.memory[0x3433] = 0x01;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x3433], 0x00);
         assert_eq!(cpu.status, 0b00100010);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x3433], 0xff);
         assert_eq!(cpu.status, 0b10100000);
     }
@@ -364,89 +474,89 @@ mod test {
     // INX
     #[test]
     fn test_inx() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0xe8, 0xe8]);
-        cpu.X = 0xfe;
-        cpu.PC = 0x0;
-        cpu.execute();
-        assert_eq!(cpu.X, 0xff);
+        cpu.x = 0xfe;
+        cpu.pc = 0x0;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.x, 0xff);
         assert_eq!(cpu.status, 0b10100000);
-        cpu.execute();
-        assert_eq!(cpu.X, 0x00);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.x, 0x00);
         assert_eq!(cpu.status, 0b00100010);
     }
 
     // INY
     #[test]
     fn test_iny() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0xc8, 0xc8]);
-        cpu.Y = 0xfe;
-        cpu.PC = 0x0;
-        cpu.execute();
-        assert_eq!(cpu.Y, 0xff);
+        cpu.y = 0xfe;
+        cpu.pc = 0x0;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.y, 0xff);
         assert_eq!(cpu.status, 0b10100000);
-        cpu.execute();
-        assert_eq!(cpu.Y, 0x00);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.y, 0x00);
         assert_eq!(cpu.status, 0b00100010);
     }
 
     // INC
     #[test]
     fn test_inc_zeropage() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0xe6, 0x10, 0xe6, 0x10]);
-        cpu.PC = 0;
+        cpu.pc = 0;
         cpu.memory[0x10] = 0xfe;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x10], 0xff);
         assert_eq!(cpu.status, 0b10100000);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x10], 0x00);
         assert_eq!(cpu.status, 0b00100010);
     }
 
     #[test]
     fn test_inc_zeropagex() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0xf6, 0x05, 0xf6, 0x05]);
-        cpu.PC = 0;
-        cpu.X = 0x0b;
+        cpu.pc = 0;
+        cpu.x = 0x0b;
         cpu.memory[0x10] = 0xfe;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x10], 0xff);
         assert_eq!(cpu.status, 0b10100000);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x10], 0x00);
         assert_eq!(cpu.status, 0b00100010);
     }
 
     #[test]
     fn test_inc_absolute() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0xee, 0x12, 0x20, 0xee, 0x12, 0x20]);
-        cpu.PC = 0;
+        cpu.pc = 0;
         cpu.memory[0x2012] = 0xfe;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x2012], 0xff);
         assert_eq!(cpu.status, 0b10100000);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x2012], 0x00);
         assert_eq!(cpu.status, 0b00100010);
     }
 
     #[test]
     fn test_inc_absolute_x() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0xfe, 0x12, 0x20, 0xfe, 0x12, 0x20]);
-        cpu.PC = 0;
-        cpu.X = 0x10;
+        cpu.pc = 0;
+        cpu.x = 0x10;
         cpu.memory[0x2012] = 0xde;
         cpu.memory[0x2022] = 0xfe;
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x2022], 0xff);
         assert_eq!(cpu.status, 0b10100000);
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.memory[0x2022], 0x00);
         assert_eq!(cpu.status, 0b00100010);
     }
@@ -454,115 +564,115 @@ mod test {
     // TSX
     #[test]
     fn test_tsx() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0xba, 0xba, 0xba]);
-        cpu.SP = 0x22;
-        cpu.PC = 0;
-        cpu.execute();
-        assert_eq!(cpu.SP, 0x22);
-        assert_eq!(cpu.X, 0x22);
+        cpu.sp = 0x22;
+        cpu.pc = 0;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.sp, 0x22);
+        assert_eq!(cpu.x, 0x22);
         assert_eq!(cpu.status, 0b00100000);
 
-        cpu.SP = 0x00;
-        cpu.execute();
-        assert_eq!(cpu.SP, 0x00);
-        assert_eq!(cpu.X, 0x00);
+        cpu.sp = 0x00;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.sp, 0x00);
+        assert_eq!(cpu.x, 0x00);
         assert_eq!(cpu.status, 0b00100010);
 
-        cpu.SP = 0xfa;
-        cpu.execute();
-        assert_eq!(cpu.SP, 0xfa);
-        assert_eq!(cpu.X, 0xfa);
+        cpu.sp = 0xfa;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.sp, 0xfa);
+        assert_eq!(cpu.x, 0xfa);
         assert_eq!(cpu.status, 0b10100000);
     }
 
     // TXS
     #[test]
     fn test_txs() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0, &[0x9a, 0x9a, 0x9a]);
-        cpu.X = 0x22;
-        cpu.PC = 0;
-        cpu.execute();
-        assert_eq!(cpu.SP, 0x22);
-        assert_eq!(cpu.X, 0x22);
+        cpu.x = 0x22;
+        cpu.pc = 0;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.sp, 0x22);
+        assert_eq!(cpu.x, 0x22);
         assert_eq!(cpu.status, 0b00100000);
 
-        cpu.X = 0x00;
-        cpu.execute();
-        assert_eq!(cpu.SP, 0x00);
-        assert_eq!(cpu.X, 0x00);
+        cpu.x = 0x00;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.sp, 0x00);
+        assert_eq!(cpu.x, 0x00);
         assert_eq!(cpu.status, 0b00100000);
 
-        cpu.X = 0xfa;
-        cpu.execute();
-        assert_eq!(cpu.SP, 0xfa);
-        assert_eq!(cpu.X, 0xfa);
+        cpu.x = 0xfa;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.sp, 0xfa);
+        assert_eq!(cpu.x, 0xfa);
         assert_eq!(cpu.status, 0b00100000);
     }
 
     // CLC, CLD, CLI, CLV, SEC, SED, SEI
     #[test]
     fn test_flags_instructions() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x600, &[0x38, 0xf8, 0x78,    0x18, 0xd8, 0x58, 0xb8]);
-        cpu.PC = 0x600;
+        cpu.pc = 0x600;
 
-        cpu.execute(); cpu.execute(); cpu.execute();
+        cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap();
         assert_eq!(cpu.status, 0b00101101);
 
-        cpu.execute(); cpu.execute(); cpu.execute(); cpu.execute();
+        cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap();
         assert_eq!(cpu.status, 0b00100000);
     }
 
     #[test]
     fn test_php_plp() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         // Set all flags, push status, clear all flags, pull status
         cpu.load_at(0x0, &[0x38, 0xf8, 0x78, 0x08, 0x18, 0xd8, 0x58, 0xb8, 0x28]);
-        cpu.PC = 0;
+        cpu.pc = 0;
 
-        cpu.execute(); cpu.execute(); cpu.execute(); cpu.execute();
+        cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap();
         assert_eq!(cpu.status, 0b00101101);
-        assert_eq!(cpu.SP, 0xfe);
+        assert_eq!(cpu.sp, 0xfe);
 
-        cpu.execute(); cpu.execute(); cpu.execute(); cpu.execute();
+        cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap();
         assert_eq!(cpu.status, 0b00100000);
 
-        cpu.execute();
+        cpu.execute().unwrap();
         assert_eq!(cpu.status, 0b00101101);
-        assert_eq!(cpu.SP, 0xff);
+        assert_eq!(cpu.sp, 0xff);
     }
 
 
     #[test]
     fn test_pha_pla() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x0, &[0x48, 0x48, 0x68, 0x68]); // push, push, pull, pull
-        cpu.PC = 0;
-        cpu.A = 0xfd;
-        cpu.execute();
-        assert_eq!(cpu.SP, 0xfe);
-
-        cpu.A = 0x00;
-        cpu.execute();
-        assert_eq!(cpu.SP, 0xfd);
-
-        cpu.execute();
-        assert_eq!(cpu.SP, 0xfe);
-        assert_eq!(cpu.A, 0x00);
+        cpu.pc = 0;
+        cpu.a = 0xfd;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.sp, 0xfe);
+
+        cpu.a = 0x00;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.sp, 0xfd);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.sp, 0xfe);
+        assert_eq!(cpu.a, 0x00);
         assert_eq!(cpu.status, 0b00100010);
 
-        cpu.execute();
-        assert_eq!(cpu.SP, 0xff);
-        assert_eq!(cpu.A, 0xfd);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.sp, 0xff);
+        assert_eq!(cpu.a, 0xfd);
         assert_eq!(cpu.status, 0b10100000);
     }
 
     // BCC/BCS, BNE/BEQ, BPL/BMI, BVC/BVS
     #[test]
     fn test_branching() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         // lda #$11
         // sec
         // bcs label_1
@@ -581,15 +691,15 @@ mod test {
         // label_2:
         // tax
         cpu.load_at(0x600, &[0xa9, 0x11, 0x38, 0xb0, 0x06, 0xa9, 0x22, 0x10, 0x07, 0xa9, 0x22, 0x18, 0x90, 0xf9, 0xa9, 0x22, 0xaa]);
-        cpu.PC = 0x600;
-        cpu.execute(); cpu.execute(); cpu.execute(); cpu.execute(); cpu.execute(); cpu.execute(); cpu.execute();
-        assert_eq!(cpu.A, 0x11);
-        assert_eq!(cpu.X, 0x11);
+        cpu.pc = 0x600;
+        cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x11);
+        assert_eq!(cpu.x, 0x11);
     }
 
     #[test]
     fn test_push_and_pull_word() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.push_word_to_stack(0xdead);
         assert_eq!(cpu.get_byte(0x1ff), 0xde);
         assert_eq!(cpu.get_byte(0x1fe), 0xad);
@@ -599,58 +709,118 @@ mod test {
 
     #[test]
     fn test_jsr_rts() { // TODO: behavior is not consistent with easy6502. Maybe the site's version is wrong
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         // jsr label
         // lda #$22
         // label:
         // lda #$11
         // rts
         cpu.load_at(0x600, &[0x20, 0x05, 0x06, 0xa9, 0x22, 0xa9, 0x11, 0x60]);
-        cpu.execute(); cpu.execute();
-        assert_eq!(cpu.A, 0x11);
-        cpu.execute(); cpu.execute();
-        assert_eq!(cpu.A, 0x22);
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x11);
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x22);
     }
 
     // JMP
     #[test]
     fn test_jmp_indirect() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x600, &[0x6c, 0x03, 0x06, 0x12, 0x20]);
-        cpu.PC = 0x600;
-        cpu.execute();
-        assert_eq!(cpu.PC, 0x2012);
+        cpu.pc = 0x600;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0x2012);
         assert_eq!(cpu.status, 0b00100000);
     }
 
+    #[test]
+    fn test_jmp_indirect_page_wrap() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x6c, 0xff, 0x20]);
+        cpu.memory[0x20ff] = 0x34;
+        cpu.memory[0x2000] = 0x12;
+        cpu.memory[0x2100] = 0x56;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0x1234);
+
+        cpu.load_at(0x1234, &[0x6c, 0xff, 0xff]);
+        cpu.memory[0xffff] = 0x00;
+        cpu.memory[0xff00] = 0x06;
+        cpu.memory[0x0000] = 0x07;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0x0600);
+    }
+
+    #[test]
+    fn test_jmp_indirect_cmos() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        cpu.load_at(0x600, &[0x6c, 0xff, 0x20]);
+        cpu.memory[0x20ff] = 0x34;
+        cpu.memory[0x2000] = 0x12;
+        cpu.memory[0x2100] = 0x56;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0x5634);
+
+        cpu.load_at(0x5634, &[0x6c, 0xff, 0xff]);
+        cpu.memory[0xffff] = 0x00;
+        cpu.memory[0xff00] = 0x06;
+        cpu.memory[0x0000] = 0x07;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0x0700);
+    }
+
     #[test]
     fn test_jmp_absolute() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         cpu.load_at(0x600, &[0x4c, 0x12, 0x20]);
-        cpu.PC = 0x600;
-        cpu.execute();
-        assert_eq!(cpu.PC, 0x2012);
+        cpu.pc = 0x600;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0x2012);
         assert_eq!(cpu.status, 0b00100000);
     }
 
     #[test]
     fn test_asl() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         // lda #$31
         // asl A
         // sta label
         // asl label
         // label:
         cpu.load_at(0x600, &[0xa9, 0x31, 0x0a, 0x8d, 0x09, 0x06, 0x0e, 0x09, 0x06]);
-        cpu.PC = 0x600;
-        cpu.execute(); cpu.execute(); cpu.execute(); cpu.execute();
-        assert_eq!(cpu.A, 0x62);
+        cpu.pc = 0x600;
+        cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x62);
         assert_eq!(cpu.get_byte(0x609), 0xc4);
     }
 
+    #[test]
+    fn test_cmp_indirect_x() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0xc1, 0x20]);
+        cpu.a = 0x40;
+        cpu.x = 0x04;
+        cpu.load_at(0x24, &[0x74, 0x20]);
+        cpu.memory[0x2074] = 0x40;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.status, 0b00100011);
+    }
+
+    #[test]
+    fn test_cmp_indirect_y() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0xd1, 0x86]);
+        cpu.a = 0x40;
+        cpu.y = 0x10;
+        cpu.load_at(0x86, &[0x28, 0x40]);
+        cpu.memory[0x4038] = 0x41;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.status, 0b10100000);
+    }
+
     #[test]
     fn test_cmp() {
-        let mut cpu = CPU::new();
+        let mut cpu = Cpu::new();
         // jmp label
         // value: nop
         // label:
@@ -665,12 +835,1603 @@ mod test {
         cpu.load_at(0x600, &[
             0x4c, 0x04, 0x06, 0x20, 0xa9, 0x20, 0x8d, 0x03, 0x06, 0xa9, 0x1a, 0xcd, 0x03,
             0x06, 0xa9, 0x45, 0xcd, 0x03, 0x06, 0xa9, 0xff, 0xcd, 0x03, 0x06]);
-        cpu.PC = 0x600;
-        cpu.execute(); cpu.execute(); cpu.execute(); cpu.execute(); cpu.execute();
+        cpu.pc = 0x600;
+        cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap();
         assert_eq!(cpu.status, 0b10100000);
-        cpu.execute(); cpu.execute();
+        cpu.execute().unwrap(); cpu.execute().unwrap();
         assert_eq!(cpu.status, 0b00100001);
-        cpu.execute(); cpu.execute();
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.status, 0b10100001);
+    }
+
+    // ADC
+    #[test]
+    fn test_adc_binary() {
+        let mut cpu = Cpu::new();
+        // lda #$50
+        // adc #$50
+        // adc #$5f
+        cpu.load_at(0x600, &[0xa9, 0x50, 0x69, 0x50, 0x69, 0x5f]);
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0xa0);
+        assert_eq!(cpu.status, 0b11100000);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0xff);
+        assert_eq!(cpu.status, 0b10100000);
+    }
+
+    #[test]
+    fn test_adc_carry() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x65, 0x10, 0x65, 0x10]);
+        cpu.a = 0xff;
+        cpu.memory[0x10] = 0x01;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x00);
+        assert_eq!(cpu.status, 0b00100011);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x02);
+        assert_eq!(cpu.status, 0b00100000);
+    }
+
+    #[test]
+    fn test_adc_decimal() {
+        let mut cpu = Cpu::new();
+        // sed
+        // sec
+        // lda #$58
+        // adc #$46
+        cpu.load_at(0x600, &[0xf8, 0x38, 0xa9, 0x58, 0x69, 0x46]);
+        cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x05);
+        assert_eq!(cpu.status, 0b11101001);
+    }
+
+    #[test]
+    fn test_adc_decimal_invalid_bcd() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x69, 0x00, 0x69, 0x00]);
+        cpu.set_flag(Flags::D, true);
+        cpu.a = 0x0f;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x15);
+        assert_eq!(cpu.status, 0b00101000);
+
+        // Z is taken from the binary result, not from the adjusted one
+        cpu.a = 0x99;
+        cpu.memory[0x603] = 0x01;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x00);
+        assert_eq!(cpu.status, 0b10101001);
+    }
+
+    // SBC
+    #[test]
+    fn test_sbc_binary() {
+        let mut cpu = Cpu::new();
+        // sec
+        // lda #$50
+        // sbc #$b0
+        // sbc #$00
+        cpu.load_at(0x600, &[0x38, 0xa9, 0x50, 0xe9, 0xb0, 0xe9, 0x00]);
+        cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0xa0);
+        assert_eq!(cpu.status, 0b11100000);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x9f);
         assert_eq!(cpu.status, 0b10100001);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_sbc_decimal() {
+        let mut cpu = Cpu::new();
+        // sed
+        // sec
+        // lda #$46
+        // sbc #$12
+        // sbc #$43
+        cpu.load_at(0x600, &[0xf8, 0x38, 0xa9, 0x46, 0xe9, 0x12, 0xe9, 0x43]);
+        cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x34);
+        assert_eq!(cpu.status, 0b00101001);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x91);
+        assert_eq!(cpu.status, 0b10101000);
+    }
+
+    // AND
+    #[test]
+    fn test_and() {
+        let mut cpu = Cpu::new();
+        // lda #$f0
+        // and #$3c
+        // and $10
+        cpu.load_at(0x600, &[0xa9, 0xf0, 0x29, 0x3c, 0x25, 0x10]);
+        cpu.memory[0x10] = 0x0f;
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x30);
+        assert_eq!(cpu.status, 0b00100000);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x00);
+        assert_eq!(cpu.status, 0b00100010);
+    }
+
+    #[test]
+    fn test_and_absolute_x() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x3d, 0x00, 0x20]);
+        cpu.a = 0xff;
+        cpu.x = 0x04;
+        cpu.memory[0x2004] = 0x81;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x81);
+        assert_eq!(cpu.status, 0b10100000);
+    }
+
+    // ORA
+    #[test]
+    fn test_ora() {
+        let mut cpu = Cpu::new();
+        // lda #$00
+        // ora #$00
+        // ora $2000,y
+        cpu.load_at(0x600, &[0xa9, 0x00, 0x09, 0x00, 0x19, 0x00, 0x20]);
+        cpu.y = 0x02;
+        cpu.memory[0x2002] = 0x90;
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x00);
+        assert_eq!(cpu.status, 0b00100010);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x90);
+        assert_eq!(cpu.status, 0b10100000);
+    }
+
+    // EOR
+    #[test]
+    fn test_eor() {
+        let mut cpu = Cpu::new();
+        // lda #$ff
+        // eor #$0f
+        // eor $10,x
+        cpu.load_at(0x600, &[0xa9, 0xff, 0x49, 0x0f, 0x55, 0x0e]);
+        cpu.x = 0x02;
+        cpu.memory[0x10] = 0xf0;
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0xf0);
+        assert_eq!(cpu.status, 0b10100000);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x00);
+        assert_eq!(cpu.status, 0b00100010);
+    }
+
+    // BIT
+    #[test]
+    fn test_bit() {
+        let mut cpu = Cpu::new();
+        // bit $10
+        // bit $2000
+        cpu.load_at(0x600, &[0x24, 0x10, 0x2c, 0x00, 0x20]);
+        cpu.a = 0x01;
+        cpu.memory[0x10] = 0xc0;
+        cpu.memory[0x2000] = 0x41;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x01);
+        assert_eq!(cpu.status, 0b11100010);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.status, 0b01100000);
+    }
+
+    // LSR
+    #[test]
+    fn test_lsr_accumulator() {
+        let mut cpu = Cpu::new();
+        // lda #$03
+        // lsr A
+        // lsr A
+        cpu.load_at(0x600, &[0xa9, 0x03, 0x4a, 0x4a]);
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x01);
+        assert_eq!(cpu.status, 0b00100001);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x00);
+        assert_eq!(cpu.status, 0b00100011);
+    }
+
+    #[test]
+    fn test_lsr_zeropage() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x46, 0x10]);
+        cpu.memory[0x10] = 0x82;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x10], 0x41);
+        assert_eq!(cpu.status, 0b00100000);
+    }
+
+    #[test]
+    fn test_lsr_zeropage_x() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x56, 0x0e]);
+        cpu.x = 0x02;
+        cpu.memory[0x10] = 0x01;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x10], 0x00);
+        assert_eq!(cpu.status, 0b00100011);
+    }
+
+    #[test]
+    fn test_lsr_absolute() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x4e, 0x33, 0x20]);
+        cpu.memory[0x2033] = 0xff;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x2033], 0x7f);
+        assert_eq!(cpu.status, 0b00100001);
+    }
+
+    #[test]
+    fn test_lsr_absolute_x() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x5e, 0x30, 0x20]);
+        cpu.x = 0x03;
+        cpu.memory[0x2033] = 0x80;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x2033], 0x40);
+        assert_eq!(cpu.status, 0b00100000);
+    }
+
+    // ROL
+    #[test]
+    fn test_rol_accumulator() {
+        let mut cpu = Cpu::new();
+        // lda #$81
+        // rol A
+        // rol A
+        cpu.load_at(0x600, &[0xa9, 0x81, 0x2a, 0x2a]);
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x02);
+        assert_eq!(cpu.status, 0b00100001);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x05);
+        assert_eq!(cpu.status, 0b00100000);
+    }
+
+    #[test]
+    fn test_rol_zeropage() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x26, 0x10]);
+        cpu.memory[0x10] = 0x80;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x10], 0x00);
+        assert_eq!(cpu.status, 0b00100011);
+    }
+
+    #[test]
+    fn test_rol_zeropage_x() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x36, 0x0e]);
+        cpu.x = 0x02;
+        cpu.set_flag(Flags::C, true);
+        cpu.memory[0x10] = 0x40;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x10], 0x81);
+        assert_eq!(cpu.status, 0b10100000);
+    }
+
+    #[test]
+    fn test_rol_absolute() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x2e, 0x33, 0x20]);
+        cpu.memory[0x2033] = 0xc0;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x2033], 0x80);
+        assert_eq!(cpu.status, 0b10100001);
+    }
+
+    #[test]
+    fn test_rol_absolute_x() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x3e, 0x30, 0x20]);
+        cpu.x = 0x03;
+        cpu.set_flag(Flags::C, true);
+        cpu.memory[0x2033] = 0x01;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x2033], 0x03);
+        assert_eq!(cpu.status, 0b00100000);
+    }
+
+    // ROR
+    #[test]
+    fn test_ror_accumulator() {
+        let mut cpu = Cpu::new();
+        // lda #$81
+        // ror A
+        // ror A
+        cpu.load_at(0x600, &[0xa9, 0x81, 0x6a, 0x6a]);
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x40);
+        assert_eq!(cpu.status, 0b00100001);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0xa0);
+        assert_eq!(cpu.status, 0b10100000);
+    }
+
+    #[test]
+    fn test_ror_zeropage() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x66, 0x10]);
+        cpu.memory[0x10] = 0x01;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x10], 0x00);
+        assert_eq!(cpu.status, 0b00100011);
+    }
+
+    #[test]
+    fn test_ror_zeropage_x() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x76, 0x0e]);
+        cpu.x = 0x02;
+        cpu.set_flag(Flags::C, true);
+        cpu.memory[0x10] = 0x02;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x10], 0x81);
+        assert_eq!(cpu.status, 0b10100000);
+    }
+
+    #[test]
+    fn test_ror_absolute() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x6e, 0x33, 0x20]);
+        cpu.memory[0x2033] = 0x03;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x2033], 0x01);
+        assert_eq!(cpu.status, 0b00100001);
+    }
+
+    #[test]
+    fn test_ror_absolute_x() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x7e, 0x30, 0x20]);
+        cpu.x = 0x03;
+        cpu.set_flag(Flags::C, true);
+        cpu.memory[0x2033] = 0x80;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x2033], 0xc0);
+        assert_eq!(cpu.status, 0b10100000);
+    }
+
+    // BRK, RTI
+    #[test]
+    fn test_brk_rti() {
+        let mut cpu = Cpu::new();
+        // sec
+        // brk
+        // .byte $ff
+        // lda #$11
+        cpu.load_at(0x600, &[0x38, 0x00, 0xff, 0xa9, 0x11]);
+        // handler: clc; rti
+        cpu.load_at(0x2000, &[0x18, 0x40]);
+        cpu.load_at(IRQ_VECTOR as usize, &[0x00, 0x20]);
+
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0x2000);
+        assert_eq!(cpu.sp, 0xfc);
+        assert_eq!(cpu.get_word(0x1fe), 0x603);
+        assert_eq!(cpu.get_byte(0x1fd), 0b00110001);
+        assert_eq!(cpu.status, 0b00100101);
+
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0x603);
+        assert_eq!(cpu.sp, 0xff);
+        assert_eq!(cpu.status, 0b00100001);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x11);
+    }
+
+    #[test]
+    fn test_php_pushes_break_flag() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x08]);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.get_byte(0x1ff), 0b00110000);
+        assert_eq!(cpu.status, 0b00100000);
+    }
+
+    #[test]
+    fn test_irq() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(IRQ_VECTOR as usize, &[0x00, 0x20]);
+        cpu.set_flag(Flags::I, true);
+        cpu.irq();
+        assert_eq!(cpu.pc, 0x600);
+        assert_eq!(cpu.sp, 0xff);
+
+        cpu.set_flag(Flags::I, false);
+        cpu.set_flag(Flags::C, true);
+        cpu.irq();
+        assert_eq!(cpu.pc, 0x2000);
+        assert_eq!(cpu.sp, 0xfc);
+        assert_eq!(cpu.get_word(0x1fe), 0x600);
+        assert_eq!(cpu.get_byte(0x1fd), 0b00100001);
+        assert_eq!(cpu.status, 0b00100101);
+    }
+
+    #[test]
+    fn test_nmi() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(NMI_VECTOR as usize, &[0x34, 0x12]);
+        // rti
+        cpu.load_at(0x1234, &[0x40]);
+        cpu.set_flag(Flags::I, true);
+        cpu.nmi();
+        assert_eq!(cpu.pc, 0x1234);
+        assert_eq!(cpu.get_byte(0x1fd), 0b00100100);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0x600);
+        assert_eq!(cpu.sp, 0xff);
+        assert_eq!(cpu.status, 0b00100100);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(RESET_VECTOR as usize, &[0x00, 0x80]);
+        cpu.reset();
+        assert_eq!(cpu.pc, 0x8000);
+        assert_eq!(cpu.sp, 0xfc);
+        assert_eq!(cpu.status, 0b00100100);
+    }
+
+    // Address wrapping
+    #[test]
+    fn test_stack_wrap() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x48, 0x48, 0x68, 0x68]); // push, push, pull, pull
+        cpu.sp = 0x00;
+        cpu.a = 0x11;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x100], 0x11);
+        assert_eq!(cpu.sp, 0xff);
+
+        cpu.a = 0x22;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x1ff], 0x22);
+        assert_eq!(cpu.sp, 0xfe);
+
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x22);
+        assert_eq!(cpu.sp, 0xff);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x11);
+        assert_eq!(cpu.sp, 0x00);
+    }
+
+    #[test]
+    fn test_absolute_indexed_wrap() {
+        let mut cpu = Cpu::new();
+        // lda $ffff,x
+        // ldx $fff0,y
+        cpu.load_at(0x600, &[0xbd, 0xff, 0xff, 0xbe, 0xf0, 0xff]);
+        cpu.x = 0x02;
+        cpu.y = 0x20;
+        cpu.memory[0x0001] = 0x42;
+        cpu.memory[0x0010] = 0x24;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x42);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.x, 0x24);
+    }
+
+    #[test]
+    fn test_pc_wrap() {
+        let mut cpu = Cpu::new();
+        // nop at $ffff, then lda #$33 at $0000
+        cpu.memory[0xffff] = 0xea;
+        cpu.load_at(0x0000, &[0xa9, 0x33]);
+        cpu.pc = 0xffff;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0x0000);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x33);
+
+        // lda #$44 with its operand on the other side of the wrap
+        cpu.load_at(0xffff, &[0xa9, 0x44]);
+        cpu.pc = 0xffff;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x44);
+        assert_eq!(cpu.pc, 0x0001);
+    }
+
+    #[test]
+    fn test_branch_wrap() {
+        let mut cpu = Cpu::new();
+        // clc, then bcc from $0001 jumping back 6 bytes to $fffd
+        cpu.load_at(0x0000, &[0x18, 0x90, 0xfa]);
+        cpu.pc = 0x0000;
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0xfffd);
+
+        // bcc from $fffd jumping 4 bytes forward to $0003
+        cpu.load_at(0xfffd, &[0x90, 0x04]);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0x0003);
+    }
+
+    #[test]
+    fn test_jsr_rts_wrap() {
+        let mut cpu = Cpu::new();
+        // jsr $2000 at $fffe, rts
+        cpu.load_at(0xfffe, &[0x20, 0x00, 0x20]);
+        cpu.load_at(0x2000, &[0x60]);
+        cpu.pc = 0xfffe;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0x2000);
+        assert_eq!(cpu.get_word(0x1fe), 0x0000);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0x0001);
+    }
+
+    #[test]
+    fn test_jsr_overwrites_operand() {
+        let mut cpu = Cpu::new();
+        // jsr $1200 at $01fc, the return address gets pushed over the high byte before it's read
+        cpu.load_at(0x1fc, &[0x20, 0x00, 0x12]);
+        cpu.pc = 0x1fc;
+        cpu.sp = 0xfe;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0x0100);
+        assert_eq!(cpu.sp, 0xfc);
+    }
+
+    // RAM that logs every access as (address, value, write)
+    struct RecordingBus {
+        ram: Ram,
+        log: Vec<(u16, u8, bool)>
+    }
+
+    impl Bus for RecordingBus {
+        fn read(&mut self, addr: u16) -> u8 {
+            let val = self.ram.read(addr);
+            self.log.push((addr, val, false));
+            val
+        }
+
+        fn write(&mut self, addr: u16, val: u8) {
+            self.ram.write(addr, val);
+            self.log.push((addr, val, true));
+        }
+
+        fn peek(&self, addr: u16) -> u8 {
+            self.ram.peek(addr)
+        }
+    }
+
+    #[test]
+    fn test_bus_access_order() {
+        let mut cpu = Cpu::with_bus(RecordingBus { ram: Ram::new(), log: vec![] }, Variant::NMOS);
+        // lda $1234
+        // jmp ($10ff)
+        cpu.load_at(0x600, &[0xad, 0x34, 0x12, 0x6c, 0xff, 0x10]);
+        cpu.load_at(0x10ff, &[0x00]);
+        cpu.load_at(0x1000, &[0x07]);
+        // jsr $1234
+        cpu.load_at(0x700, &[0x20, 0x34, 0x12]);
+        cpu.bus_mut().log.clear();
+
+        // Words are read low byte first
+        cpu.execute().unwrap();
+        assert_eq!(cpu.bus().log, vec![(0x600, 0xad, false), (0x601, 0x34, false), (0x602, 0x12, false), (0x1234, 0x00, false)]);
+        cpu.bus_mut().log.clear();
+        cpu.execute().unwrap();
+        assert_eq!(cpu.bus().log, vec![(0x603, 0x6c, false), (0x604, 0xff, false), (0x605, 0x10, false),
+                                       (0x10ff, 0x00, false), (0x1000, 0x07, false)]);
+        cpu.bus_mut().log.clear();
+
+        // JSR pushes the return address before it reads the high byte of the target
+        cpu.execute().unwrap();
+        assert_eq!(cpu.bus().log, vec![(0x700, 0x20, false), (0x701, 0x34, false),
+                                       (0x1ff, 0x07, true), (0x1fe, 0x02, true), (0x702, 0x12, false)]);
+        assert_eq!(cpu.pc, 0x1234);
+    }
+
+    // Cycles
+    #[test]
+    fn test_cycles_exact() {
+        let mut cpu = Cpu::new();
+        // lda #$01
+        // sta $2000,x
+        // inc $2000
+        cpu.load_at(0x600, &[0xa9, 0x01, 0x9d, 0xff, 0x20, 0xee, 0x00, 0x20]);
+        cpu.x = 0x01;
+        assert_eq!(cpu.execute().unwrap().cycles, 2);
+        assert_eq!(cpu.execute().unwrap().cycles, 5);
+        assert_eq!(cpu.execute().unwrap().cycles, 6);
+        assert_eq!(cpu.cycles, 13);
+    }
+
+    #[test]
+    fn test_cycles_page_boundary() {
+        let mut cpu = Cpu::new();
+        // lda $20f0,x
+        // lda $20f0,y
+        // lda ($10),y
+        // lda ($10),y
+        cpu.load_at(0x600, &[0xbd, 0xf0, 0x20, 0xb9, 0xf0, 0x20, 0xb1, 0x10, 0xb1, 0x10]);
+        cpu.load_at(0x10, &[0xf0, 0x20]);
+        cpu.x = 0x0f;
+        cpu.y = 0x10;
+        assert_eq!(cpu.execute().unwrap().cycles, 4);
+        assert_eq!(cpu.execute().unwrap().cycles, 5);
+        assert_eq!(cpu.execute().unwrap().cycles, 6);
+        cpu.y = 0x0f;
+        assert_eq!(cpu.execute().unwrap().cycles, 5);
+        assert_eq!(cpu.cycles, 20);
+    }
+
+    #[test]
+    fn test_cycles_branching() {
+        let mut cpu = Cpu::new();
+        // bcs *+2 (not taken)
+        // bcc *+2 (taken)
+        // bcc $070e (taken, crosses a page)
+        cpu.load_at(0x6f8, &[0xb0, 0x00, 0x90, 0x00, 0x90, 0x10]);
+        cpu.pc = 0x6f8;
+        assert_eq!(cpu.execute().unwrap().cycles, 2);
+        assert_eq!(cpu.execute().unwrap().cycles, 3);
+        assert_eq!(cpu.execute().unwrap().cycles, 4);
+        assert_eq!(cpu.pc, 0x70e);
+        assert_eq!(cpu.cycles, 9);
+    }
+
+    #[test]
+    fn test_cycles_interrupts() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x00]);
+        assert_eq!(cpu.execute().unwrap().cycles, 7);
+        cpu.nmi();
+        cpu.irq(); // masked by BRK
+        assert_eq!(cpu.cycles, 14);
+    }
+
+    // Errors
+    #[test]
+    fn test_illegal_opcode() {
+        let mut cpu = Cpu::new();
+        cpu.set_undocumented_opcodes(false);
+        cpu.load_at(0x600, &[0xff]);
+        assert_eq!(cpu.execute(), Err(ExecutionError::IllegalOpcode { addr: 0x600, opcode: 0xff }));
+        assert_eq!(cpu.pc, 0x600);
+        assert_eq!(cpu.cycles, 0);
+    }
+
+    #[test]
+    fn test_jam() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0xea, 0x02]);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.execute(), Err(ExecutionError::Jam { addr: 0x601, opcode: 0x02 }));
+        assert_eq!(cpu.execute(), Err(ExecutionError::Jam { addr: 0x601, opcode: 0x02 }));
+        assert_eq!(cpu.pc, 0x601);
+    }
+
+    #[test]
+    fn test_step_info() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0xa9, 0x01]);
+        let info = cpu.execute().unwrap();
+        assert_eq!(info, StepInfo { addr: 0x600, opcode: 0xa9, instruction: Instruction::LDA, cycles: 2 });
+    }
+
+    #[test]
+    fn test_strict_stack_overflow() {
+        let mut cpu = Cpu::new();
+        // pha, jsr $0600
+        cpu.load_at(0x600, &[0x48, 0x20, 0x00, 0x06]);
+        cpu.set_strict(true);
+        cpu.sp = 0x01;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.sp, 0x00);
+        assert_eq!(cpu.execute(), Err(ExecutionError::StackOverflow { addr: 0x601, sp: 0x00 }));
+        assert_eq!(cpu.pc, 0x601);
+        assert_eq!(cpu.sp, 0x00);
+
+        cpu.set_strict(false);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.sp, 0xfe);
+    }
+
+    #[test]
+    fn test_strict_stack_underflow() {
+        let mut cpu = Cpu::new();
+        // pla, rts
+        cpu.load_at(0x600, &[0x68, 0x60]);
+        cpu.set_strict(true);
+        cpu.sp = 0xfe;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.execute(), Err(ExecutionError::StackUnderflow { addr: 0x601, sp: 0xff }));
+        assert_eq!(cpu.sp, 0xff);
+    }
+
+    // Bus
+    struct LoggingBus {
+        ram: Ram,
+        writes: Vec<(u16, u8)>
+    }
+
+    impl Bus for LoggingBus {
+        fn read(&mut self, addr: u16) -> u8 {
+            // $fe reads as a fixed value, the rest is plain RAM
+            if addr == 0xfe { 0x42 } else { self.ram.read(addr) }
+        }
+
+        fn write(&mut self, addr: u16, val: u8) {
+            self.writes.push((addr, val));
+            self.ram.write(addr, val);
+        }
+
+        fn peek(&self, addr: u16) -> u8 {
+            self.ram.peek(addr)
+        }
+    }
+
+    #[test]
+    fn test_custom_bus() {
+        let mut cpu = Cpu::with_bus(LoggingBus { ram: Ram::new(), writes: vec![] }, Variant::NMOS);
+        // lda $fe
+        // sta $0200
+        cpu.load_at(0x600, &[0xa5, 0xfe, 0x8d, 0x00, 0x02]);
+        cpu.bus_mut().writes.clear();
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x42);
+        assert_eq!(cpu.bus().writes, vec![(0x0200, 0x42)]);
+        assert_eq!(cpu.bus().peek(0x0200), 0x42);
+    }
+
+    // Run loops
+    #[test]
+    fn test_run_for_cycles() {
+        let mut cpu = Cpu::new();
+        // loop: inx; jmp loop
+        cpu.load_at(0x600, &[0xe8, 0x4c, 0x00, 0x06]);
+        let summary = cpu.run_for_cycles(50);
+        assert_eq!(summary, RunSummary { instructions: 20, cycles: 50, stop: StopReason::CycleLimit });
+        assert_eq!(cpu.x, 10);
+
+        // the last instruction is allowed to overshoot
+        let summary = cpu.run_for_cycles(1);
+        assert_eq!(summary, RunSummary { instructions: 1, cycles: 2, stop: StopReason::CycleLimit });
+    }
+
+    #[test]
+    fn test_run_until() {
+        let mut cpu = Cpu::new();
+        // loop: inx; jmp loop
+        cpu.load_at(0x600, &[0xe8, 0x4c, 0x00, 0x06]);
+        let summary = cpu.run_until(|cpu| cpu.x() == 3);
+        assert_eq!(summary, RunSummary { instructions: 5, cycles: 12, stop: StopReason::Condition });
+        assert_eq!(cpu.pc, 0x601);
+
+        let summary = cpu.run_until(|cpu| cpu.x() == 3);
+        assert_eq!(summary.instructions, 0);
+    }
+
+    #[test]
+    fn test_run_until_pc() {
+        let mut cpu = Cpu::new();
+        // jsr sub
+        // lda #$11
+        // sub: rts
+        cpu.load_at(0x600, &[0x20, 0x05, 0x06, 0xa9, 0x11, 0x60]);
+        let summary = cpu.run_until_pc(0x603);
+        assert_eq!(summary, RunSummary { instructions: 2, cycles: 12, stop: StopReason::Condition });
+        assert_eq!(cpu.a, 0x00);
+    }
+
+    #[test]
+    fn test_run_until_brk() {
+        let mut cpu = Cpu::new();
+        // lda #$11
+        // brk
+        cpu.load_at(0x600, &[0xa9, 0x11, 0x00]);
+        let summary = cpu.run_until_brk();
+        assert_eq!(summary, RunSummary { instructions: 1, cycles: 2, stop: StopReason::Brk { addr: 0x602 } });
+        assert_eq!(cpu.pc, 0x602);
+        assert_eq!(cpu.sp, 0xff);
+    }
+
+    #[test]
+    fn test_run_starting_on_brk_or_breakpoint() {
+        let mut cpu = Cpu::new();
+        // nop
+        // brk
+        cpu.load_at(0x600, &[0xea, 0x00]);
+        let id = cpu.breakpoints_mut().add_breakpoint(0x600);
+        cpu.breakpoints_mut().add_breakpoint(0x601);
+
+        // A breakpoint at PC doesn't stop the run, BRK at PC does
+        let summary = cpu.run_until_brk();
+        assert_eq!(summary, RunSummary { instructions: 1, cycles: 2, stop: StopReason::Brk { addr: 0x601 } });
+        assert_eq!(cpu.run_until_brk(), RunSummary { instructions: 0, cycles: 0, stop: StopReason::Brk { addr: 0x601 } });
+        assert_eq!(cpu.breakpoints().get(id).unwrap().hits, 0);
+    }
+
+    #[test]
+    fn test_resume_until() {
+        let mut cpu = Cpu::new();
+        // brk
+        // irq: inx
+        //      inx
+        cpu.load_at(0x600, &[0x00]);
+        cpu.load_at(IRQ_VECTOR as usize, &[0x00, 0x07]);
+        cpu.load_at(0x700, &[0xe8, 0xe8]);
+        cpu.set_stop_conditions(StopConditions { brk: true, trap: false });
+        assert_eq!(cpu.run_until(|_| true).stop, StopReason::Condition);
+        assert_eq!(cpu.run_until(|_| false).stop, StopReason::Brk { addr: 0x600 });
+
+        // The BRK at PC runs, the predicate is checked from the next instruction on
+        let summary = cpu.resume_until(|_| true);
+        assert_eq!(summary, RunSummary { instructions: 1, cycles: 7, stop: StopReason::Condition });
+        assert_eq!((cpu.pc, cpu.x), (0x700, 0));
+        let id = cpu.breakpoints_mut().add_breakpoint(0x701);
+        assert_eq!(cpu.resume_until(|_| false).stop, StopReason::Breakpoint { id, addr: 0x701 });
+        assert_eq!(cpu.x, 1);
+    }
+
+    #[test]
+    fn test_run_until_trap() {
+        let mut cpu = Cpu::new();
+        // lda #$00
+        // trap: beq trap
+        cpu.load_at(0x600, &[0xa9, 0x00, 0xf0, 0xfe]);
+        let summary = cpu.run_until_brk();
+        assert_eq!(summary, RunSummary { instructions: 2, cycles: 5, stop: StopReason::Trap { addr: 0x602 } });
+
+        // jmp * is a trap as well
+        cpu.load_at(0x700, &[0x4c, 0x00, 0x07]);
+        cpu.pc = 0x700;
+        assert_eq!(cpu.run_until_brk().stop, StopReason::Trap { addr: 0x700 });
+    }
+
+    #[test]
+    fn test_run_stop_conditions() {
+        let mut cpu = Cpu::new();
+        // nop
+        // brk
+        cpu.load_at(0x600, &[0xea, 0x00]);
+        cpu.load_at(IRQ_VECTOR as usize, &[0x00, 0x20]);
+        cpu.load_at(0x2000, &[0x4c, 0x00, 0x20]);
+        let summary = cpu.run_for_cycles(20);
+        assert_eq!(summary.stop, StopReason::CycleLimit);
+        assert_eq!(cpu.pc, 0x2000);
+
+        cpu.pc = 0x600;
+        cpu.set_stop_conditions(StopConditions { brk: true, trap: false });
+        assert_eq!(cpu.run_for_cycles(20).stop, StopReason::Brk { addr: 0x601 });
+
+        cpu.pc = 0x2000;
+        cpu.set_stop_conditions(StopConditions { brk: false, trap: true });
+        assert_eq!(cpu.run_for_cycles(20).stop, StopReason::Trap { addr: 0x2000 });
+    }
+
+    #[test]
+    fn test_run_error() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0xea, 0x02]);
+        let summary = cpu.run_for_cycles(100);
+        assert_eq!(summary.stop, StopReason::Error(ExecutionError::Jam { addr: 0x601, opcode: 0x02 }));
+        assert_eq!(summary.instructions, 1);
+    }
+
+    // Undocumented opcodes
+    #[test]
+    fn test_undocumented_opcodes_disabled() {
+        let mut cpu = Cpu::new();
+        cpu.set_undocumented_opcodes(false);
+        // lda #$01
+        // sbc #$01 (undocumented encoding)
+        // nop $10
+        cpu.load_at(0x600, &[0xa9, 0x01, 0xeb, 0x01, 0x04, 0x10, 0x02]);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.execute(), Err(ExecutionError::IllegalOpcode { addr: 0x602, opcode: 0xeb }));
+        cpu.pc = 0x604;
+        assert_eq!(cpu.execute(), Err(ExecutionError::IllegalOpcode { addr: 0x604, opcode: 0x04 }));
+        cpu.pc = 0x606;
+        assert_eq!(cpu.execute(), Err(ExecutionError::Jam { addr: 0x606, opcode: 0x02 }));
+
+        cpu.set_undocumented_opcodes(true);
+        cpu.pc = 0x602;
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0xff);
+        assert_eq!(cpu.pc, 0x606);
+    }
+
+    #[test]
+    fn test_illegal_opcode_leaves_bus_untouched() {
+        let bus = MemoryMap::builder().ram(0x0000..=0x07ff).build().unwrap();
+        let mut cpu = Cpu::with_bus(bus, Variant::NMOS);
+        cpu.set_undocumented_opcodes(false);
+        // slo ($10,x), the pointer would be read while resolving the operand
+        cpu.load_at(0x600, &[0x03, 0x10]);
+        cpu.load_at(0x10, &[0x00, 0x02]);
+        cpu.set_pc(0x600);
+        cpu.breakpoints_mut().add_watchpoint(0x0010..=0x0011, Access::Read);
+        cpu.set_byte(0x0000, 0x5a);
+
+        assert_eq!(cpu.execute(), Err(ExecutionError::IllegalOpcode { addr: 0x600, opcode: 0x03 }));
+        // Unmapped addresses read back the last value on the data bus
+        assert_eq!(cpu.bus().peek(0x8000), 0x5a);
+        assert_eq!(cpu.run_for_cycles(10).stop, StopReason::Error(ExecutionError::IllegalOpcode { addr: 0x600, opcode: 0x03 }));
+    }
+
+    #[test]
+    fn test_undocumented_nops() {
+        let mut cpu = Cpu::new();
+        // nop
+        // nop #$ff
+        // nop $10
+        // nop $10,x
+        // nop $2000
+        // nop $20ff,x
+        cpu.load_at(0x600, &[0x1a, 0x80, 0xff, 0x04, 0x10, 0x14, 0x10, 0x0c, 0x00, 0x20, 0x1c, 0xff, 0x20]);
+        cpu.x = 0x01;
+        assert_eq!(cpu.execute().unwrap().cycles, 2);
+        assert_eq!(cpu.execute().unwrap().cycles, 2);
+        assert_eq!(cpu.execute().unwrap().cycles, 3);
+        assert_eq!(cpu.execute().unwrap().cycles, 4);
+        assert_eq!(cpu.execute().unwrap().cycles, 4);
+        assert_eq!(cpu.execute().unwrap().cycles, 5);
+        assert_eq!(cpu.pc, 0x60d);
+        assert_eq!(cpu.status, 0b00100000);
+    }
+
+    #[test]
+    fn test_slo() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x07, 0x10]);
+        cpu.a = 0x01;
+        cpu.memory[0x10] = 0x81;
+        assert_eq!(cpu.execute().unwrap().cycles, 5);
+        assert_eq!(cpu.memory[0x10], 0x02);
+        assert_eq!(cpu.a, 0x03);
+        assert_eq!(cpu.status, 0b00100001);
+    }
+
+    #[test]
+    fn test_rla() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x2f, 0x00, 0x20]);
+        cpu.a = 0xf0;
+        cpu.set_flag(Flags::C, true);
+        cpu.memory[0x2000] = 0xc0;
+        assert_eq!(cpu.execute().unwrap().cycles, 6);
+        assert_eq!(cpu.memory[0x2000], 0x81);
+        assert_eq!(cpu.a, 0x80);
+        assert_eq!(cpu.status, 0b10100001);
+    }
+
+    #[test]
+    fn test_sre() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x5b, 0x00, 0x20]);
+        cpu.a = 0x01;
+        cpu.y = 0x02;
+        cpu.memory[0x2002] = 0x03;
+        assert_eq!(cpu.execute().unwrap().cycles, 7);
+        assert_eq!(cpu.memory[0x2002], 0x01);
+        assert_eq!(cpu.a, 0x00);
+        assert_eq!(cpu.status, 0b00100011);
+    }
+
+    #[test]
+    fn test_rra() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x63, 0x20]);
+        cpu.a = 0x10;
+        cpu.x = 0x04;
+        cpu.load_at(0x24, &[0x00, 0x30]);
+        cpu.memory[0x3000] = 0x03;
+        assert_eq!(cpu.execute().unwrap().cycles, 8);
+        // ROR leaves 0x01 with the carry set, ADC adds both
+        assert_eq!(cpu.memory[0x3000], 0x01);
+        assert_eq!(cpu.a, 0x12);
+        assert_eq!(cpu.status, 0b00100000);
+    }
+
+    #[test]
+    fn test_rra_decimal() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x67, 0x10]);
+        cpu.memory[0x10] = 0x33;
+        cpu.status = 0b00101001;
+        cpu.execute().unwrap();
+        // ROR leaves $99 with the carry set, $00 + $99 + 1 wraps to $00 in decimal,
+        // but Z comes from the binary sum and N from the half-adjusted one
+        assert_eq!(cpu.memory[0x10], 0x99);
+        assert_eq!(cpu.a, 0x00);
+        assert_eq!(cpu.status, 0b10101001);
+    }
+
+    #[test]
+    fn test_sax() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x97, 0x10]);
+        cpu.a = 0xf0;
+        cpu.x = 0x3c;
+        cpu.y = 0x02;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x12], 0x30);
+        assert_eq!(cpu.status, 0b00100000);
+    }
+
+    #[test]
+    fn test_lax() {
+        let mut cpu = Cpu::new();
+        // lax ($10),y
+        // lax #$0f
+        cpu.load_at(0x600, &[0xb3, 0x10, 0xab, 0x0f]);
+        cpu.y = 0x10;
+        cpu.load_at(0x10, &[0xf8, 0x20]);
+        cpu.memory[0x2108] = 0x80;
+        assert_eq!(cpu.execute().unwrap().cycles, 6);
+        assert_eq!((cpu.a, cpu.x), (0x80, 0x80));
+        assert_eq!(cpu.status, 0b10100000);
+
+        cpu.execute().unwrap();
+        assert_eq!((cpu.a, cpu.x), (0x0e, 0x0e));
+        assert_eq!(cpu.status, 0b00100000);
+    }
+
+    #[test]
+    fn test_dcp() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0xc7, 0x10, 0xc7, 0x10]);
+        cpu.a = 0x40;
+        cpu.memory[0x10] = 0x41;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x10], 0x40);
+        assert_eq!(cpu.status, 0b00100011);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x10], 0x3f);
+        assert_eq!(cpu.status, 0b00100001);
+    }
+
+    #[test]
+    fn test_isc() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0xe7, 0x10]);
+        cpu.a = 0x40;
+        cpu.set_flag(Flags::C, true);
+        cpu.memory[0x10] = 0x3f;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x10], 0x40);
+        assert_eq!(cpu.a, 0x00);
+        assert_eq!(cpu.status, 0b00100011);
+    }
+
+    #[test]
+    fn test_anc_alr() {
+        let mut cpu = Cpu::new();
+        // anc #$f0
+        // alr #$81
+        cpu.load_at(0x600, &[0x0b, 0xf0, 0x4b, 0x81]);
+        cpu.a = 0x81;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x80);
+        assert_eq!(cpu.status, 0b10100001);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x40);
+        assert_eq!(cpu.status, 0b00100000);
+    }
+
+    #[test]
+    fn test_arr() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x6b, 0xff, 0x6b, 0xff]);
+        cpu.a = 0xc0;
+        cpu.execute().unwrap();
+        // bit 6 goes to C, bit 6 ^ bit 5 to V
+        assert_eq!(cpu.a, 0x60);
+        assert_eq!(cpu.status, 0b00100001);
+
+        cpu.a = 0x3f;
+        cpu.set_flag(Flags::C, true);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x9f);
+        assert_eq!(cpu.status, 0b10100000);
+    }
+
+    #[test]
+    fn test_arr_decimal() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0x6b, 0xff]);
+        cpu.set_flag(Flags::D, true);
+        cpu.a = 0x77;
+        cpu.execute().unwrap();
+        // 0x77 >> 1 = 0x3b, both nibbles get adjusted
+        assert_eq!(cpu.a, 0x91);
+        assert_eq!(cpu.status, 0b01101001);
+    }
+
+    #[test]
+    fn test_sbx() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0xcb, 0x10, 0xcb, 0x10]);
+        cpu.a = 0xf0;
+        cpu.x = 0x3c;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.x, 0x20);
+        assert_eq!(cpu.status, 0b00100001);
+        cpu.a = 0x00;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.x, 0xf0);
+        assert_eq!(cpu.status, 0b10100000);
+    }
+
+    #[test]
+    fn test_shx_shy() {
+        let mut cpu = Cpu::new();
+        // shx $2010,y
+        // shy $2010,x
+        // shx $20ff,y (page crossed, the high byte gets replaced)
+        cpu.load_at(0x600, &[0x9e, 0x10, 0x20, 0x9c, 0x10, 0x20, 0x9e, 0xff, 0x20]);
+        cpu.x = 0xff;
+        cpu.y = 0x01;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x2011], 0x21);
+        cpu.y = 0xff;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x210f], 0x21);
+        cpu.x = 0x13;
+        cpu.y = 0x01;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x0100], 0x01);
+    }
+
+    #[test]
+    fn test_tas_las() {
+        let mut cpu = Cpu::new();
+        // tas $2000,y
+        // las $2000,y
+        cpu.load_at(0x600, &[0x9b, 0x00, 0x20, 0xbb, 0x00, 0x20]);
+        cpu.a = 0xf3;
+        cpu.x = 0x3f;
+        cpu.y = 0x05;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.sp, 0x33);
+        assert_eq!(cpu.memory[0x2005], 0x21);
+
+        cpu.memory[0x2005] = 0xf1;
+        cpu.execute().unwrap();
+        assert_eq!((cpu.a, cpu.x, cpu.sp), (0x31, 0x31, 0x31));
+        assert_eq!(cpu.status, 0b00100000);
+    }
+
+    // WDC 65C02
+    #[test]
+    fn test_cmos_reserved_nops() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        // 0x03 is a single byte NOP, 0x02 takes an immediate, 0x5c an absolute address
+        cpu.load_at(0x600, &[0x03, 0x02, 0xff, 0x5c, 0x00, 0x20, 0xcb]);
+        assert_eq!(cpu.execute().unwrap().cycles, 1);
+        assert_eq!(cpu.execute().unwrap().cycles, 2);
+        assert_eq!(cpu.execute().unwrap().cycles, 8);
+        assert_eq!(cpu.pc, 0x606);
+
+        cpu.set_undocumented_opcodes(false);
+        cpu.pc = 0x600;
+        assert_eq!(cpu.execute(), Err(ExecutionError::IllegalOpcode { addr: 0x600, opcode: 0x03 }));
+    }
+
+    #[test]
+    fn test_zeropage_indirect() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        // lda ($10)
+        // sta ($ff)
+        cpu.load_at(0x600, &[0xb2, 0x10, 0x92, 0xff]);
+        cpu.load_at(0x10, &[0x00, 0x20]);
+        cpu.memory[0xff] = 0x00;
+        cpu.memory[0x00] = 0x30;
+        cpu.memory[0x2000] = 0x80;
+        assert_eq!(cpu.execute().unwrap().cycles, 5);
+        assert_eq!(cpu.a, 0x80);
+        assert_eq!(cpu.status, 0b10100000);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x3000], 0x80);
+    }
+
+    #[test]
+    fn test_jmp_absolute_indirect_x() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        cpu.load_at(0x600, &[0x7c, 0xfe, 0x20]);
+        cpu.x = 0x01;
+        cpu.load_at(0x20ff, &[0x34, 0x12]);
+        assert_eq!(cpu.execute().unwrap().cycles, 6);
+        assert_eq!(cpu.pc, 0x1234);
+    }
+
+    #[test]
+    fn test_bra() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        // bra +$02
+        // bra -$7f
+        cpu.load_at(0x600, &[0x80, 0x02, 0x00, 0x00, 0x80, 0x81]);
+        assert_eq!(cpu.execute().unwrap().cycles, 3);
+        assert_eq!(cpu.pc, 0x604);
+        assert_eq!(cpu.execute().unwrap().cycles, 4);
+        assert_eq!(cpu.pc, 0x587);
+    }
+
+    #[test]
+    fn test_phx_phy_plx_ply() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        // phx
+        // phy
+        // plx
+        // ply
+        cpu.load_at(0x600, &[0xda, 0x5a, 0xfa, 0x7a]);
+        cpu.x = 0x80;
+        cpu.y = 0x00;
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.sp, 0xfd);
+        assert_eq!(cpu.execute().unwrap().cycles, 4);
+        assert_eq!(cpu.x, 0x00);
+        assert_eq!(cpu.status, 0b00100010);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.y, 0x80);
+        assert_eq!(cpu.sp, 0xff);
+        assert_eq!(cpu.status, 0b10100000);
+    }
+
+    #[test]
+    fn test_stz() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        // stz $10
+        // stz $2000,x
+        cpu.load_at(0x600, &[0x64, 0x10, 0x9e, 0x00, 0x20]);
+        cpu.x = 0x05;
+        cpu.memory[0x10] = 0xff;
+        cpu.memory[0x2005] = 0xff;
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x10], 0x00);
+        assert_eq!(cpu.memory[0x2005], 0x00);
+    }
+
+    #[test]
+    fn test_trb_tsb() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        // tsb $10
+        // trb $10
+        cpu.load_at(0x600, &[0x04, 0x10, 0x14, 0x10]);
+        cpu.a = 0x0f;
+        cpu.memory[0x10] = 0xf0;
+        assert_eq!(cpu.execute().unwrap().cycles, 5);
+        assert_eq!(cpu.memory[0x10], 0xff);
+        assert_eq!(cpu.status, 0b00100010);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x10], 0xf0);
+        assert_eq!(cpu.status, 0b00100000);
+    }
+
+    #[test]
+    fn test_inc_dec_accumulator() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        // inc a
+        // dec a
+        // dec a
+        cpu.load_at(0x600, &[0x1a, 0x3a, 0x3a]);
+        cpu.a = 0xff;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x00);
+        assert_eq!(cpu.status, 0b00100010);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0xff);
+        assert_eq!(cpu.status, 0b10100000);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0xfe);
+    }
+
+    #[test]
+    fn test_bit_cmos() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        // bit #$c0
+        // bit $10,x
+        cpu.load_at(0x600, &[0x89, 0xc0, 0x34, 0x10]);
+        cpu.a = 0x01;
+        cpu.x = 0x02;
+        cpu.memory[0x12] = 0xc1;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.status, 0b00100010);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.status, 0b11100000);
+    }
+
+    #[test]
+    fn test_rmb_smb() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        // rmb0 $10
+        // smb7 $10
+        cpu.load_at(0x600, &[0x07, 0x10, 0xf7, 0x10]);
+        cpu.memory[0x10] = 0x01;
+        assert_eq!(cpu.execute().unwrap().cycles, 5);
+        assert_eq!(cpu.memory[0x10], 0x00);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.memory[0x10], 0x80);
+        assert_eq!(cpu.status, 0b00100000);
+    }
+
+    #[test]
+    fn test_bbr_bbs() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        // bbr2 $10, +$05
+        // bbs2 $10, -$03
+        cpu.load_at(0x600, &[0x2f, 0x10, 0x05, 0xaf, 0x10, 0xfd]);
+        cpu.memory[0x10] = 0b00000100;
+        assert_eq!(cpu.execute().unwrap().cycles, 5);
+        assert_eq!(cpu.pc, 0x603);
+        assert_eq!(cpu.execute().unwrap().cycles, 6);
+        assert_eq!(cpu.pc, 0x603);
+
+        cpu.memory[0x10] = 0x00;
+        cpu.pc = 0x600;
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0x608);
+    }
+
+    #[test]
+    fn test_wai() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        // cli
+        // wai
+        // sei
+        // wai
+        // nop
+        cpu.load_at(0x600, &[0x58, 0xcb, 0x78, 0xcb, 0xea]);
+        cpu.load_at(IRQ_VECTOR as usize, &[0x02, 0x06]);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.execute().unwrap().cycles, 3);
+        assert_eq!(cpu.pc, 0x602);
+        let info = cpu.execute().unwrap();
+        assert_eq!(info, StepInfo { addr: 0x601, opcode: 0xcb, instruction: Instruction::WAI, cycles: 1 });
+        assert_eq!(cpu.pc, 0x602);
+
+        // The interrupt returns to the instruction after WAI
+        cpu.irq();
+        assert_eq!(cpu.pc, 0x602);
+        assert_eq!(cpu.sp, 0xfc);
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0x604);
+
+        // A masked IRQ just resumes execution
+        cpu.execute().unwrap();
+        cpu.irq();
+        assert_eq!(cpu.execute().unwrap().instruction, Instruction::NOP);
+        assert_eq!(cpu.sp, 0xfc);
+    }
+
+    #[test]
+    fn test_stp() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        cpu.load_at(0x600, &[0xdb]);
+        cpu.load_at(RESET_VECTOR as usize, &[0x00, 0x07]);
+        assert_eq!(cpu.execute(), Err(ExecutionError::Stopped { addr: 0x600 }));
+        assert_eq!(cpu.execute(), Err(ExecutionError::Stopped { addr: 0x600 }));
+        cpu.reset();
+        assert_eq!(cpu.pc, 0x700);
+    }
+
+    #[test]
+    fn test_adc_sbc_decimal_cmos() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        // adc #$01
+        // sbc #$21
+        cpu.load_at(0x600, &[0x69, 0x01, 0xe9, 0x21]);
+        cpu.set_flag(Flags::D, true);
+        cpu.a = 0x99;
+        // N and Z are valid on the 65C02, at the cost of an extra cycle
+        assert_eq!(cpu.execute().unwrap().cycles, 3);
+        assert_eq!(cpu.a, 0x00);
+        assert_eq!(cpu.status, 0b00101011);
+
+        assert_eq!(cpu.execute().unwrap().cycles, 3);
+        assert_eq!(cpu.a, 0x79);
+        assert_eq!(cpu.status, 0b00101000);
+    }
+
+    #[test]
+    fn test_brk_clears_decimal_cmos() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        cpu.load_at(0x600, &[0x00]);
+        cpu.load_at(IRQ_VECTOR as usize, &[0x00, 0x07]);
+        cpu.set_flag(Flags::D, true);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc, 0x700);
+        assert_eq!(cpu.status, 0b00100100);
+        assert_eq!(cpu.memory[0x1fd], 0b00111000);
+    }
+
+    #[test]
+    fn test_shift_absolute_x_cycles_cmos() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        // asl $2000,x
+        // asl $20ff,x
+        // inc $2000,x
+        cpu.load_at(0x600, &[0x1e, 0x00, 0x20, 0x1e, 0xff, 0x20, 0xfe, 0x00, 0x20]);
+        cpu.x = 0x01;
+        assert_eq!(cpu.execute().unwrap().cycles, 6);
+        assert_eq!(cpu.execute().unwrap().cycles, 7);
+        assert_eq!(cpu.execute().unwrap().cycles, 7);
+    }
+
+    // Ricoh 2A03
+    #[test]
+    fn test_decimal_disabled_2a03() {
+        let mut cpu = Cpu::with_variant(Variant::RP2A03);
+        // sed
+        // lda #$09
+        // adc #$01
+        // sbc #$01
+        // cld
+        cpu.load_at(0x600, &[0xf8, 0xa9, 0x09, 0x69, 0x01, 0xe9, 0x01, 0xd8]);
+        cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x0a);
+        assert_eq!(cpu.status, 0b00101000);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.a, 0x08);
+        assert_eq!(cpu.status, 0b00101001);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.status, 0b00100001);
+    }
+
+    // Tracing
+    #[derive(Clone, Default)]
+    struct SharedBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_trace_line() {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0xc000, &[0x4c, 0xf5, 0xc5]);
+        cpu.pc = 0xc000;
+        cpu.sp = 0xfd;
+        cpu.status = 0x24;
+        cpu.cycles = 7;
+        assert_eq!(cpu.trace_line(), "C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD CYC:7");
+
+        cpu.load_at(0xd959, &[0xb1, 0x89]);
+        cpu.load_at(0x89, &[0x00, 0x03]);
+        cpu.memory[0x300] = 0x89;
+        cpu.pc = 0xd959;
+        cpu.cycles = 0;
+        assert_eq!(cpu.trace_line(), "D959  B1 89     LDA ($89),Y = 0300 @ 0300 = 89  A:00 X:00 Y:00 P:24 SP:FD CYC:0");
+    }
+
+    #[test]
+    fn test_trace_line_undocumented() {
+        let mut cpu = Cpu::new();
+        // nop $a9
+        // isc $10
+        cpu.load_at(0x600, &[0x04, 0xa9, 0xe7, 0x10]);
+        assert!(cpu.trace_line().starts_with("0600  04 A9    *NOP $A9 = 00                    A:00"));
+        cpu.pc = 0x602;
+        assert!(cpu.trace_line().starts_with("0602  E7 10    *ISB $10 = 00"));
+
+        cpu.set_undocumented_opcodes(false);
+        assert!(cpu.trace_line().starts_with("0602  E7        .byte $E7                       A:00"));
+    }
+
+    #[test]
+    fn test_trace_sink() {
+        let mut cpu = Cpu::new();
+        // lda #$01
+        // ldx #$02
+        // ldy #$03
+        cpu.load_at(0x600, &[0xa9, 0x01, 0xa2, 0x02, 0xa0, 0x03]);
+        let buffer = SharedBuffer::default();
+        cpu.set_trace(buffer.clone());
+        cpu.execute().unwrap();
+        cpu.set_tracing(false);
+        cpu.execute().unwrap();
+        cpu.set_tracing(true);
+        cpu.execute().unwrap();
+
+        let trace = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = trace.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("0600  A9 01     LDA #$01"));
+        assert!(lines[1].starts_with("0604  A0 03     LDY #$03"));
+        assert!(lines[1].ends_with("A:01 X:02 Y:00 P:20 SP:FF CYC:4"));
+
+        assert!(cpu.take_trace().is_some());
+        cpu.set_tracing(true);
+        cpu.pc = 0x600;
+        cpu.execute().unwrap();
+        assert_eq!(buffer.0.lock().unwrap().len(), trace.len());
+    }
+
+    // A traced CPU can still be handed to another thread
+    const _: () = {
+        const fn assert_send<T: Send>() {}
+        assert_send::<Cpu>();
+    };
+
+    #[test]
+    fn test_save_state() {
+        let mut cpu = Cpu::with_variant(Variant::CMOS);
+        // lda #$42
+        // sta $10
+        // ldx #$07
+        // wai
+        cpu.load_at(0x600, &[0xa9, 0x42, 0x85, 0x10, 0xa2, 0x07, 0xcb]);
+        for _ in 0..4 {
+            cpu.execute().unwrap();
+        }
+        let saved = cpu.save_state();
+
+        let mut other = Cpu::new();
+        other.load_state(&saved).unwrap();
+        assert_eq!(other.registers(), cpu.registers());
+        assert_eq!(other.cycles(), cpu.cycles());
+        assert_eq!(other.variant(), Variant::CMOS);
+        assert_eq!(other.get_byte(0x10), 0x42);
+        assert_eq!(other.get_byte(0x606), 0xcb);
+        // Still waiting for an interrupt
+        assert_eq!(other.execute().unwrap().instruction, Instruction::WAI);
+        assert_eq!(other.pc(), 0x607);
+
+        cpu.set_a(0);
+        cpu.set_byte(0x10, 0);
+        cpu.load_state(&saved).unwrap();
+        assert_eq!(cpu.a(), 0x42);
+        assert_eq!(cpu.get_byte(0x10), 0x42);
+        assert_eq!(cpu.save_state(), saved);
+    }
+
+    #[test]
+    fn test_load_state_errors() {
+        let mut cpu = Cpu::new();
+        let saved = cpu.save_state();
+        assert_eq!(cpu.load_state(b"NES\x1a"), Err(StateError::BadMagic));
+        assert_eq!(cpu.load_state(&saved[..saved.len() - 1]), Err(StateError::UnexpectedEnd));
+
+        let mut newer = saved.clone();
+        newer[4] = 0xff;
+        assert_eq!(cpu.load_state(&newer), Err(StateError::UnsupportedVersion(0x00ff)));
+
+        let mut longer = saved.clone();
+        longer.push(0);
+        assert_eq!(cpu.load_state(&longer), Err(StateError::TrailingData(1)));
+
+        // A failed load leaves the registers alone
+        cpu.set_a(0x12);
+        let mut state = cpu.state();
+        state.a = 0x34;
+        state.bus.truncate(100);
+        assert_eq!(cpu.restore_state(&state), Err(StateError::UnexpectedEnd));
+        assert_eq!(cpu.a(), 0x12);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_save_state_json() {
+        let mut cpu = Cpu::with_variant(Variant::RP2A03);
+        cpu.set_x(0x99);
+        cpu.set_byte(0x1234, 0x56);
+        let json = serde_json::to_string(&cpu.state()).unwrap();
+        let state: CpuState = serde_json::from_str(&json).unwrap();
+
+        let mut other = Cpu::new();
+        other.restore_state(&state).unwrap();
+        assert_eq!(other.x(), 0x99);
+        assert_eq!(other.get_byte(0x1234), 0x56);
+        assert_eq!(other.variant(), Variant::RP2A03);
+    }
+}
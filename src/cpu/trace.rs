@@ -0,0 +1,112 @@
+use std::io;
+use crate::bus::Bus;
+use crate::cpu::{Cpu, Variant};
+use crate::instructions::{AddressingMode, Instruction};
+
+impl<B: Bus> Cpu<B> {
+    // Starts writing a trace line before every instruction
+    pub fn set_trace<W: io::Write + Send + 'static>(&mut self, sink: W) {
+        self.trace = Some(Box::new(sink));
+        self.tracing = true;
+    }
+
+    // Pauses or resumes tracing without dropping the sink
+    pub fn set_tracing(&mut self, enabled: bool) {
+        self.tracing = enabled && self.trace.is_some();
+    }
+
+    pub fn take_trace(&mut self) -> Option<Box<dyn io::Write + Send>> {
+        self.tracing = false;
+        self.trace.take()
+    }
+
+    pub(super) fn write_trace(&mut self) {
+        let line = self.trace_line();
+        if let Some(sink) = self.trace.as_mut() {
+            // A broken sink shouldn't bring the emulation down with it
+            let _ = writeln!(sink, "{}", line);
+        }
+    }
+
+    // Describes the instruction at PC and the registers before it runs, in the nestest.log layout:
+    // C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD CYC:7
+    // There's no PPU here, so unlike nestest.log the line has no PPU column
+    pub fn trace_line(&self) -> String {
+        let pc = self.pc;
+        let byte = self.memory.peek(pc);
+        let (text, len) = self.disassembler().disassemble(&self.memory, pc);
+        let bytes: Vec<String> = (0..len).map(|i| format!("{:02X}", self.memory.peek(pc.wrapping_add(i)))).collect();
+
+        // nestest marks undocumented opcodes with a star and calls ISC ISB
+        let undocumented = self.variant.is_undocumented(byte) && self.undocumented_opcodes;
+        let text = if undocumented { text.replacen("ISC", "ISB", 1) } else { text };
+        let text = match self.trace_annotation() {
+            Some(annotation) if !text.starts_with(".byte") => format!("{} {}", text, annotation),
+            _ => text
+        };
+
+        format!("{:04X}  {:<8} {}{:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+                pc, bytes.join(" "), if undocumented { '*' } else { ' ' }, text,
+                self.a, self.x, self.y, self.status, self.sp, self.cycles)
+    }
+
+    // Effective address and memory contents the instruction is going to touch, like "@ 0300 = 89"
+    fn trace_annotation(&self) -> Option<String> {
+        let peek = |addr: u16| self.memory.peek(addr);
+        let peek_word = |lo: u16, hi: u16| ((peek(hi) as u16) << 8) | peek(lo) as u16;
+        let zero_page_word = |ptr: u8| peek_word(ptr as u16, ptr.wrapping_add(1) as u16);
+
+        let (instruction, mode, _) = self.variant.opcodes()[peek(self.pc) as usize];
+        let arg = peek(self.pc.wrapping_add(1));
+        let word = peek_word(self.pc.wrapping_add(1), self.pc.wrapping_add(2));
+
+        Some(match mode {
+            AddressingMode::ZeroPage => format!("= {:02X}", peek(arg as u16)),
+            AddressingMode::ZeroPageX => {
+                let addr = arg.wrapping_add(self.x);
+                format!("@ {:02X} = {:02X}", addr, peek(addr as u16))
+            }
+            AddressingMode::ZeroPageY => {
+                let addr = arg.wrapping_add(self.y);
+                format!("@ {:02X} = {:02X}", addr, peek(addr as u16))
+            }
+            AddressingMode::Absolute if matches!(instruction, Instruction::JMP | Instruction::JSR) => { return None; }
+            AddressingMode::Absolute => format!("= {:02X}", peek(word)),
+            AddressingMode::AbsoluteX => {
+                let addr = word.wrapping_add(self.x as u16);
+                format!("@ {:04X} = {:02X}", addr, peek(addr))
+            }
+            AddressingMode::AbsoluteY => {
+                let addr = word.wrapping_add(self.y as u16);
+                format!("@ {:04X} = {:02X}", addr, peek(addr))
+            }
+            AddressingMode::Indirect => {
+                let hi = match self.variant {
+                    Variant::CMOS => word.wrapping_add(1),
+                    Variant::NMOS | Variant::RP2A03 => (word & 0xff00) | (word.wrapping_add(1) & 0x00ff)
+                };
+                format!("= {:04X}", peek_word(word, hi))
+            }
+            AddressingMode::IndirectX => {
+                let ptr = arg.wrapping_add(self.x);
+                let addr = zero_page_word(ptr);
+                format!("@ {:02X} = {:04X} = {:02X}", ptr, addr, peek(addr))
+            }
+            AddressingMode::IndirectY => {
+                let base = zero_page_word(arg);
+                let addr = base.wrapping_add(self.y as u16);
+                format!("= {:04X} @ {:04X} = {:02X}", base, addr, peek(addr))
+            }
+            AddressingMode::ZeroPageIndirect => {
+                let addr = zero_page_word(arg);
+                format!("= {:04X} = {:02X}", addr, peek(addr))
+            }
+            AddressingMode::AbsoluteIndirectX => {
+                let ptr = word.wrapping_add(self.x as u16);
+                format!("@ {:04X} = {:04X}", ptr, peek_word(ptr, ptr.wrapping_add(1)))
+            }
+            AddressingMode::Implied | AddressingMode::Accumulator | AddressingMode::Immediate
+            | AddressingMode::Relative | AddressingMode::ZeroPageRelative => { return None; }
+        })
+    }
+}
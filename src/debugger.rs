@@ -0,0 +1,396 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use crate::breakpoints::{Access, BreakpointId, BreakpointKind, Condition, ConditionError};
+use crate::bus::Bus;
+use crate::cpu::{Cpu, Flags, StopReason};
+use crate::expr::parse_number;
+use crate::instructions::Instruction;
+
+// How much `x` shows when only the start address is given
+const EXAMINE_BYTES: u16 = 0x40;
+// Instructions shown before PC by `list` without an address, and in total
+const LIST_BEFORE_PC: u16 = 4;
+const LIST_LINES: u16 = 10;
+
+const HELP: &str = "\
+s, step [n]          execute n instructions (1 by default)
+n, next              step over JSR
+fin, finish          run until the current subroutine returns
+c, continue          run until a breakpoint, BRK or a trap
+b, break [addr]      set a breakpoint, or list breakpoints and watchpoints without an address
+w, watch [r|w|rw] start [end]
+                     stop after an instruction reads or writes memory (both by default)
+d, delete id         remove a breakpoint or watchpoint
+enable id, disable id
+r, regs              show the registers
+set name value       change a register (a, x, y, sp, pc, p) or a flag (c, z, i, d, b, v, n)
+x, examine start [end]
+                     dump memory
+fill start end byte  fill memory
+l, list [addr] [n]   disassemble n instructions, around PC without an address
+q, quit              leave the debugger
+Breakpoints and watchpoints take a condition at the end: b $0600 if A == $10 && [$00FE] > 3
+Numbers are decimal, $ or 0x for hex, % for binary. An empty line repeats the last command
+";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DebuggerError {
+    UnknownCommand(String),
+    MissingArgument(&'static str),
+    InvalidNumber(String),
+    OutOfRange(String), // Number is too big for what it's used for
+    InvalidRange(u16, u16), // End of a range is before its start
+    UnknownRegister(String),
+    Condition(ConditionError)
+}
+
+impl fmt::Display for DebuggerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DebuggerError::UnknownCommand(name) => write!(f, "unknown command '{}', try 'help'", name),
+            DebuggerError::MissingArgument(what) => write!(f, "missing {}", what),
+            DebuggerError::InvalidNumber(text) => write!(f, "'{}' isn't a number", text),
+            DebuggerError::OutOfRange(text) => write!(f, "{} is out of range", text),
+            DebuggerError::InvalidRange(start, end) => write!(f, "${:04X}-${:04X} is an empty range", start, end),
+            DebuggerError::UnknownRegister(name) => write!(f, "unknown register or flag '{}'", name),
+            DebuggerError::Condition(e) => write!(f, "invalid condition: {}", e)
+        }
+    }
+}
+
+impl std::error::Error for DebuggerError {}
+
+// What `set` can change
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    A,
+    X,
+    Y,
+    SP,
+    PC,
+    P,
+    Flag(Flags)
+}
+
+impl Target {
+    fn parse(name: &str) -> Result<Target, DebuggerError> {
+        Ok(match name.to_ascii_lowercase().as_str() {
+            "a" => Target::A,
+            "x" => Target::X,
+            "y" => Target::Y,
+            "sp" | "s" => Target::SP,
+            "pc" => Target::PC,
+            "p" => Target::P,
+            "c" => Target::Flag(Flags::C),
+            "z" => Target::Flag(Flags::Z),
+            "i" => Target::Flag(Flags::I),
+            "d" => Target::Flag(Flags::D),
+            "b" => Target::Flag(Flags::B),
+            "v" => Target::Flag(Flags::V),
+            "n" => Target::Flag(Flags::N),
+            _ => return Err(DebuggerError::UnknownRegister(name.to_string()))
+        })
+    }
+
+    fn max_value(self) -> u32 {
+        match self {
+            Target::PC => 0xffff,
+            Target::Flag(_) => 1,
+            _ => 0xff
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Step(u32),
+    Next,
+    Finish,
+    Continue,
+    Break(u16, Option<Condition>),
+    Watch(RangeInclusive<u16>, Access, Option<Condition>),
+    Delete(BreakpointId),
+    Enable(BreakpointId, bool),
+    Breakpoints,
+    Registers,
+    Set(Target, u16),
+    Examine(RangeInclusive<u16>),
+    Fill(RangeInclusive<u16>, u8),
+    List(Option<u16>, u16), // Start address, amount of instructions
+    Help,
+    Quit
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, DebuggerError> {
+        let mut words = line.split_whitespace();
+        let name = words.next().ok_or(DebuggerError::MissingArgument("command"))?;
+        let mut args: Vec<&str> = words.collect();
+
+        // Everything after `if` is a condition
+        let condition = match args.iter().position(|arg| arg.eq_ignore_ascii_case("if")) {
+            Some(i) => {
+                let condition = Condition::parse(&args[i + 1..].join(" ")).map_err(DebuggerError::Condition)?;
+                args.truncate(i);
+                Some(condition)
+            }
+            None => None
+        };
+        let arg = |i: usize, what: &'static str| args.get(i).copied().ok_or(DebuggerError::MissingArgument(what));
+
+        Ok(match name.to_ascii_lowercase().as_str() {
+            "s" | "step" => Command::Step(args.first().map(|n| number(n)).transpose()?.unwrap_or(1)),
+            "n" | "next" => Command::Next,
+            "fin" | "finish" => Command::Finish,
+            "c" | "cont" | "continue" => Command::Continue,
+            "b" | "break" => match args.first() {
+                Some(addr) => Command::Break(address(addr)?, condition),
+                None => Command::Breakpoints
+            },
+            "w" | "watch" => {
+                let (access, rest) = match args.first().map(|arg| arg.to_ascii_lowercase()).as_deref() {
+                    Some("r") => (Access::Read, &args[1..]),
+                    Some("w") => (Access::Write, &args[1..]),
+                    Some("rw") => (Access::ReadWrite, &args[1..]),
+                    _ => (Access::ReadWrite, &args[..])
+                };
+                let start = address(rest.first().ok_or(DebuggerError::MissingArgument("address"))?)?;
+                let end = rest.get(1).map(|end| address(end)).transpose()?.unwrap_or(start);
+                Command::Watch(range(start, end)?, access, condition)
+            }
+            "d" | "delete" => Command::Delete(number(arg(0, "id")?)? as BreakpointId),
+            "enable" => Command::Enable(number(arg(0, "id")?)? as BreakpointId, true),
+            "disable" => Command::Enable(number(arg(0, "id")?)? as BreakpointId, false),
+            "r" | "regs" | "registers" => Command::Registers,
+            "set" => {
+                let target = Target::parse(arg(0, "register")?)?;
+                let text = arg(1, "value")?;
+                let value = number(text)?;
+                if value > target.max_value() {
+                    return Err(DebuggerError::OutOfRange(text.to_string()));
+                }
+                Command::Set(target, value as u16)
+            }
+            "x" | "examine" => {
+                let start = address(arg(0, "address")?)?;
+                let end = match args.get(1) {
+                    Some(end) => address(end)?,
+                    None => start.saturating_add(EXAMINE_BYTES - 1)
+                };
+                Command::Examine(range(start, end)?)
+            }
+            "fill" => {
+                let range = range(address(arg(0, "start address")?)?, address(arg(1, "end address")?)?)?;
+                Command::Fill(range, byte(arg(2, "byte")?)?)
+            }
+            "l" | "list" => {
+                let start = args.first().map(|addr| address(addr)).transpose()?;
+                let count = args.get(1).map(|n| address(n)).transpose()?.unwrap_or(LIST_LINES);
+                Command::List(start, count)
+            }
+            "h" | "help" | "?" => Command::Help,
+            "q" | "quit" | "exit" => Command::Quit,
+            _ => return Err(DebuggerError::UnknownCommand(name.to_string()))
+        })
+    }
+}
+
+// Same notation as the assembler: decimal, $ (or 0x) for hex and % for binary
+// Same number syntax as the assembler and breakpoint conditions
+fn number(text: &str) -> Result<u32, DebuggerError> {
+    parse_number(text).and_then(|n| u32::try_from(n).ok()).ok_or_else(|| DebuggerError::InvalidNumber(text.to_string()))
+}
+
+fn address(text: &str) -> Result<u16, DebuggerError> {
+    u16::try_from(number(text)?).map_err(|_| DebuggerError::OutOfRange(text.to_string()))
+}
+
+fn byte(text: &str) -> Result<u8, DebuggerError> {
+    u8::try_from(number(text)?).map_err(|_| DebuggerError::OutOfRange(text.to_string()))
+}
+
+fn range(start: u16, end: u16) -> Result<RangeInclusive<u16>, DebuggerError> {
+    if end < start {
+        return Err(DebuggerError::InvalidRange(start, end));
+    }
+    Ok(start..=end)
+}
+
+// Runs debugger commands against a CPU. Breakpoints live in the CPU, so they're shared with everything else
+// that runs it. Commands return their output instead of printing it, so front ends decide where it goes
+#[derive(Default)]
+pub struct Debugger;
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger
+    }
+
+    pub fn run_command<B: Bus>(&mut self, cpu: &mut Cpu<B>, command: &Command) -> String {
+        match command {
+            Command::Step(count) => {
+                let mut executed = 0;
+                let note = self.resume(cpu, |_| {
+                    executed += 1;
+                    executed >= *count
+                });
+                note + &self.location(cpu)
+            }
+            Command::Next => {
+                if instruction_at_pc(cpu) != Instruction::JSR {
+                    return self.run_command(cpu, &Command::Step(1));
+                }
+                let (ret, sp) = (cpu.pc().wrapping_add(3), cpu.sp());
+                let note = self.resume(cpu, |cpu| cpu.pc() == ret && cpu.sp() == sp);
+                note + &self.location(cpu)
+            }
+            Command::Finish => {
+                // Run up to the RTS of the current frame, then past it. Calls on the way are counted rather than
+                // SP compared, so whatever the routine pushed doesn't hide its RTS. A JSR at PC runs unchecked
+                let mut depth = u32::from(instruction_at_pc(cpu) == Instruction::JSR);
+                let mut note = String::new();
+                if instruction_at_pc(cpu) != Instruction::RTS {
+                    note = self.resume(cpu, |cpu| match instruction_at_pc(cpu) {
+                        Instruction::RTS if depth == 0 => true,
+                        Instruction::RTS => { depth -= 1; false }
+                        Instruction::JSR => { depth += 1; false }
+                        _ => false
+                    });
+                }
+                if note.is_empty() {
+                    note = self.resume(cpu, |_| true);
+                }
+                note + &self.location(cpu)
+            }
+            Command::Continue => {
+                let note = self.resume(cpu, |_| false);
+                note + &self.location(cpu)
+            }
+            Command::Break(addr, condition) => {
+                let id = cpu.breakpoints_mut().add(BreakpointKind::Execute(*addr), condition.clone());
+                format!("Breakpoint {} at ${:04X}\n", id, addr)
+            }
+            Command::Watch(range, access, condition) => {
+                let id = cpu.breakpoints_mut().add(BreakpointKind::Watch(range.clone(), *access), condition.clone());
+                format!("Watchpoint {} on ${:04X}-${:04X}\n", id, range.start(), range.end())
+            }
+            Command::Delete(id) => {
+                if cpu.breakpoints_mut().remove(*id) {
+                    format!("Deleted {}\n", id)
+                } else {
+                    format!("No breakpoint {}\n", id)
+                }
+            }
+            Command::Enable(id, enabled) => {
+                if cpu.breakpoints_mut().set_enabled(*id, *enabled) {
+                    String::new()
+                } else {
+                    format!("No breakpoint {}\n", id)
+                }
+            }
+            Command::Breakpoints => {
+                if cpu.breakpoints().is_empty() {
+                    return String::from("No breakpoints\n");
+                }
+                cpu.breakpoints().iter().map(|breakpoint| format!("{}\n", breakpoint)).collect()
+            }
+            Command::Registers => registers(cpu),
+            Command::Set(target, value) => {
+                match target {
+                    Target::A => cpu.set_a(*value as u8),
+                    Target::X => cpu.set_x(*value as u8),
+                    Target::Y => cpu.set_y(*value as u8),
+                    Target::SP => cpu.set_sp(*value as u8),
+                    Target::PC => cpu.set_pc(*value),
+                    Target::P => cpu.set_status(Flags::from_bits_retain(*value as u8)),
+                    Target::Flag(flag) => cpu.set_flag(*flag, *value != 0)
+                }
+                registers(cpu)
+            }
+            Command::Examine(range) => cpu.dump_memory(range.clone()),
+            Command::Fill(range, byte) => {
+                for addr in range.clone() {
+                    cpu.set_byte(addr, *byte);
+                }
+                String::new()
+            }
+            Command::List(start, count) => self.list(cpu, *start, *count),
+            Command::Help => String::from(HELP),
+            Command::Quit => String::new()
+        }
+    }
+
+    // Instruction at PC and the registers
+    pub fn location<B: Bus>(&self, cpu: &Cpu<B>) -> String {
+        self.list(cpu, Some(cpu.pc()), 1) + &registers(cpu)
+    }
+
+    // Runs until `done` or something else stops the CPU. Returns why it stopped, unless it was because of `done`.
+    // `done` isn't checked before the first instruction, so every command makes progress
+    // The instruction at PC always runs, done is checked from the next one on
+    fn resume<B: Bus, F: FnMut(&Cpu<B>) -> bool>(&self, cpu: &mut Cpu<B>, done: F) -> String {
+        let summary = cpu.resume_until(done);
+
+        match summary.stop {
+            StopReason::Condition => String::new(),
+            StopReason::CycleLimit => String::from("Cycle limit reached\n"),
+            StopReason::Brk { addr } => format!("BRK at ${:04X}\n", addr),
+            StopReason::Trap { addr } => format!("Trapped at ${:04X}\n", addr),
+            StopReason::Breakpoint { id, addr } => format!("Breakpoint {} at ${:04X}\n", id, addr),
+            StopReason::Watchpoint { id, access: Access::Write, addr, value, pc } =>
+                format!("Watchpoint {}: ${:04X} wrote ${:02X} to ${:04X}\n", id, pc, value, addr),
+            StopReason::Watchpoint { id, addr, value, pc, .. } =>
+                format!("Watchpoint {}: ${:04X} read ${:02X} from ${:04X}\n", id, pc, value, addr),
+            StopReason::Error(e) => format!("{}\n", e)
+        }
+    }
+
+    fn list<B: Bus>(&self, cpu: &Cpu<B>, start: Option<u16>, count: u16) -> String {
+        let disassembler = cpu.disassembler();
+        let mut addr = start.unwrap_or_else(|| self.start_before(cpu, cpu.pc(), LIST_BEFORE_PC));
+        let mut out = String::new();
+
+        for _ in 0..count {
+            let line = disassembler.disassemble_range(cpu.bus(), addr..=addr).remove(0);
+            let breakpoint = if cpu.breakpoints().has_breakpoint_at(addr) { '*' } else { ' ' };
+            let current = if addr == cpu.pc() { '>' } else { ' ' };
+            out += &format!("{}{} {}\n", breakpoint, current, line);
+            addr = addr.wrapping_add(line.bytes.len() as u16);
+        }
+
+        out
+    }
+
+    // Instructions have different lengths, so there's no telling where the ones before `addr` start.
+    // Picks the earliest start that decodes into at most `count` instructions landing exactly on `addr`
+    fn start_before<B: Bus>(&self, cpu: &Cpu<B>, addr: u16, count: u16) -> u16 {
+        let disassembler = cpu.disassembler();
+        for back in (1..=count * 3).rev() {
+            let start = addr.wrapping_sub(back);
+            let (mut offset, mut instructions) = (0, 0);
+            while offset < back {
+                offset += disassembler.disassemble(cpu.bus(), start.wrapping_add(offset)).1;
+                instructions += 1;
+            }
+            if offset == back && instructions <= count {
+                return start;
+            }
+        }
+        addr
+    }
+}
+
+fn instruction_at_pc<B: Bus>(cpu: &Cpu<B>) -> Instruction {
+    cpu.variant().opcodes()[cpu.bus().peek(cpu.pc()) as usize].0
+}
+
+// Set flags are upper case: P:nV-BdIzc
+fn registers<B: Bus>(cpu: &Cpu<B>) -> String {
+    let status: String = "NV-BDIZC".chars().enumerate().map(|(i, name)| {
+        if cpu.status().bits() & (0x80 >> i) != 0 { name } else { name.to_ascii_lowercase() }
+    }).collect();
+    format!("PC:{:04X} A:{:02X} X:{:02X} Y:{:02X} SP:{:02X} P:{} CYC:{}\n",
+            cpu.pc(), cpu.a(), cpu.x(), cpu.y(), cpu.sp(), status, cpu.cycles())
+}
+
+mod test;
@@ -0,0 +1,221 @@
+#![allow(clippy::module_inception)]
+
+#[cfg(test)]
+mod test {
+    use super::super::*;
+    use crate::cpu::{Cpu, StopConditions, IRQ_VECTOR};
+
+    //     ldx #$00
+    //     jsr sub
+    //     inx
+    //     brk
+    //     nop
+    // sub:
+    //     lda #$05
+    //     jsr sub2
+    //     rts
+    // sub2:
+    //     inx
+    //     rts
+    fn cpu_with_subroutines() -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load_at(0x600, &[0xa2, 0x00, 0x20, 0x08, 0x06, 0xe8, 0x00, 0xea,
+                             0xa9, 0x05, 0x20, 0x0e, 0x06, 0x60, 0xe8, 0x60]);
+        cpu.set_stop_conditions(StopConditions { brk: true, trap: true });
+        cpu
+    }
+
+    fn run(debugger: &mut Debugger, cpu: &mut Cpu, line: &str) -> String {
+        debugger.run_command(cpu, &Command::parse(line).unwrap())
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Command::parse("s"), Ok(Command::Step(1)));
+        assert_eq!(Command::parse("step 10"), Ok(Command::Step(10)));
+        assert_eq!(Command::parse("b $0600"), Ok(Command::Break(0x600, None)));
+        assert_eq!(Command::parse("b $0600 if A == 1"), Ok(Command::Break(0x600, Some(Condition::parse("A == 1").unwrap()))));
+        assert_eq!(Command::parse("watch w $10 $1f"), Ok(Command::Watch(0x10..=0x1f, Access::Write, None)));
+        assert_eq!(Command::parse("w $10"), Ok(Command::Watch(0x10..=0x10, Access::ReadWrite, None)));
+        assert_eq!(Command::parse("d 2"), Ok(Command::Delete(2)));
+        assert_eq!(Command::parse("break"), Ok(Command::Breakpoints));
+        assert_eq!(Command::parse("x 0x10 %11111"), Ok(Command::Examine(0x10..=0x1f)));
+        assert_eq!(Command::parse("x $fff0"), Ok(Command::Examine(0xfff0..=0xffff)));
+        assert_eq!(Command::parse("set C 1"), Ok(Command::Set(Target::Flag(Flags::C), 1)));
+        assert_eq!(Command::parse("set pc $1234"), Ok(Command::Set(Target::PC, 0x1234)));
+
+        assert_eq!(Command::parse("frob"), Err(DebuggerError::UnknownCommand(String::from("frob"))));
+        assert_eq!(Command::parse(""), Err(DebuggerError::MissingArgument("command")));
+        assert_eq!(Command::parse("set a"), Err(DebuggerError::MissingArgument("value")));
+        assert_eq!(Command::parse("set a 256"), Err(DebuggerError::OutOfRange(String::from("256"))));
+        assert_eq!(Command::parse("set q 1"), Err(DebuggerError::UnknownRegister(String::from("q"))));
+        assert_eq!(Command::parse("b foo"), Err(DebuggerError::InvalidNumber(String::from("foo"))));
+        assert_eq!(Command::parse("fill $20 $10 0"), Err(DebuggerError::InvalidRange(0x20, 0x10)));
+        assert_eq!(Command::parse("b $0600 if A =="),
+                   Err(DebuggerError::Condition(ConditionError::Syntax(String::from("unexpected end of expression")))));
+    }
+
+    #[test]
+    fn test_step_next_finish() {
+        let mut debugger = Debugger::new();
+        let mut cpu = cpu_with_subroutines();
+        assert_eq!(run(&mut debugger, &mut cpu, "step"),
+                   " > 0602  20 08 06  JSR $0608\nPC:0602 A:00 X:00 Y:00 SP:FF P:nv-bdiZc CYC:2\n");
+        run(&mut debugger, &mut cpu, "next");
+        assert_eq!((cpu.pc(), cpu.a(), cpu.x(), cpu.sp()), (0x605, 0x05, 0x01, 0xff));
+
+        let mut cpu = cpu_with_subroutines();
+        run(&mut debugger, &mut cpu, "step 3");
+        assert_eq!(cpu.pc(), 0x60a);
+        run(&mut debugger, &mut cpu, "next");
+        assert_eq!(cpu.pc(), 0x60d);
+        // Already at the RTS
+        run(&mut debugger, &mut cpu, "finish");
+        assert_eq!((cpu.pc(), cpu.sp()), (0x605, 0xff));
+
+        let mut cpu = cpu_with_subroutines();
+        run(&mut debugger, &mut cpu, "step 4");
+        assert_eq!(cpu.pc(), 0x60e);
+        run(&mut debugger, &mut cpu, "finish");
+        assert_eq!((cpu.pc(), cpu.sp()), (0x60d, 0xfd));
+    }
+
+    #[test]
+    fn test_finish_after_push() {
+        let mut debugger = Debugger::new();
+        let mut cpu = Cpu::new();
+        //     jsr sub
+        //     inx
+        //     brk
+        //     nop
+        // sub:
+        //     pha
+        //     jsr sub2
+        //     pla
+        //     rts
+        // sub2:
+        //     inx
+        //     rts
+        cpu.load_at(0x600, &[0x20, 0x06, 0x06, 0xe8, 0x00, 0xea,
+                             0x48, 0x20, 0x0c, 0x06, 0x68, 0x60, 0xe8, 0x60]);
+        cpu.set_stop_conditions(StopConditions { brk: true, trap: true });
+
+        // SP is below the frame's return address after PHA, and the JSR at PC is a call of its own
+        run(&mut debugger, &mut cpu, "step 2");
+        assert_eq!((cpu.pc(), cpu.sp()), (0x607, 0xfc));
+        assert_eq!(run(&mut debugger, &mut cpu, "finish"),
+                   " > 0603  E8        INX\nPC:0603 A:00 X:01 Y:00 SP:FF P:nv-bdiZc CYC:33\n");
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut debugger = Debugger::new();
+        let mut cpu = cpu_with_subroutines();
+        assert_eq!(run(&mut debugger, &mut cpu, "b $060e"), "Breakpoint 1 at $060E\n");
+        run(&mut debugger, &mut cpu, "b $0600");
+        assert_eq!(run(&mut debugger, &mut cpu, "break"), "1: break $060E, 0 hits\n2: break $0600, 0 hits\n");
+
+        // The breakpoint on the current instruction doesn't stop it
+        assert!(run(&mut debugger, &mut cpu, "c").starts_with("Breakpoint 1 at $060E\n"));
+        assert_eq!(cpu.pc(), 0x60e);
+        // next stops at breakpoints inside the subroutine too
+        run(&mut debugger, &mut cpu, "set pc $060a");
+        run(&mut debugger, &mut cpu, "set sp $fd");
+        assert!(run(&mut debugger, &mut cpu, "n").starts_with("Breakpoint 1 at $060E\n"));
+
+        assert_eq!(run(&mut debugger, &mut cpu, "d 1"), "Deleted 1\n");
+        assert_eq!(run(&mut debugger, &mut cpu, "d 1"), "No breakpoint 1\n");
+        assert!(run(&mut debugger, &mut cpu, "c").starts_with("BRK at $0606\n"));
+        assert_eq!(cpu.breakpoints().iter().map(|breakpoint| breakpoint.id).collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn test_resume_from_brk() {
+        let mut debugger = Debugger::new();
+        let mut cpu = cpu_with_subroutines();
+        // irq: inx
+        //      rti
+        cpu.load_at(IRQ_VECTOR as usize, &[0x00, 0x07]);
+        cpu.load_at(0x700, &[0xe8, 0x40]);
+        assert!(run(&mut debugger, &mut cpu, "c").starts_with("BRK at $0606\n"));
+        let x = cpu.x();
+
+        // Stepping from a BRK runs only the BRK
+        run(&mut debugger, &mut cpu, "s");
+        assert_eq!((cpu.pc(), cpu.x(), cpu.sp()), (0x700, x, 0xfc));
+        run(&mut debugger, &mut cpu, "s");
+        assert_eq!((cpu.pc(), cpu.x()), (0x701, x + 1));
+
+        // Breakpoints and watchpoints still apply to a BRK that's continued from
+        run(&mut debugger, &mut cpu, "set pc $0606");
+        run(&mut debugger, &mut cpu, "set sp $ff");
+        run(&mut debugger, &mut cpu, "watch w $01ff");
+        run(&mut debugger, &mut cpu, "b $0700");
+        assert!(run(&mut debugger, &mut cpu, "c").starts_with("Watchpoint 1: $0606 wrote $06 to $01FF\n"));
+        run(&mut debugger, &mut cpu, "d 1");
+        run(&mut debugger, &mut cpu, "set pc $0606");
+        run(&mut debugger, &mut cpu, "set sp $ff");
+        assert!(run(&mut debugger, &mut cpu, "c").starts_with("Breakpoint 2 at $0700\n"));
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut debugger = Debugger::new();
+        let mut cpu = cpu_with_subroutines();
+        // Return addresses pushed by the JSRs
+        assert_eq!(run(&mut debugger, &mut cpu, "watch w $01fe $01ff"), "Watchpoint 1 on $01FE-$01FF\n");
+        assert!(run(&mut debugger, &mut cpu, "c").starts_with("Watchpoint 1: $0602 wrote $06 to $01FF\n"));
+        assert_eq!(cpu.pc(), 0x608);
+
+        run(&mut debugger, &mut cpu, "disable 1");
+        run(&mut debugger, &mut cpu, "b $060e if X == 0");
+        run(&mut debugger, &mut cpu, "watch r $01fc $01fd if X == 1");
+        assert!(run(&mut debugger, &mut cpu, "c").starts_with("Breakpoint 2 at $060E\n"));
+        // RTS pulls the return address after INX
+        assert!(run(&mut debugger, &mut cpu, "c").starts_with("Watchpoint 3: $060F read $0C from $01FC\n"));
+        assert_eq!(run(&mut debugger, &mut cpu, "b"), [
+            "1: watch w $01FE-$01FF (disabled), 1 hits",
+            "2: break $060E if X == 0, 1 hits",
+            "3: watch r $01FC-$01FD if X == 1, 1 hits",
+            ""
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_registers_and_memory() {
+        let mut debugger = Debugger::new();
+        let mut cpu = cpu_with_subroutines();
+        assert_eq!(run(&mut debugger, &mut cpu, "set a $80"), "PC:0600 A:80 X:00 Y:00 SP:FF P:nv-bdizc CYC:0\n");
+        run(&mut debugger, &mut cpu, "set n 1");
+        run(&mut debugger, &mut cpu, "set c 1");
+        assert_eq!(cpu.status(), Flags::N | Flags::S | Flags::C);
+        run(&mut debugger, &mut cpu, "set p 0");
+        assert_eq!(cpu.status(), Flags::S);
+
+        run(&mut debugger, &mut cpu, "fill $0012 $0014 $aa");
+        assert_eq!(run(&mut debugger, &mut cpu, "x $0011 $0015"),
+                   "0x0010:    00 aa aa aa 00\n");
+    }
+
+    #[test]
+    fn test_list() {
+        let mut debugger = Debugger::new();
+        let mut cpu = cpu_with_subroutines();
+        run(&mut debugger, &mut cpu, "b $0605");
+        run(&mut debugger, &mut cpu, "set pc $0608");
+        assert_eq!(run(&mut debugger, &mut cpu, "l"), [
+            "   0602  20 08 06  JSR $0608",
+            "*  0605  E8        INX",
+            "   0606  00        BRK",
+            "   0607  EA        NOP",
+            " > 0608  A9 05     LDA #$05",
+            "   060A  20 0E 06  JSR $060E",
+            "   060D  60        RTS",
+            "   060E  E8        INX",
+            "   060F  60        RTS",
+            "   0610  00        BRK",
+            ""
+        ].join("\n"));
+        assert_eq!(run(&mut debugger, &mut cpu, "l $060d 1"), "   060D  60        RTS\n");
+    }
+}
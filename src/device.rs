@@ -0,0 +1,63 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::state::{StateError, StateReader, StateWriter};
+
+// Memory-mapped peripheral. Addresses passed to it are relative to the start of the range it's mapped at
+pub trait Device {
+    fn read(&mut self, offset: u16) -> u8;
+    fn write(&mut self, offset: u16, val: u8);
+
+    // Same as read, but without any side effects. Used by debugging tools
+    fn peek(&self, offset: u16) -> u8;
+
+    // Called after every instruction with the amount of cycles it took
+    fn tick(&mut self, _cycles: u16) {}
+
+    // State of the device's IRQ output. IRQ is level-triggered, so keep it asserted until acknowledged
+    fn irq(&self) -> bool { false }
+
+    // State of the device's NMI output. NMI is edge-triggered: the CPU reacts when it goes from low to high
+    fn nmi(&self) -> bool { false }
+
+    // Internal state for save states. The memory map keeps each device's data separate,
+    // so a device only has to read back what it wrote
+    fn save_state(&self, _out: &mut StateWriter) {}
+    fn load_state(&mut self, _data: &mut StateReader) -> Result<(), StateError> { Ok(()) }
+}
+
+// Lets the caller keep a handle to a device after handing it over to the memory map
+impl<D: Device> Device for Rc<RefCell<D>> {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.borrow_mut().read(offset)
+    }
+
+    fn write(&mut self, offset: u16, val: u8) {
+        self.borrow_mut().write(offset, val)
+    }
+
+    fn peek(&self, offset: u16) -> u8 {
+        self.borrow().peek(offset)
+    }
+
+    fn tick(&mut self, cycles: u16) {
+        self.borrow_mut().tick(cycles)
+    }
+
+    fn irq(&self) -> bool {
+        self.borrow().irq()
+    }
+
+    fn nmi(&self) -> bool {
+        self.borrow().nmi()
+    }
+
+    fn save_state(&self, out: &mut StateWriter) {
+        self.borrow().save_state(out)
+    }
+
+    fn load_state(&mut self, data: &mut StateReader) -> Result<(), StateError> {
+        self.borrow_mut().load_state(data)
+    }
+}
+
+mod test;
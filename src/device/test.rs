@@ -0,0 +1,220 @@
+#![allow(clippy::module_inception)]
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::super::*;
+    use crate::bus::Bus;
+    use crate::cpu::{Cpu, Variant, IRQ_VECTOR, NMI_VECTOR, RESET_VECTOR};
+    use crate::memory_map::MemoryMap;
+    use crate::state::{StateError, StateReader, StateWriter};
+
+    // Writing to register 0 starts a countdown, IRQ is asserted when it runs out.
+    // Reading register 1 acknowledges the interrupt
+    #[derive(Default)]
+    struct Timer {
+        counter: u16,
+        running: bool,
+        irq: bool,
+        ticks: u64
+    }
+
+    impl Device for Timer {
+        fn read(&mut self, offset: u16) -> u8 {
+            let val = self.peek(offset);
+            if offset == 1 {
+                self.irq = false;
+            }
+            val
+        }
+
+        fn write(&mut self, offset: u16, val: u8) {
+            if offset == 0 {
+                self.counter = val as u16;
+                self.running = true;
+            }
+        }
+
+        fn peek(&self, offset: u16) -> u8 {
+            match offset {
+                0 => self.counter as u8,
+                _ => self.irq as u8
+            }
+        }
+
+        fn tick(&mut self, cycles: u16) {
+            self.ticks += cycles as u64;
+            if self.running {
+                self.counter = self.counter.saturating_sub(cycles);
+                if self.counter == 0 {
+                    self.running = false;
+                    self.irq = true;
+                }
+            }
+        }
+
+        fn irq(&self) -> bool {
+            self.irq
+        }
+
+        fn save_state(&self, out: &mut StateWriter) {
+            out.u16(self.counter);
+            out.bool(self.running);
+            out.bool(self.irq);
+            out.u64(self.ticks);
+        }
+
+        fn load_state(&mut self, data: &mut StateReader) -> Result<(), StateError> {
+            self.counter = data.u16()?;
+            self.running = data.bool()?;
+            self.irq = data.bool()?;
+            self.ticks = data.u64()?;
+            Ok(())
+        }
+    }
+
+    // Holds its NMI output high from the start
+    struct NmiSource;
+
+    impl Device for NmiSource {
+        fn read(&mut self, _offset: u16) -> u8 { 0 }
+        fn write(&mut self, _offset: u16, _val: u8) {}
+        fn peek(&self, _offset: u16) -> u8 { 0 }
+        fn nmi(&self) -> bool { true }
+    }
+
+    fn map_with(device: impl Device + 'static) -> MemoryMap {
+        MemoryMap::builder()
+            .ram(0x0000..=0x8fff)
+            .device(0x9000..=0x9001, device)
+            .ram(0x9002..=0xffff)
+            .build().unwrap()
+    }
+
+    #[test]
+    fn test_device_registers() {
+        let timer = Rc::new(RefCell::new(Timer::default()));
+        let mut map = map_with(timer.clone());
+        map.write(0x9000, 0x20);
+        assert_eq!(timer.borrow().counter, 0x20);
+        assert_eq!(map.peek(0x9000), 0x20);
+        assert_eq!(map.read(0x9001), 0x00);
+    }
+
+    #[test]
+    fn test_device_irq() {
+        let timer = Rc::new(RefCell::new(Timer::default()));
+        let mut cpu = Cpu::with_bus(map_with(timer.clone()), Variant::NMOS);
+        // cli
+        // lda #$0a
+        // sta $9000
+        // loop: jmp loop
+        cpu.load_at(0x600, &[0x58, 0xa9, 0x0a, 0x8d, 0x00, 0x90, 0x4c, 0x06, 0x06]);
+        // handler:
+        // lda $9001
+        // inc $10
+        // rti
+        cpu.load_at(0x2000, &[0xad, 0x01, 0x90, 0xe6, 0x10, 0x40]);
+        cpu.load_at(IRQ_VECTOR as usize, &[0x00, 0x20]);
+
+        cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap();
+        // STA already counts 4 of the 10 cycles, so the handler is entered after the second JMP
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x606);
+        let info = cpu.execute().unwrap();
+        assert_eq!(info.cycles, 3 + 7);
+        assert_eq!(cpu.pc(), 0x2000);
+
+        cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x606);
+        assert_eq!(cpu.get_byte(0x10), 1);
+        for _ in 0..10 {
+            cpu.execute().unwrap();
+        }
+        assert_eq!(cpu.get_byte(0x10), 1);
+        assert_eq!(timer.borrow().ticks, cpu.cycles());
+    }
+
+    #[test]
+    fn test_device_irq_masked() {
+        let timer = Rc::new(RefCell::new(Timer::default()));
+        let mut cpu = Cpu::with_bus(map_with(timer.clone()), Variant::NMOS);
+        // sei
+        // lda #$01
+        // sta $9000
+        // nop
+        // cli
+        // nop
+        cpu.load_at(0x600, &[0x78, 0xa9, 0x01, 0x8d, 0x00, 0x90, 0xea, 0x58, 0xea]);
+        cpu.load_at(IRQ_VECTOR as usize, &[0x00, 0x20]);
+        cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert!(timer.borrow().irq);
+        assert_eq!(cpu.pc(), 0x607);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x2000);
+    }
+
+    #[test]
+    fn test_device_nmi_edge() {
+        let mut cpu = Cpu::with_bus(map_with(NmiSource), Variant::NMOS);
+        cpu.load_at(0x600, &[0xea, 0xea, 0xea]);
+        // handler: nop; nop
+        cpu.load_at(0x3000, &[0xea, 0xea]);
+        cpu.load_at(NMI_VECTOR as usize, &[0x00, 0x30]);
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x3000);
+        // the line stays high, so there is no new edge
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x3002);
+    }
+
+    #[test]
+    fn test_device_reset() {
+        let timer = Rc::new(RefCell::new(Timer::default()));
+        let mut cpu = Cpu::with_bus(map_with(timer.clone()), Variant::NMOS);
+        cpu.load_at(RESET_VECTOR as usize, &[0x00, 0x06]);
+        cpu.load_at(0x600, &[0xea]);
+        cpu.reset();
+        cpu.execute().unwrap();
+        assert_eq!(cpu.cycles(), 7 + 2);
+        assert_eq!(timer.borrow().ticks, cpu.cycles());
+
+        // The line was already asserted before the reset, that's not an edge
+        let mut cpu = Cpu::with_bus(map_with(NmiSource), Variant::NMOS);
+        cpu.load_at(RESET_VECTOR as usize, &[0x00, 0x06]);
+        cpu.load_at(NMI_VECTOR as usize, &[0x00, 0x30]);
+        cpu.load_at(0x600, &[0xea, 0xea]);
+        cpu.reset();
+        cpu.execute().unwrap();
+        assert_eq!(cpu.pc(), 0x601);
+    }
+
+    #[test]
+    fn test_device_state() {
+        let timer = Rc::new(RefCell::new(Timer::default()));
+        let mut cpu = Cpu::with_bus(map_with(timer.clone()), Variant::NMOS);
+        // lda #$20
+        // sta $9000
+        // inc $10
+        cpu.load_at(0x600, &[0xa9, 0x20, 0x8d, 0x00, 0x90, 0xe6, 0x10]);
+        cpu.execute().unwrap(); cpu.execute().unwrap();
+        let saved = cpu.save_state();
+        assert_eq!(timer.borrow().counter, 0x1c);
+
+        cpu.execute().unwrap();
+        assert_eq!(timer.borrow().counter, 0x17);
+        assert_eq!(cpu.get_byte(0x10), 1);
+
+        cpu.load_state(&saved).unwrap();
+        assert_eq!(timer.borrow().counter, 0x1c);
+        assert!(timer.borrow().running);
+        assert_eq!(timer.borrow().ticks, cpu.cycles());
+        assert_eq!(cpu.get_byte(0x10), 0);
+        assert_eq!(cpu.pc(), 0x605);
+
+        // Same state, but the timer is missing from the map
+        let mut other = Cpu::with_bus(MemoryMap::builder().ram(0x0000..=0x8fff).build().unwrap(), Variant::NMOS);
+        assert_eq!(other.load_state(&saved), Err(StateError::Mismatch("memory map")));
+    }
+}
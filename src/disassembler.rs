@@ -0,0 +1,108 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use crate::bus::Bus;
+use crate::cpu::Variant;
+use crate::instructions::{AddressingMode, Instruction};
+
+// Turns machine code back into assembly. Only peeks at the bus, so it's safe to use on memory-mapped IO
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Disassembler {
+    variant: Variant,
+    undocumented: bool // Show undocumented opcodes as instructions instead of .byte
+}
+
+// One decoded instruction of a listing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    pub text: String
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes: Vec<String> = self.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        write!(f, "{:04X}  {:<8}  {}", self.addr, bytes.join(" "), self.text)
+    }
+}
+
+impl Disassembler {
+    pub fn new(variant: Variant) -> Disassembler {
+        Disassembler { variant, undocumented: false }
+    }
+
+    pub fn undocumented(mut self, enabled: bool) -> Disassembler {
+        self.undocumented = enabled;
+        self
+    }
+
+    // Returns the instruction at `addr` and its length in bytes
+    pub fn disassemble<B: Bus + ?Sized>(&self, bus: &B, addr: u16) -> (String, u16) {
+        let byte = bus.peek(addr);
+        let (instruction, mode, _) = self.variant.opcodes()[byte as usize];
+        if instruction == Instruction::None || (!self.undocumented && self.variant.is_undocumented(byte)) {
+            return (format!(".byte ${:02X}", byte), 1);
+        }
+
+        let arg = bus.peek(addr.wrapping_add(1));
+        let word = ((bus.peek(addr.wrapping_add(2)) as u16) << 8) | arg as u16;
+        let operand = match mode {
+            AddressingMode::Implied => String::new(),
+            AddressingMode::Accumulator => String::from("A"),
+            AddressingMode::Immediate => format!("#${:02X}", arg),
+            AddressingMode::ZeroPage => format!("${:02X}", arg),
+            AddressingMode::ZeroPageX => format!("${:02X},X", arg),
+            AddressingMode::ZeroPageY => format!("${:02X},Y", arg),
+            AddressingMode::Relative =>
+                format!("${:04X}", addr.wrapping_add(2).wrapping_add_signed(arg as i8 as i16)),
+            AddressingMode::Absolute => format!("${:04X}", word),
+            AddressingMode::AbsoluteX => format!("${:04X},X", word),
+            AddressingMode::AbsoluteY => format!("${:04X},Y", word),
+            AddressingMode::Indirect => format!("(${:04X})", word),
+            AddressingMode::IndirectX => format!("(${:02X},X)", arg),
+            AddressingMode::IndirectY => format!("(${:02X}),Y", arg),
+            AddressingMode::ZeroPageIndirect => format!("(${:02X})", arg),
+            AddressingMode::AbsoluteIndirectX => format!("(${:04X},X)", word),
+            AddressingMode::ZeroPageRelative => {
+                let offset = bus.peek(addr.wrapping_add(2)) as i8 as i16;
+                format!("${:02X},${:04X}", arg, addr.wrapping_add(3).wrapping_add_signed(offset))
+            }
+        };
+
+        // The Rockwell bit instructions carry the bit number in the opcode: RMB0-RMB7 and so on
+        let mnemonic = match instruction {
+            Instruction::RMB | Instruction::SMB | Instruction::BBR | Instruction::BBS =>
+                format!("{:?}{}", instruction, (byte >> 4) & 0b111),
+            _ => format!("{:?}", instruction)
+        };
+
+        let text = if operand.is_empty() { mnemonic } else { format!("{} {}", mnemonic, operand) };
+        (text, 1 + mode.operand_bytes())
+    }
+
+    // Disassembles every instruction that starts inside the range. The last one may reach past its end
+    pub fn disassemble_range<B: Bus + ?Sized>(&self, bus: &B, range: RangeInclusive<u16>) -> Vec<Line> {
+        let mut lines = vec![];
+        let mut addr = *range.start() as u32;
+
+        while addr <= *range.end() as u32 {
+            let (text, len) = self.disassemble(bus, addr as u16);
+            let bytes = (0..len).map(|i| bus.peek((addr as u16).wrapping_add(i))).collect();
+            lines.push(Line { addr: addr as u16, bytes, text });
+            addr += len as u32;
+        }
+
+        lines
+    }
+}
+
+// Disassembles a single NMOS instruction, showing undocumented opcodes as .byte
+pub fn disassemble<B: Bus + ?Sized>(bus: &B, addr: u16) -> (String, u16) {
+    Disassembler::new(Variant::NMOS).disassemble(bus, addr)
+}
+
+pub fn disassemble_range<B: Bus + ?Sized>(bus: &B, range: RangeInclusive<u16>) -> Vec<Line> {
+    Disassembler::new(Variant::NMOS).disassemble_range(bus, range)
+}
+
+mod test;
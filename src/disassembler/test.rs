@@ -0,0 +1,95 @@
+#![allow(clippy::module_inception)]
+
+#[cfg(test)]
+mod test {
+    use super::super::*;
+    use crate::bus::Ram;
+
+    fn ram_with(addr: usize, data: &[u8]) -> Ram {
+        let mut ram = Ram::new();
+        for (i, byte) in data.iter().enumerate() {
+            ram[(addr + i) & 0xffff] = *byte;
+        }
+        ram
+    }
+
+    #[test]
+    fn test_addressing_modes() {
+        let cases: &[(&[u8], &str)] = &[
+            (&[0x18], "CLC"),
+            (&[0x0a], "ASL A"),
+            (&[0xa9, 0x42], "LDA #$42"),
+            (&[0xa5, 0x20], "LDA $20"),
+            (&[0xb5, 0x20], "LDA $20,X"),
+            (&[0xb6, 0x20], "LDX $20,Y"),
+            (&[0xad, 0x34, 0x12], "LDA $1234"),
+            (&[0xbd, 0x34, 0x12], "LDA $1234,X"),
+            (&[0xb9, 0x34, 0x12], "LDA $1234,Y"),
+            (&[0x6c, 0x34, 0x12], "JMP ($1234)"),
+            (&[0xa1, 0x20], "LDA ($20,X)"),
+            (&[0xb1, 0x20], "LDA ($20),Y"),
+        ];
+
+        for (bytes, text) in cases {
+            let ram = ram_with(0x600, bytes);
+            assert_eq!(disassemble(&ram, 0x600), (text.to_string(), bytes.len() as u16));
+        }
+    }
+
+    #[test]
+    fn test_branch_targets() {
+        // bne +$02
+        // beq -$04
+        let ram = ram_with(0x600, &[0xd0, 0x02, 0xf0, 0xfc]);
+        assert_eq!(disassemble(&ram, 0x600), (String::from("BNE $0604"), 2));
+        assert_eq!(disassemble(&ram, 0x602), (String::from("BEQ $0600"), 2));
+
+        let ram = ram_with(0xfffe, &[0x10, 0x00]);
+        assert_eq!(disassemble(&ram, 0xfffe), (String::from("BPL $0000"), 2));
+    }
+
+    #[test]
+    fn test_undocumented() {
+        let ram = ram_with(0x600, &[0xa7, 0x10, 0x02]);
+        assert_eq!(disassemble(&ram, 0x600), (String::from(".byte $A7"), 1));
+
+        let disassembler = Disassembler::new(Variant::NMOS).undocumented(true);
+        assert_eq!(disassembler.disassemble(&ram, 0x600), (String::from("LAX $10"), 2));
+        assert_eq!(disassembler.disassemble(&ram, 0x602), (String::from("JAM"), 1));
+    }
+
+    #[test]
+    fn test_65c02() {
+        let disassembler = Disassembler::new(Variant::CMOS);
+        let cases: &[(&[u8], &str)] = &[
+            (&[0xb2, 0x20], "LDA ($20)"),
+            (&[0x7c, 0x34, 0x12], "JMP ($1234,X)"),
+            (&[0x1a], "INC A"),
+            (&[0x89, 0x01], "BIT #$01"),
+            (&[0x37, 0x20], "RMB3 $20"),
+            (&[0xff, 0x20, 0xfd], "BBS7 $20,$0600"),
+            (&[0x03], ".byte $03"),
+        ];
+
+        for (bytes, text) in cases {
+            let ram = ram_with(0x600, bytes);
+            assert_eq!(disassembler.disassemble(&ram, 0x600).0, *text);
+        }
+    }
+
+    #[test]
+    fn test_range() {
+        // lda #$01
+        // sta $0200
+        // brk
+        let ram = ram_with(0x600, &[0xa9, 0x01, 0x8d, 0x00, 0x02, 0x00]);
+        let lines = disassemble_range(&ram, 0x600..=0x602);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], Line { addr: 0x600, bytes: vec![0xa9, 0x01], text: String::from("LDA #$01") });
+        assert_eq!(lines[1].to_string(), "0602  8D 00 02  STA $0200");
+
+        // Stops at the end of the address space instead of wrapping around
+        let lines = disassemble_range(&ram, 0xfffe..=0xffff);
+        assert_eq!(lines.len(), 2);
+    }
+}
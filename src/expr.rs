@@ -0,0 +1,265 @@
+// Numbers and expressions shared by the assembler and breakpoint conditions, so both read `$ff`, `0xff`,
+// `%1010` and operators the same way. Operators bind like in C
+
+// Text that isn't a valid expression. The assembler and conditions wrap it in their own error types
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SyntaxError(pub String);
+
+// What can go wrong while working out the value of a well-formed expression
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum EvalError {
+    DivisionByZero,
+    Overflow
+}
+
+// $ff and 0xff are hex, %1010 is binary, anything else decimal. Signs are left to the expression
+pub(crate) fn parse_number(text: &str) -> Option<i64> {
+    let (digits, radix) = if let Some(hex) = text.strip_prefix('$').or_else(|| text.strip_prefix("0x")) {
+        (hex, 16)
+    } else if let Some(binary) = text.strip_prefix('%') {
+        (binary, 2)
+    } else {
+        (text, 10)
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    i64::from_str_radix(digits, radix).ok()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Token {
+    Number(i64),
+    Ident(String),
+    Str(String),
+    Op(&'static str)
+}
+
+pub(crate) fn tokenize(text: &str) -> Result<Vec<Token>, SyntaxError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        match c {
+            ' ' | '\t' => { i += 1; }
+            '$' | '%' | '0'..='9' => {
+                i += 1;
+                while i < chars.len() && chars[i].is_ascii_alphanumeric() {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let number = parse_number(&text).ok_or_else(|| SyntaxError(format!("invalid number {}", text)))?;
+                tokens.push(Token::Number(number));
+            }
+            '\'' => {
+                if i + 2 >= chars.len() || chars[i + 2] != '\'' {
+                    return Err(SyntaxError(String::from("unterminated character literal")));
+                }
+                tokens.push(Token::Number(chars[i + 1] as i64));
+                i += 3;
+            }
+            '"' => {
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    i += 1;
+                }
+                if i == chars.len() {
+                    return Err(SyntaxError(String::from("unterminated string")));
+                }
+                tokens.push(Token::Str(chars[start + 1..i].iter().collect()));
+                i += 1;
+            }
+            c if c.is_ascii_alphabetic() || c == '_' || c == '.' => {
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            _ => {
+                let two: String = chars[i..chars.len().min(i + 2)].iter().collect();
+                let op = match two.as_str() {
+                    "<<" => "<<", ">>" => ">>", "==" => "==", "!=" => "!=", "<=" => "<=", ">=" => ">=",
+                    "&&" => "&&", "||" => "||",
+                    _ => match c {
+                        '+' => "+", '-' => "-", '*' => "*", '/' => "/", '&' => "&", '|' => "|", '^' => "^",
+                        '!' => "!", '<' => "<", '>' => ">", '(' => "(", ')' => ")", '[' => "[", ']' => "]",
+                        ',' => ",", '#' => "#", ':' => ":", '=' => "=",
+                        _ => { return Err(SyntaxError(format!("unexpected character '{}'", c))); }
+                    }
+                };
+                tokens.push(Token::Op(op));
+                i += op.len();
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+// Names are resolved while parsing, so L is whatever the caller turns them into
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Expr<L> {
+    Number(i64),
+    Ident(L),
+    Pc, // * is the current address
+    Memory(Box<Expr<L>>), // [addr] is the byte at addr
+    Unary(&'static str, Box<Expr<L>>),
+    Binary(&'static str, Box<Expr<L>>, Box<Expr<L>>)
+}
+
+// Binary operators from the loosest to the tightest binding, same order as C
+const PRECEDENCE: [&[&str]; 10] = [
+    &["||"], &["&&"], &["|"], &["^"], &["&"], &["==", "!="], &["<", "<=", ">", ">="], &["<<", ">>"], &["+", "-"],
+    &["*", "/"]
+];
+
+pub(crate) struct ExprParser<'a, F> {
+    tokens: &'a [Token],
+    pos: usize,
+    memory: bool, // Whether [addr] is allowed
+    resolve: F
+}
+
+impl<'a, L, E, F> ExprParser<'a, F> where E: From<SyntaxError>, F: FnMut(&str) -> Result<L, E> {
+    pub(crate) fn parse(tokens: &'a [Token], memory: bool, resolve: F) -> Result<Expr<L>, E> {
+        if tokens.is_empty() {
+            return Err(SyntaxError(String::from("expected an expression")).into());
+        }
+
+        let mut parser = ExprParser { tokens, pos: 0, memory, resolve };
+        let expr = parser.binary(0)?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(token) => Err(SyntaxError(format!("unexpected {:?} in expression", token)).into())
+        }
+    }
+
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) => Some(op),
+            _ => None
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr<L>, E> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(op) = self.peek_op().filter(|op| PRECEDENCE[level].contains(op)) {
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    // Parses the rest of a bracketed expression and the closing bracket
+    fn closed(&mut self, close: &'static str) -> Result<Expr<L>, E> {
+        let expr = self.binary(0)?;
+        if self.peek_op() != Some(close) {
+            return Err(SyntaxError(format!("expected {}", close)).into());
+        }
+        self.pos += 1;
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr<L>, E> {
+        match self.tokens.get(self.pos) {
+            // < and > in front of a value are its low and high byte
+            Some(Token::Op(op)) if matches!(*op, "-" | "!" | "<" | ">") => {
+                self.pos += 1;
+                Ok(Expr::Unary(op, Box::new(self.unary()?)))
+            }
+            Some(Token::Op("*")) => {
+                self.pos += 1;
+                Ok(Expr::Pc)
+            }
+            Some(Token::Op("(")) => {
+                self.pos += 1;
+                self.closed(")")
+            }
+            Some(Token::Op("[")) if self.memory => {
+                self.pos += 1;
+                Ok(Expr::Memory(Box::new(self.closed("]")?)))
+            }
+            Some(Token::Number(n)) => {
+                self.pos += 1;
+                Ok(Expr::Number(*n))
+            }
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                Ok(Expr::Ident((self.resolve)(name)?))
+            }
+            Some(token) => Err(SyntaxError(format!("unexpected {:?} in expression", token)).into()),
+            None => Err(SyntaxError(String::from("unexpected end of expression")).into())
+        }
+    }
+}
+
+// What the names, * and [addr] of an expression stand for
+pub(crate) trait Scope<L> {
+    type Error: From<EvalError>;
+
+    fn ident(&self, ident: &L) -> Result<i64, Self::Error>;
+    fn pc(&self) -> i64;
+    fn memory(&self, addr: u16) -> i64;
+}
+
+impl<L> Expr<L> {
+    // Addition, subtraction, multiplication and shifts wrap around, like they would on the target.
+    // Comparisons and logic give 1 or 0, && and || only evaluate their right side when needed
+    pub(crate) fn evaluate<S: Scope<L>>(&self, scope: &S) -> Result<i64, S::Error> {
+        Ok(match self {
+            Expr::Number(n) => *n,
+            Expr::Ident(ident) => scope.ident(ident)?,
+            Expr::Pc => scope.pc(),
+            Expr::Memory(addr) => scope.memory(addr.evaluate(scope)? as u16),
+            Expr::Unary(op, expr) => {
+                let value = expr.evaluate(scope)?;
+                match *op {
+                    "-" => value.checked_neg().ok_or(EvalError::Overflow)?,
+                    "!" => (value == 0) as i64,
+                    "<" => value & 0xff,
+                    ">" => (value >> 8) & 0xff,
+                    _ => { unreachable!() }
+                }
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = lhs.evaluate(scope)?;
+                match *op {
+                    "&&" => return Ok((lhs != 0 && rhs.evaluate(scope)? != 0) as i64),
+                    "||" => return Ok((lhs != 0 || rhs.evaluate(scope)? != 0) as i64),
+                    _ => {}
+                }
+                let rhs = rhs.evaluate(scope)?;
+                match *op {
+                    "==" => (lhs == rhs) as i64,
+                    "!=" => (lhs != rhs) as i64,
+                    "<" => (lhs < rhs) as i64,
+                    "<=" => (lhs <= rhs) as i64,
+                    ">" => (lhs > rhs) as i64,
+                    ">=" => (lhs >= rhs) as i64,
+                    "|" => lhs | rhs,
+                    "^" => lhs ^ rhs,
+                    "&" => lhs & rhs,
+                    "<<" => lhs.wrapping_shl(rhs as u32),
+                    ">>" => lhs.wrapping_shr(rhs as u32),
+                    "+" => lhs.wrapping_add(rhs),
+                    "-" => lhs.wrapping_sub(rhs),
+                    "*" => lhs.wrapping_mul(rhs),
+                    "/" if rhs == 0 => { return Err(EvalError::DivisionByZero.into()); }
+                    "/" => lhs.checked_div(rhs).ok_or(EvalError::Overflow)?,
+                    _ => { unreachable!() }
+                }
+            }
+        })
+    }
+}
+
+mod test;
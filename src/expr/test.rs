@@ -0,0 +1,64 @@
+#![allow(clippy::module_inception)]
+
+#[cfg(test)]
+mod test {
+    use super::super::*;
+
+    // No names, * is $0600 and every byte of memory is $42
+    struct Empty;
+
+    impl Scope<String> for Empty {
+        type Error = EvalError;
+
+        fn ident(&self, _name: &String) -> Result<i64, EvalError> {
+            unreachable!()
+        }
+
+        fn pc(&self) -> i64 {
+            0x600
+        }
+
+        fn memory(&self, _addr: u16) -> i64 {
+            0x42
+        }
+    }
+
+    fn eval(source: &str) -> Result<i64, EvalError> {
+        let tokens = tokenize(source).unwrap();
+        let expr: Expr<String> = ExprParser::parse(&tokens, true, |_| Err(SyntaxError(String::from("no names")))).unwrap();
+        expr.evaluate(&Empty)
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("$ff"), Some(0xff));
+        assert_eq!(parse_number("0xFF"), Some(0xff));
+        assert_eq!(parse_number("%1010"), Some(10));
+        assert_eq!(parse_number("42"), Some(42));
+        assert_eq!(parse_number("$"), None);
+        assert_eq!(parse_number("$+1"), None);
+        assert_eq!(parse_number("12ab"), None);
+        assert_eq!(parse_number("%102"), None);
+        assert_eq!(tokenize("0x1g"), Err(SyntaxError(String::from("invalid number 0x1g"))));
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(eval("1 | 2 == 2"), Ok(1));
+        assert_eq!(eval("6 & 3 != 0"), Ok(6 & 1));
+        assert_eq!(eval("1 << 2 + 1"), Ok(8));
+        assert_eq!(eval("1 + 2 * 3"), Ok(7));
+        assert_eq!(eval("2 < 3 == 1"), Ok(1));
+        assert_eq!(eval("0 && 1 || 1"), Ok(1));
+        assert_eq!(eval("-(2 - 5) * 2"), Ok(6));
+        assert_eq!(eval("!0 + >$1234 + <$1234"), Ok(1 + 0x12 + 0x34));
+        assert_eq!(eval("[*] + * - $600"), Ok(0x42));
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        assert_eq!(eval("1 / 0"), Err(EvalError::DivisionByZero));
+        assert_eq!(eval("0 && 1 / 0"), Ok(0));
+        assert_eq!(eval("-($7fffffffffffffff + 1)"), Err(EvalError::Overflow));
+    }
+}
@@ -2,7 +2,7 @@ use crate::instructions::AddressingMode::*;
 use crate::instructions::Instruction::*;
 use crate::instructions::Cycles::*;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum AddressingMode {
     Implied = 0,
     Accumulator = 1,
@@ -16,12 +16,15 @@ pub enum AddressingMode {
     AbsoluteY = 9,
     Indirect = 10,
     IndirectX = 11,
-    IndirectY = 12
+    IndirectY = 12,
+    ZeroPageIndirect = 13,  // 65C02 only: (zp)
+    AbsoluteIndirectX = 14, // 65C02 only: JMP (abs,X)
+    ZeroPageRelative = 15   // 65C02 only: BBR/BBS zp,rel
 }
 
 impl AddressingMode {
     pub fn operand_bytes(self) -> u16 {
-        return match self {
+        match self {
             Implied => 0,
             Accumulator => 0,
             Immediate => 1,
@@ -34,21 +37,33 @@ impl AddressingMode {
             AbsoluteY => 2,
             Indirect => 2,
             IndirectX => 1,
-            IndirectY => 1
+            IndirectY => 1,
+            ZeroPageIndirect => 1,
+            AbsoluteIndirectX => 2,
+            ZeroPageRelative => 2
         }
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Cycles {
     Exact(u16), // Exact amount of cycles
     PageBoundary(u16), // Exact amount of cycles + 1 if page boundary has been crossed
     Branching // 2 cycles if not taken; 3 cycles if taken + 1 if boundary has been crossed
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Instruction {
+    ADC, // Add with carry;    Modes: Immediate, ZP, ZPX, Absolute, AX, AY, IX, IY; Flags: NV----ZC
+    SBC, // Sub with carry;    Modes: Immediate, ZP, ZPX, Absolute, AX, AY, IX, IY; Flags: NV----ZC
+    AND, // Bitwise AND;       Modes: Immediate, ZP, ZPX, Absolute, AX, AY, IX, IY; Flags: N-----Z-
+    ORA, // Bitwise OR;        Modes: Immediate, ZP, ZPX, Absolute, AX, AY, IX, IY; Flags: N-----Z-
+    EOR, // Bitwise XOR;       Modes: Immediate, ZP, ZPX, Absolute, AX, AY, IX, IY; Flags: N-----Z-
+    BIT, // Test bits;         Modes: ZP, Absolute;                                 Flags: NV----Z-
     ASL, // Shift bits left;   Modes: Accumulator; ZP, ZPX, Absolute, AbsoluteX;    Flags: N-----ZC
+    LSR, // Shift bits right;  Modes: Accumulator; ZP, ZPX, Absolute, AbsoluteX;    Flags: N-----ZC
+    ROL, // Rotate left;       Modes: Accumulator; ZP, ZPX, Absolute, AbsoluteX;    Flags: N-----ZC
+    ROR, // Rotate right;      Modes: Accumulator; ZP, ZPX, Absolute, AbsoluteX;    Flags: N-----ZC
     CMP, // Compare A;         Modes: Immediate, ZP, ZPX, Absolute, AX, AY, IX, IY  Flags: N-----ZC
     CPX, // Compare X;         Modes: Immediate, ZP, Absolute;                      Flags: N-----ZC
     CPY, // Compare Y;         Modes: Immediate, ZP, Absolute;                      Flags: N-----ZC
@@ -93,264 +108,583 @@ pub enum Instruction {
     BVS, // Branch on V set;   Modes: Relative;                                     Flags: --------
     JSR, // Call;              Modes: Absolute;                                     Flags: --------
     RTS, // Return;            Modes: Implied;                                      Flags: --------
+    BRK, // Software interrupt; Modes: Implied;                                     Flags: -----I--
+    RTI, // Return from int.;  Modes: Implied;                                      Flags: NV-BDIZC
+    // Undocumented NMOS instructions
+    SLO, // ASL, then ORA;     Modes: ZP, ZPX, Absolute, AX, AY, IX, IY;            Flags: N-----ZC
+    RLA, // ROL, then AND;     Modes: ZP, ZPX, Absolute, AX, AY, IX, IY;            Flags: N-----ZC
+    SRE, // LSR, then EOR;     Modes: ZP, ZPX, Absolute, AX, AY, IX, IY;            Flags: N-----ZC
+    RRA, // ROR, then ADC;     Modes: ZP, ZPX, Absolute, AX, AY, IX, IY;            Flags: NV----ZC
+    SAX, // Store A & X;       Modes: ZP, ZPY, Absolute, IX;                        Flags: --------
+    LAX, // Load A and X;      Modes: Immediate, ZP, ZPY, Absolute, AY, IX, IY;     Flags: N-----Z-
+    DCP, // DEC, then CMP;     Modes: ZP, ZPX, Absolute, AX, AY, IX, IY;            Flags: N-----ZC
+    ISC, // INC, then SBC;     Modes: ZP, ZPX, Absolute, AX, AY, IX, IY;            Flags: NV----ZC
+    ANC, // AND, C = N;        Modes: Immediate;                                    Flags: N-----ZC
+    ALR, // AND, then LSR A;   Modes: Immediate;                                    Flags: N-----ZC
+    ARR, // AND, then ROR A;   Modes: Immediate;                                    Flags: NV----ZC
+    SBX, // X = A & X - imm;   Modes: Immediate;                                    Flags: N-----ZC
+    ANE, // A = A & X & imm;   Modes: Immediate;                                    Flags: N-----Z-
+    SHA, // Store A & X & H+1; Modes: AY, IY;                                       Flags: --------
+    SHX, // Store X & H+1;     Modes: AY;                                           Flags: --------
+    SHY, // Store Y & H+1;     Modes: AX;                                           Flags: --------
+    TAS, // SP = A & X, SHA;   Modes: AY;                                           Flags: --------
+    LAS, // A, X, SP = M & SP; Modes: AY;                                           Flags: N-----Z-
+    JAM, // Halt the CPU;      Modes: Implied;                                      Flags: --------
+    // WDC 65C02 instructions
+    BRA, // Branch always;     Modes: Relative;                                     Flags: --------
+    PHX, // Push X;            Modes: Implied;                                      Flags: --------
+    PHY, // Push Y;            Modes: Implied;                                      Flags: --------
+    PLX, // Pull X;            Modes: Implied;                                      Flags: N-----Z-
+    PLY, // Pull Y;            Modes: Implied;                                      Flags: N-----Z-
+    STZ, // Store zero;        Modes: ZP, ZPX, Absolute, AX;                        Flags: --------
+    TRB, // Test, reset bits;  Modes: ZP, Absolute;                                 Flags: ------Z-
+    TSB, // Test, set bits;    Modes: ZP, Absolute;                                 Flags: ------Z-
+    RMB, // Reset bit n of M;  Modes: ZP; n comes from bits 4-6 of the opcode;      Flags: --------
+    SMB, // Set bit n of M;    Modes: ZP; n comes from bits 4-6 of the opcode;      Flags: --------
+    BBR, // Branch on bit n 0; Modes: ZeroPageRelative;                             Flags: --------
+    BBS, // Branch on bit n 1; Modes: ZeroPageRelative;                             Flags: --------
+    WAI, // Wait for interrupt; Modes: Implied;                                     Flags: --------
+    STP, // Stop until reset;  Modes: Implied;                                      Flags: --------
     None
 }
 
+impl Instruction {
+    // Mnemonics that only exist as undocumented opcodes
+    pub fn is_undocumented(self) -> bool {
+        matches!(self, SLO | RLA | SRE | RRA | SAX | LAX | DCP | ISC | ANC | ALR | ARR | SBX | ANE | SHA | SHX | SHY
+            | TAS | LAS | JAM)
+    }
+}
+
+// Besides the undocumented mnemonics there is a second SBC #imm and a bunch of NOPs with operands
+pub fn is_undocumented(opcode: u8) -> bool {
+    match OPCODES[opcode as usize].0 {
+        NOP => opcode != 0xea,
+        SBC => opcode == 0xeb,
+        instruction => instruction.is_undocumented()
+    }
+}
+
+// Every opcode the 65C02 doesn't define is a NOP of some length
+pub fn is_undocumented_65c02(opcode: u8) -> bool {
+    OPCODES_65C02[opcode as usize].0 == NOP && opcode != 0xea
+}
+
 pub static OPCODES: [(Instruction, AddressingMode, Cycles); 256] = [
-    (None, Implied, Exact(0)), // 0x00
-    (None, Implied, Exact(0)), // 0x01
-    (None, Implied, Exact(0)), // 0x02
-    (None, Implied, Exact(0)), // 0x03
-    (None, Implied, Exact(0)), // 0x04
-    (None, Implied, Exact(0)), // 0x05
+    (BRK, Implied, Exact(7)), // 0x00
+    (ORA, IndirectX, Exact(6)), // 0x01
+    (JAM, Implied, Exact(0)), // 0x02
+    (SLO, IndirectX, Exact(8)), // 0x03
+    (NOP, ZeroPage, Exact(3)), // 0x04
+    (ORA, ZeroPage, Exact(3)), // 0x05
     (ASL, ZeroPage, Exact(5)), // 0x06
-    (None, Implied, Exact(0)), // 0x07
+    (SLO, ZeroPage, Exact(5)), // 0x07
     (PHP, Implied, Exact(3)), // 0x08
-    (None, Implied, Exact(0)), // 0x09
+    (ORA, Immediate, Exact(2)), // 0x09
     (ASL, Accumulator, Exact(2)), // 0x0a
-    (None, Implied, Exact(0)), // 0x0b
-    (None, Implied, Exact(0)), // 0x0c
-    (None, Implied, Exact(0)), // 0x0d
+    (ANC, Immediate, Exact(2)), // 0x0b
+    (NOP, Absolute, Exact(4)), // 0x0c
+    (ORA, Absolute, Exact(4)), // 0x0d
     (ASL, Absolute, Exact(6)), // 0x0e
-    (None, Implied, Exact(0)), // 0x0f
+    (SLO, Absolute, Exact(6)), // 0x0f
     (BPL, Relative, Branching), // 0x10
-    (None, Implied, Exact(0)), // 0x11
-    (None, Implied, Exact(0)), // 0x12
-    (None, Implied, Exact(0)), // 0x13
-    (None, Implied, Exact(0)), // 0x14
-    (None, Implied, Exact(0)), // 0x15
+    (ORA, IndirectY, PageBoundary(5)), // 0x11
+    (JAM, Implied, Exact(0)), // 0x12
+    (SLO, IndirectY, Exact(8)), // 0x13
+    (NOP, ZeroPageX, Exact(4)), // 0x14
+    (ORA, ZeroPageX, Exact(4)), // 0x15
     (ASL, ZeroPageX, Exact(6)), // 0x16
-    (None, Implied, Exact(0)), // 0x17
+    (SLO, ZeroPageX, Exact(6)), // 0x17
     (CLC, Implied, Exact(2)), // 0x18
-    (None, Implied, Exact(0)), // 0x19
-    (None, Implied, Exact(0)), // 0x1a
-    (None, Implied, Exact(0)), // 0x1b
-    (None, Implied, Exact(0)), // 0x1c
-    (None, Implied, Exact(0)), // 0x1d
+    (ORA, AbsoluteY, PageBoundary(4)), // 0x19
+    (NOP, Implied, Exact(2)), // 0x1a
+    (SLO, AbsoluteY, Exact(7)), // 0x1b
+    (NOP, AbsoluteX, PageBoundary(4)), // 0x1c
+    (ORA, AbsoluteX, PageBoundary(4)), // 0x1d
     (ASL, AbsoluteX, Exact(7)), // 0x1e
-    (None, Implied, Exact(0)), // 0x1f
+    (SLO, AbsoluteX, Exact(7)), // 0x1f
     (JSR, Absolute, Exact(6)), // 0x20
-    (None, Implied, Exact(0)), // 0x21
-    (None, Implied, Exact(0)), // 0x22
-    (None, Implied, Exact(0)), // 0x23
-    (None, Implied, Exact(0)), // 0x24
-    (None, Implied, Exact(0)), // 0x25
-    (None, Implied, Exact(0)), // 0x26
-    (None, Implied, Exact(0)), // 0x27
+    (AND, IndirectX, Exact(6)), // 0x21
+    (JAM, Implied, Exact(0)), // 0x22
+    (RLA, IndirectX, Exact(8)), // 0x23
+    (BIT, ZeroPage, Exact(3)), // 0x24
+    (AND, ZeroPage, Exact(3)), // 0x25
+    (ROL, ZeroPage, Exact(5)), // 0x26
+    (RLA, ZeroPage, Exact(5)), // 0x27
     (PLP, Implied, Exact(4)), // 0x28
-    (None, Implied, Exact(0)), // 0x29
-    (None, Implied, Exact(0)), // 0x2a
-    (None, Implied, Exact(0)), // 0x2b
-    (None, Implied, Exact(0)), // 0x2c
-    (None, Implied, Exact(0)), // 0x2d
-    (None, Implied, Exact(0)), // 0x2e
-    (None, Implied, Exact(0)), // 0x2f
+    (AND, Immediate, Exact(2)), // 0x29
+    (ROL, Accumulator, Exact(2)), // 0x2a
+    (ANC, Immediate, Exact(2)), // 0x2b
+    (BIT, Absolute, Exact(4)), // 0x2c
+    (AND, Absolute, Exact(4)), // 0x2d
+    (ROL, Absolute, Exact(6)), // 0x2e
+    (RLA, Absolute, Exact(6)), // 0x2f
     (BMI, Relative, Branching), // 0x30
-    (None, Implied, Exact(0)), // 0x31
-    (None, Implied, Exact(0)), // 0x32
-    (None, Implied, Exact(0)), // 0x33
-    (None, Implied, Exact(0)), // 0x34
-    (None, Implied, Exact(0)), // 0x35
-    (None, Implied, Exact(0)), // 0x36
-    (None, Implied, Exact(0)), // 0x37
+    (AND, IndirectY, PageBoundary(5)), // 0x31
+    (JAM, Implied, Exact(0)), // 0x32
+    (RLA, IndirectY, Exact(8)), // 0x33
+    (NOP, ZeroPageX, Exact(4)), // 0x34
+    (AND, ZeroPageX, Exact(4)), // 0x35
+    (ROL, ZeroPageX, Exact(6)), // 0x36
+    (RLA, ZeroPageX, Exact(6)), // 0x37
     (SEC, Implied, Exact(2)), // 0x38
-    (None, Implied, Exact(0)), // 0x39
-    (None, Implied, Exact(0)), // 0x3a
-    (None, Implied, Exact(0)), // 0x3b
-    (None, Implied, Exact(0)), // 0x3c
-    (None, Implied, Exact(0)), // 0x3d
-    (None, Implied, Exact(0)), // 0x3e
-    (None, Implied, Exact(0)), // 0x3f
-    (None, Implied, Exact(0)), // 0x40
-    (None, Implied, Exact(0)), // 0x41
-    (None, Implied, Exact(0)), // 0x42
-    (None, Implied, Exact(0)), // 0x43
-    (None, Implied, Exact(0)), // 0x44
-    (None, Implied, Exact(0)), // 0x45
-    (None, Implied, Exact(0)), // 0x46
-    (None, Implied, Exact(0)), // 0x47
+    (AND, AbsoluteY, PageBoundary(4)), // 0x39
+    (NOP, Implied, Exact(2)), // 0x3a
+    (RLA, AbsoluteY, Exact(7)), // 0x3b
+    (NOP, AbsoluteX, PageBoundary(4)), // 0x3c
+    (AND, AbsoluteX, PageBoundary(4)), // 0x3d
+    (ROL, AbsoluteX, Exact(7)), // 0x3e
+    (RLA, AbsoluteX, Exact(7)), // 0x3f
+    (RTI, Implied, Exact(6)), // 0x40
+    (EOR, IndirectX, Exact(6)), // 0x41
+    (JAM, Implied, Exact(0)), // 0x42
+    (SRE, IndirectX, Exact(8)), // 0x43
+    (NOP, ZeroPage, Exact(3)), // 0x44
+    (EOR, ZeroPage, Exact(3)), // 0x45
+    (LSR, ZeroPage, Exact(5)), // 0x46
+    (SRE, ZeroPage, Exact(5)), // 0x47
     (PHA, Implied, Exact(3)), // 0x48
-    (None, Implied, Exact(0)), // 0x49
-    (None, Implied, Exact(0)), // 0x4a
-    (None, Implied, Exact(0)), // 0x4b
+    (EOR, Immediate, Exact(2)), // 0x49
+    (LSR, Accumulator, Exact(2)), // 0x4a
+    (ALR, Immediate, Exact(2)), // 0x4b
     (JMP, Absolute, Exact(3)), // 0x4c
-    (None, Implied, Exact(0)), // 0x4d
-    (None, Implied, Exact(0)), // 0x4e
-    (None, Implied, Exact(0)), // 0x4f
+    (EOR, Absolute, Exact(4)), // 0x4d
+    (LSR, Absolute, Exact(6)), // 0x4e
+    (SRE, Absolute, Exact(6)), // 0x4f
     (BVC, Relative, Branching), // 0x50
-    (None, Implied, Exact(0)), // 0x51
-    (None, Implied, Exact(0)), // 0x52
-    (None, Implied, Exact(0)), // 0x53
-    (None, Implied, Exact(0)), // 0x54
-    (None, Implied, Exact(0)), // 0x55
-    (None, Implied, Exact(0)), // 0x56
-    (None, Implied, Exact(0)), // 0x57
+    (EOR, IndirectY, PageBoundary(5)), // 0x51
+    (JAM, Implied, Exact(0)), // 0x52
+    (SRE, IndirectY, Exact(8)), // 0x53
+    (NOP, ZeroPageX, Exact(4)), // 0x54
+    (EOR, ZeroPageX, Exact(4)), // 0x55
+    (LSR, ZeroPageX, Exact(6)), // 0x56
+    (SRE, ZeroPageX, Exact(6)), // 0x57
     (CLI, Implied, Exact(2)), // 0x58
-    (None, Implied, Exact(0)), // 0x59
-    (None, Implied, Exact(0)), // 0x5a
-    (None, Implied, Exact(0)), // 0x5b
-    (None, Implied, Exact(0)), // 0x5c
-    (None, Implied, Exact(0)), // 0x5d
-    (None, Implied, Exact(0)), // 0x5e
-    (None, Implied, Exact(0)), // 0x5f
+    (EOR, AbsoluteY, PageBoundary(4)), // 0x59
+    (NOP, Implied, Exact(2)), // 0x5a
+    (SRE, AbsoluteY, Exact(7)), // 0x5b
+    (NOP, AbsoluteX, PageBoundary(4)), // 0x5c
+    (EOR, AbsoluteX, PageBoundary(4)), // 0x5d
+    (LSR, AbsoluteX, Exact(7)), // 0x5e
+    (SRE, AbsoluteX, Exact(7)), // 0x5f
     (RTS, Implied, Exact(6)), // 0x60
-    (None, Implied, Exact(0)), // 0x61
-    (None, Implied, Exact(0)), // 0x62
-    (None, Implied, Exact(0)), // 0x63
-    (None, Implied, Exact(0)), // 0x64
-    (None, Implied, Exact(0)), // 0x65
-    (None, Implied, Exact(0)), // 0x66
-    (None, Implied, Exact(0)), // 0x67
+    (ADC, IndirectX, Exact(6)), // 0x61
+    (JAM, Implied, Exact(0)), // 0x62
+    (RRA, IndirectX, Exact(8)), // 0x63
+    (NOP, ZeroPage, Exact(3)), // 0x64
+    (ADC, ZeroPage, Exact(3)), // 0x65
+    (ROR, ZeroPage, Exact(5)), // 0x66
+    (RRA, ZeroPage, Exact(5)), // 0x67
     (PLA, Implied, Exact(4)), // 0x68
-    (None, Implied, Exact(0)), // 0x69
-    (None, Implied, Exact(0)), // 0x6a
-    (None, Implied, Exact(0)), // 0x6b
+    (ADC, Immediate, Exact(2)), // 0x69
+    (ROR, Accumulator, Exact(2)), // 0x6a
+    (ARR, Immediate, Exact(2)), // 0x6b
     (JMP, Indirect, Exact(5)), // 0x6c
-    (None, Implied, Exact(0)), // 0x6d
-    (None, Implied, Exact(0)), // 0x6e
-    (None, Implied, Exact(0)), // 0x6f
+    (ADC, Absolute, Exact(4)), // 0x6d
+    (ROR, Absolute, Exact(6)), // 0x6e
+    (RRA, Absolute, Exact(6)), // 0x6f
+    (BVS, Relative, Branching), // 0x70
+    (ADC, IndirectY, PageBoundary(5)), // 0x71
+    (JAM, Implied, Exact(0)), // 0x72
+    (RRA, IndirectY, Exact(8)), // 0x73
+    (NOP, ZeroPageX, Exact(4)), // 0x74
+    (ADC, ZeroPageX, Exact(4)), // 0x75
+    (ROR, ZeroPageX, Exact(6)), // 0x76
+    (RRA, ZeroPageX, Exact(6)), // 0x77
+    (SEI, Implied, Exact(2)), // 0x78
+    (ADC, AbsoluteY, PageBoundary(4)), // 0x79
+    (NOP, Implied, Exact(2)), // 0x7a
+    (RRA, AbsoluteY, Exact(7)), // 0x7b
+    (NOP, AbsoluteX, PageBoundary(4)), // 0x7c
+    (ADC, AbsoluteX, PageBoundary(4)), // 0x7d
+    (ROR, AbsoluteX, Exact(7)), // 0x7e
+    (RRA, AbsoluteX, Exact(7)), // 0x7f
+    (NOP, Immediate, Exact(2)), // 0x80
+    (STA, IndirectX, Exact(6)), // 0x81
+    (NOP, Immediate, Exact(2)), // 0x82
+    (SAX, IndirectX, Exact(6)), // 0x83
+    (STY, ZeroPage, Exact(3)), // 0x84
+    (STA, ZeroPage, Exact(3)), // 0x85
+    (STX, ZeroPage, Exact(3)), // 0x86
+    (SAX, ZeroPage, Exact(3)), // 0x87
+    (DEY, Implied, Exact(2)), // 0x88
+    (NOP, Immediate, Exact(2)), // 0x89
+    (TXA, Implied, Exact(2)), // 0x8a
+    (ANE, Immediate, Exact(2)), // 0x8b
+    (STY, Absolute, Exact(4)), // 0x8c
+    (STA, Absolute, Exact(4)), // 0x8d
+    (STX, Absolute, Exact(4)), // 0x8e
+    (SAX, Absolute, Exact(4)), // 0x8f
+    (BCC, Relative, Branching), // 0x90
+    (STA, IndirectY, Exact(6)), // 0x91
+    (JAM, Implied, Exact(0)), // 0x92
+    (SHA, IndirectY, Exact(6)), // 0x93
+    (STY, ZeroPageX, Exact(4)), // 0x94
+    (STA, ZeroPageX, Exact(4)), // 0x95
+    (STX, ZeroPageY, Exact(4)), // 0x96
+    (SAX, ZeroPageY, Exact(4)), // 0x97
+    (TYA, Implied, Exact(2)), // 0x98
+    (STA, AbsoluteY, Exact(5)), // 0x99
+    (TXS, Implied, Exact(2)), // 0x9a
+    (TAS, AbsoluteY, Exact(5)), // 0x9b
+    (SHY, AbsoluteX, Exact(5)), // 0x9c
+    (STA, AbsoluteX, Exact(5)), // 0x9d
+    (SHX, AbsoluteY, Exact(5)), // 0x9e
+    (SHA, AbsoluteY, Exact(5)), // 0x9f
+    (LDY, Immediate, Exact(2)), // 0xa0
+    (LDA, IndirectX, Exact(6)), // 0xa1
+    (LDX, Immediate, Exact(2)), // 0xa2
+    (LAX, IndirectX, Exact(6)), // 0xa3
+    (LDY, ZeroPage, Exact(3)), // 0xa4
+    (LDA, ZeroPage, Exact(3)), // 0xa5
+    (LDX, ZeroPage, Exact(3)), // 0xa6
+    (LAX, ZeroPage, Exact(3)), // 0xa7
+    (TAY, Implied, Exact(2)), // 0xa8
+    (LDA, Immediate, Exact(2)), // 0xa9
+    (TAX, Implied, Exact(2)), // 0xaa
+    (LAX, Immediate, Exact(2)), // 0xab
+    (LDY, Absolute, Exact(4)), // 0xac
+    (LDA, Absolute, Exact(4)), // 0xad
+    (LDX, Absolute, Exact(4)), // 0xae
+    (LAX, Absolute, Exact(4)), // 0xaf
+    (BCS, Relative, Branching), // 0xb0
+    (LDA, IndirectY, PageBoundary(5)), // 0xb1
+    (JAM, Implied, Exact(0)), // 0xb2
+    (LAX, IndirectY, PageBoundary(5)), // 0xb3
+    (LDY, ZeroPageX, Exact(4)), // 0xb4
+    (LDA, ZeroPageX, Exact(4)), // 0xb5
+    (LDX, ZeroPageY, Exact(4)), // 0xb6
+    (LAX, ZeroPageY, Exact(4)), // 0xb7
+    (CLV, Implied, Exact(2)), // 0xb8
+    (LDA, AbsoluteY, PageBoundary(4)), // 0xb9
+    (TSX, Implied, Exact(2)), // 0xba
+    (LAS, AbsoluteY, PageBoundary(4)), // 0xbb
+    (LDY, AbsoluteX, PageBoundary(4)), // 0xbc
+    (LDA, AbsoluteX, PageBoundary(4)), // 0xbd
+    (LDX, AbsoluteY, PageBoundary(4)), // 0xbe
+    (LAX, AbsoluteY, PageBoundary(4)), // 0xbf
+    (CPY, Immediate, Exact(2)), // 0xc0
+    (CMP, IndirectX, Exact(6)), // 0xc1
+    (NOP, Immediate, Exact(2)), // 0xc2
+    (DCP, IndirectX, Exact(8)), // 0xc3
+    (CPY, ZeroPage, Exact(3)), // 0xc4
+    (CMP, ZeroPage, Exact(3)), // 0xc5
+    (DEC, ZeroPage, Exact(5)), // 0xc6
+    (DCP, ZeroPage, Exact(5)), // 0xc7
+    (INY, Implied, Exact(2)), // 0xc8
+    (CMP, Immediate, Exact(2)), // 0xc9
+    (DEX, Implied, Exact(2)), // 0xca
+    (SBX, Immediate, Exact(2)), // 0xcb
+    (CPY, Absolute, Exact(4)), // 0xcc
+    (CMP, Absolute, Exact(4)), // 0xcd
+    (DEC, Absolute, Exact(6)), // 0xce
+    (DCP, Absolute, Exact(6)), // 0xcf
+    (BNE, Relative, Branching), // 0xd0
+    (CMP, IndirectY, PageBoundary(5)), // 0xd1
+    (JAM, Implied, Exact(0)), // 0xd2
+    (DCP, IndirectY, Exact(8)), // 0xd3
+    (NOP, ZeroPageX, Exact(4)), // 0xd4
+    (CMP, ZeroPageX, Exact(4)), // 0xd5
+    (DEC, ZeroPageX, Exact(6)), // 0xd6
+    (DCP, ZeroPageX, Exact(6)), // 0xd7
+    (CLD, Implied, Exact(2)), // 0xd8
+    (CMP, AbsoluteY, PageBoundary(4)), // 0xd9
+    (NOP, Implied, Exact(2)), // 0xda
+    (DCP, AbsoluteY, Exact(7)), // 0xdb
+    (NOP, AbsoluteX, PageBoundary(4)), // 0xdc
+    (CMP, AbsoluteX, PageBoundary(4)), // 0xdd
+    (DEC, AbsoluteX, Exact(7)), // 0xde
+    (DCP, AbsoluteX, Exact(7)), // 0xdf
+    (CPX, Immediate, Exact(2)), // 0xe0
+    (SBC, IndirectX, Exact(6)), // 0xe1
+    (NOP, Immediate, Exact(2)), // 0xe2
+    (ISC, IndirectX, Exact(8)), // 0xe3
+    (CPX, ZeroPage, Exact(3)), // 0xe4
+    (SBC, ZeroPage, Exact(3)), // 0xe5
+    (INC, ZeroPage, Exact(5)), // 0xe6
+    (ISC, ZeroPage, Exact(5)), // 0xe7
+    (INX, Implied, Exact(2)), // 0xe8
+    (SBC, Immediate, Exact(2)), // 0xe9
+    (NOP, Implied, Exact(2)), // 0xea
+    (SBC, Immediate, Exact(2)), // 0xeb
+    (CPX, Absolute, Exact(4)), // 0xec
+    (SBC, Absolute, Exact(4)), // 0xed
+    (INC, Absolute, Exact(6)), // 0xee
+    (ISC, Absolute, Exact(6)), // 0xef
+    (BEQ, Relative, Branching), // 0xf0
+    (SBC, IndirectY, PageBoundary(5)), // 0xf1
+    (JAM, Implied, Exact(0)), // 0xf2
+    (ISC, IndirectY, Exact(8)), // 0xf3
+    (NOP, ZeroPageX, Exact(4)), // 0xf4
+    (SBC, ZeroPageX, Exact(4)), // 0xf5
+    (INC, ZeroPageX, Exact(6)), // 0xf6
+    (ISC, ZeroPageX, Exact(6)), // 0xf7
+    (SED, Implied, Exact(2)), // 0xf8
+    (SBC, AbsoluteY, PageBoundary(4)), // 0xf9
+    (NOP, Implied, Exact(2)), // 0xfa
+    (ISC, AbsoluteY, Exact(7)), // 0xfb
+    (NOP, AbsoluteX, PageBoundary(4)), // 0xfc
+    (SBC, AbsoluteX, PageBoundary(4)), // 0xfd
+    (INC, AbsoluteX, Exact(7)), // 0xfe
+    (ISC, AbsoluteX, Exact(7)), // 0xff
+];
+
+// WDC 65C02 with the Rockwell bit instructions. Unused opcodes are NOPs of various lengths
+pub static OPCODES_65C02: [(Instruction, AddressingMode, Cycles); 256] = [
+    (BRK, Implied, Exact(7)), // 0x00
+    (ORA, IndirectX, Exact(6)), // 0x01
+    (NOP, Immediate, Exact(2)), // 0x02
+    (NOP, Implied, Exact(1)), // 0x03
+    (TSB, ZeroPage, Exact(5)), // 0x04
+    (ORA, ZeroPage, Exact(3)), // 0x05
+    (ASL, ZeroPage, Exact(5)), // 0x06
+    (RMB, ZeroPage, Exact(5)), // 0x07
+    (PHP, Implied, Exact(3)), // 0x08
+    (ORA, Immediate, Exact(2)), // 0x09
+    (ASL, Accumulator, Exact(2)), // 0x0a
+    (NOP, Implied, Exact(1)), // 0x0b
+    (TSB, Absolute, Exact(6)), // 0x0c
+    (ORA, Absolute, Exact(4)), // 0x0d
+    (ASL, Absolute, Exact(6)), // 0x0e
+    (BBR, ZeroPageRelative, Exact(5)), // 0x0f
+    (BPL, Relative, Branching), // 0x10
+    (ORA, IndirectY, PageBoundary(5)), // 0x11
+    (ORA, ZeroPageIndirect, Exact(5)), // 0x12
+    (NOP, Implied, Exact(1)), // 0x13
+    (TRB, ZeroPage, Exact(5)), // 0x14
+    (ORA, ZeroPageX, Exact(4)), // 0x15
+    (ASL, ZeroPageX, Exact(6)), // 0x16
+    (RMB, ZeroPage, Exact(5)), // 0x17
+    (CLC, Implied, Exact(2)), // 0x18
+    (ORA, AbsoluteY, PageBoundary(4)), // 0x19
+    (INC, Accumulator, Exact(2)), // 0x1a
+    (NOP, Implied, Exact(1)), // 0x1b
+    (TRB, Absolute, Exact(6)), // 0x1c
+    (ORA, AbsoluteX, PageBoundary(4)), // 0x1d
+    (ASL, AbsoluteX, PageBoundary(6)), // 0x1e
+    (BBR, ZeroPageRelative, Exact(5)), // 0x1f
+    (JSR, Absolute, Exact(6)), // 0x20
+    (AND, IndirectX, Exact(6)), // 0x21
+    (NOP, Immediate, Exact(2)), // 0x22
+    (NOP, Implied, Exact(1)), // 0x23
+    (BIT, ZeroPage, Exact(3)), // 0x24
+    (AND, ZeroPage, Exact(3)), // 0x25
+    (ROL, ZeroPage, Exact(5)), // 0x26
+    (RMB, ZeroPage, Exact(5)), // 0x27
+    (PLP, Implied, Exact(4)), // 0x28
+    (AND, Immediate, Exact(2)), // 0x29
+    (ROL, Accumulator, Exact(2)), // 0x2a
+    (NOP, Implied, Exact(1)), // 0x2b
+    (BIT, Absolute, Exact(4)), // 0x2c
+    (AND, Absolute, Exact(4)), // 0x2d
+    (ROL, Absolute, Exact(6)), // 0x2e
+    (BBR, ZeroPageRelative, Exact(5)), // 0x2f
+    (BMI, Relative, Branching), // 0x30
+    (AND, IndirectY, PageBoundary(5)), // 0x31
+    (AND, ZeroPageIndirect, Exact(5)), // 0x32
+    (NOP, Implied, Exact(1)), // 0x33
+    (BIT, ZeroPageX, Exact(4)), // 0x34
+    (AND, ZeroPageX, Exact(4)), // 0x35
+    (ROL, ZeroPageX, Exact(6)), // 0x36
+    (RMB, ZeroPage, Exact(5)), // 0x37
+    (SEC, Implied, Exact(2)), // 0x38
+    (AND, AbsoluteY, PageBoundary(4)), // 0x39
+    (DEC, Accumulator, Exact(2)), // 0x3a
+    (NOP, Implied, Exact(1)), // 0x3b
+    (BIT, AbsoluteX, PageBoundary(4)), // 0x3c
+    (AND, AbsoluteX, PageBoundary(4)), // 0x3d
+    (ROL, AbsoluteX, PageBoundary(6)), // 0x3e
+    (BBR, ZeroPageRelative, Exact(5)), // 0x3f
+    (RTI, Implied, Exact(6)), // 0x40
+    (EOR, IndirectX, Exact(6)), // 0x41
+    (NOP, Immediate, Exact(2)), // 0x42
+    (NOP, Implied, Exact(1)), // 0x43
+    (NOP, ZeroPage, Exact(3)), // 0x44
+    (EOR, ZeroPage, Exact(3)), // 0x45
+    (LSR, ZeroPage, Exact(5)), // 0x46
+    (RMB, ZeroPage, Exact(5)), // 0x47
+    (PHA, Implied, Exact(3)), // 0x48
+    (EOR, Immediate, Exact(2)), // 0x49
+    (LSR, Accumulator, Exact(2)), // 0x4a
+    (NOP, Implied, Exact(1)), // 0x4b
+    (JMP, Absolute, Exact(3)), // 0x4c
+    (EOR, Absolute, Exact(4)), // 0x4d
+    (LSR, Absolute, Exact(6)), // 0x4e
+    (BBR, ZeroPageRelative, Exact(5)), // 0x4f
+    (BVC, Relative, Branching), // 0x50
+    (EOR, IndirectY, PageBoundary(5)), // 0x51
+    (EOR, ZeroPageIndirect, Exact(5)), // 0x52
+    (NOP, Implied, Exact(1)), // 0x53
+    (NOP, ZeroPageX, Exact(4)), // 0x54
+    (EOR, ZeroPageX, Exact(4)), // 0x55
+    (LSR, ZeroPageX, Exact(6)), // 0x56
+    (RMB, ZeroPage, Exact(5)), // 0x57
+    (CLI, Implied, Exact(2)), // 0x58
+    (EOR, AbsoluteY, PageBoundary(4)), // 0x59
+    (PHY, Implied, Exact(3)), // 0x5a
+    (NOP, Implied, Exact(1)), // 0x5b
+    (NOP, Absolute, Exact(8)), // 0x5c
+    (EOR, AbsoluteX, PageBoundary(4)), // 0x5d
+    (LSR, AbsoluteX, PageBoundary(6)), // 0x5e
+    (BBR, ZeroPageRelative, Exact(5)), // 0x5f
+    (RTS, Implied, Exact(6)), // 0x60
+    (ADC, IndirectX, Exact(6)), // 0x61
+    (NOP, Immediate, Exact(2)), // 0x62
+    (NOP, Implied, Exact(1)), // 0x63
+    (STZ, ZeroPage, Exact(3)), // 0x64
+    (ADC, ZeroPage, Exact(3)), // 0x65
+    (ROR, ZeroPage, Exact(5)), // 0x66
+    (RMB, ZeroPage, Exact(5)), // 0x67
+    (PLA, Implied, Exact(4)), // 0x68
+    (ADC, Immediate, Exact(2)), // 0x69
+    (ROR, Accumulator, Exact(2)), // 0x6a
+    (NOP, Implied, Exact(1)), // 0x6b
+    (JMP, Indirect, Exact(6)), // 0x6c
+    (ADC, Absolute, Exact(4)), // 0x6d
+    (ROR, Absolute, Exact(6)), // 0x6e
+    (BBR, ZeroPageRelative, Exact(5)), // 0x6f
     (BVS, Relative, Branching), // 0x70
-    (None, Implied, Exact(0)), // 0x71
-    (None, Implied, Exact(0)), // 0x72
-    (None, Implied, Exact(0)), // 0x73
-    (None, Implied, Exact(0)), // 0x74
-    (None, Implied, Exact(0)), // 0x75
-    (None, Implied, Exact(0)), // 0x76
-    (None, Implied, Exact(0)), // 0x77
+    (ADC, IndirectY, PageBoundary(5)), // 0x71
+    (ADC, ZeroPageIndirect, Exact(5)), // 0x72
+    (NOP, Implied, Exact(1)), // 0x73
+    (STZ, ZeroPageX, Exact(4)), // 0x74
+    (ADC, ZeroPageX, Exact(4)), // 0x75
+    (ROR, ZeroPageX, Exact(6)), // 0x76
+    (RMB, ZeroPage, Exact(5)), // 0x77
     (SEI, Implied, Exact(2)), // 0x78
-    (None, Implied, Exact(0)), // 0x79
-    (None, Implied, Exact(0)), // 0x7a
-    (None, Implied, Exact(0)), // 0x7b
-    (None, Implied, Exact(0)), // 0x7c
-    (None, Implied, Exact(0)), // 0x7d
-    (None, Implied, Exact(0)), // 0x7e
-    (None, Implied, Exact(0)), // 0x7f
-    (None, Implied, Exact(0)), // 0x80
+    (ADC, AbsoluteY, PageBoundary(4)), // 0x79
+    (PLY, Implied, Exact(4)), // 0x7a
+    (NOP, Implied, Exact(1)), // 0x7b
+    (JMP, AbsoluteIndirectX, Exact(6)), // 0x7c
+    (ADC, AbsoluteX, PageBoundary(4)), // 0x7d
+    (ROR, AbsoluteX, PageBoundary(6)), // 0x7e
+    (BBR, ZeroPageRelative, Exact(5)), // 0x7f
+    (BRA, Relative, Branching), // 0x80
     (STA, IndirectX, Exact(6)), // 0x81
-    (None, Implied, Exact(0)), // 0x82
-    (None, Implied, Exact(0)), // 0x83
+    (NOP, Immediate, Exact(2)), // 0x82
+    (NOP, Implied, Exact(1)), // 0x83
     (STY, ZeroPage, Exact(3)), // 0x84
     (STA, ZeroPage, Exact(3)), // 0x85
     (STX, ZeroPage, Exact(3)), // 0x86
-    (None, Implied, Exact(0)), // 0x87
+    (SMB, ZeroPage, Exact(5)), // 0x87
     (DEY, Implied, Exact(2)), // 0x88
-    (None, Implied, Exact(0)), // 0x89
+    (BIT, Immediate, Exact(2)), // 0x89
     (TXA, Implied, Exact(2)), // 0x8a
-    (None, Implied, Exact(0)), // 0x8b
+    (NOP, Implied, Exact(1)), // 0x8b
     (STY, Absolute, Exact(4)), // 0x8c
     (STA, Absolute, Exact(4)), // 0x8d
     (STX, Absolute, Exact(4)), // 0x8e
-    (None, Implied, Exact(0)), // 0x8f
+    (BBS, ZeroPageRelative, Exact(5)), // 0x8f
     (BCC, Relative, Branching), // 0x90
     (STA, IndirectY, Exact(6)), // 0x91
-    (None, Implied, Exact(0)), // 0x92
-    (None, Implied, Exact(0)), // 0x93
+    (STA, ZeroPageIndirect, Exact(5)), // 0x92
+    (NOP, Implied, Exact(1)), // 0x93
     (STY, ZeroPageX, Exact(4)), // 0x94
     (STA, ZeroPageX, Exact(4)), // 0x95
     (STX, ZeroPageY, Exact(4)), // 0x96
-    (None, Implied, Exact(0)), // 0x97
+    (SMB, ZeroPage, Exact(5)), // 0x97
     (TYA, Implied, Exact(2)), // 0x98
     (STA, AbsoluteY, Exact(5)), // 0x99
     (TXS, Implied, Exact(2)), // 0x9a
-    (None, Implied, Exact(0)), // 0x9b
-    (None, Implied, Exact(0)), // 0x9c
+    (NOP, Implied, Exact(1)), // 0x9b
+    (STZ, Absolute, Exact(4)), // 0x9c
     (STA, AbsoluteX, Exact(5)), // 0x9d
-    (None, Implied, Exact(0)), // 0x9e
-    (None, Implied, Exact(0)), // 0x9f
+    (STZ, AbsoluteX, Exact(5)), // 0x9e
+    (BBS, ZeroPageRelative, Exact(5)), // 0x9f
     (LDY, Immediate, Exact(2)), // 0xa0
     (LDA, IndirectX, Exact(6)), // 0xa1
     (LDX, Immediate, Exact(2)), // 0xa2
-    (None, Implied, Exact(0)), // 0xa3
+    (NOP, Implied, Exact(1)), // 0xa3
     (LDY, ZeroPage, Exact(3)), // 0xa4
     (LDA, ZeroPage, Exact(3)), // 0xa5
     (LDX, ZeroPage, Exact(3)), // 0xa6
-    (None, Implied, Exact(0)), // 0xa7
+    (SMB, ZeroPage, Exact(5)), // 0xa7
     (TAY, Implied, Exact(2)), // 0xa8
     (LDA, Immediate, Exact(2)), // 0xa9
     (TAX, Implied, Exact(2)), // 0xaa
-    (None, Implied, Exact(0)), // 0xab
+    (NOP, Implied, Exact(1)), // 0xab
     (LDY, Absolute, Exact(4)), // 0xac
     (LDA, Absolute, Exact(4)), // 0xad
     (LDX, Absolute, Exact(4)), // 0xae
-    (None, Implied, Exact(0)), // 0xaf
+    (BBS, ZeroPageRelative, Exact(5)), // 0xaf
     (BCS, Relative, Branching), // 0xb0
     (LDA, IndirectY, PageBoundary(5)), // 0xb1
-    (None, Implied, Exact(0)), // 0xb2
-    (None, Implied, Exact(0)), // 0xb3
+    (LDA, ZeroPageIndirect, Exact(5)), // 0xb2
+    (NOP, Implied, Exact(1)), // 0xb3
     (LDY, ZeroPageX, Exact(4)), // 0xb4
     (LDA, ZeroPageX, Exact(4)), // 0xb5
     (LDX, ZeroPageY, Exact(4)), // 0xb6
-    (None, Implied, Exact(0)), // 0xb7
+    (SMB, ZeroPage, Exact(5)), // 0xb7
     (CLV, Implied, Exact(2)), // 0xb8
     (LDA, AbsoluteY, PageBoundary(4)), // 0xb9
     (TSX, Implied, Exact(2)), // 0xba
-    (None, Implied, Exact(0)), // 0xbb
+    (NOP, Implied, Exact(1)), // 0xbb
     (LDY, AbsoluteX, PageBoundary(4)), // 0xbc
     (LDA, AbsoluteX, PageBoundary(4)), // 0xbd
     (LDX, AbsoluteY, PageBoundary(4)), // 0xbe
-    (None, Implied, Exact(0)), // 0xbf
+    (BBS, ZeroPageRelative, Exact(5)), // 0xbf
     (CPY, Immediate, Exact(2)), // 0xc0
     (CMP, IndirectX, Exact(6)), // 0xc1
-    (None, Implied, Exact(0)), // 0xc2
-    (None, Implied, Exact(0)), // 0xc3
+    (NOP, Immediate, Exact(2)), // 0xc2
+    (NOP, Implied, Exact(1)), // 0xc3
     (CPY, ZeroPage, Exact(3)), // 0xc4
     (CMP, ZeroPage, Exact(3)), // 0xc5
     (DEC, ZeroPage, Exact(5)), // 0xc6
-    (None, Implied, Exact(0)), // 0xc7
+    (SMB, ZeroPage, Exact(5)), // 0xc7
     (INY, Implied, Exact(2)), // 0xc8
     (CMP, Immediate, Exact(2)), // 0xc9
     (DEX, Implied, Exact(2)), // 0xca
-    (None, Implied, Exact(0)), // 0xcb
+    (WAI, Implied, Exact(3)), // 0xcb
     (CPY, Absolute, Exact(4)), // 0xcc
     (CMP, Absolute, Exact(4)), // 0xcd
     (DEC, Absolute, Exact(6)), // 0xce
-    (None, Implied, Exact(0)), // 0xcf
+    (BBS, ZeroPageRelative, Exact(5)), // 0xcf
     (BNE, Relative, Branching), // 0xd0
     (CMP, IndirectY, PageBoundary(5)), // 0xd1
-    (None, Implied, Exact(0)), // 0xd2
-    (None, Implied, Exact(0)), // 0xd3
-    (None, Implied, Exact(0)), // 0xd4
+    (CMP, ZeroPageIndirect, Exact(5)), // 0xd2
+    (NOP, Implied, Exact(1)), // 0xd3
+    (NOP, ZeroPageX, Exact(4)), // 0xd4
     (CMP, ZeroPageX, Exact(4)), // 0xd5
     (DEC, ZeroPageX, Exact(6)), // 0xd6
-    (None, Implied, Exact(0)), // 0xd7
+    (SMB, ZeroPage, Exact(5)), // 0xd7
     (CLD, Implied, Exact(2)), // 0xd8
     (CMP, AbsoluteY, PageBoundary(4)), // 0xd9
-    (None, Implied, Exact(0)), // 0xda
-    (None, Implied, Exact(0)), // 0xdb
-    (None, Implied, Exact(0)), // 0xdc
+    (PHX, Implied, Exact(3)), // 0xda
+    (STP, Implied, Exact(3)), // 0xdb
+    (NOP, Absolute, Exact(4)), // 0xdc
     (CMP, AbsoluteX, PageBoundary(4)), // 0xdd
     (DEC, AbsoluteX, Exact(7)), // 0xde
-    (None, Implied, Exact(0)), // 0xdf
+    (BBS, ZeroPageRelative, Exact(5)), // 0xdf
     (CPX, Immediate, Exact(2)), // 0xe0
-    (None, Implied, Exact(0)), // 0xe1
-    (None, Implied, Exact(0)), // 0xe2
-    (None, Implied, Exact(0)), // 0xe3
+    (SBC, IndirectX, Exact(6)), // 0xe1
+    (NOP, Immediate, Exact(2)), // 0xe2
+    (NOP, Implied, Exact(1)), // 0xe3
     (CPX, ZeroPage, Exact(3)), // 0xe4
-    (None, Implied, Exact(0)), // 0xe5
+    (SBC, ZeroPage, Exact(3)), // 0xe5
     (INC, ZeroPage, Exact(5)), // 0xe6
-    (None, Implied, Exact(0)), // 0xe7
+    (SMB, ZeroPage, Exact(5)), // 0xe7
     (INX, Implied, Exact(2)), // 0xe8
-    (None, Implied, Exact(0)), // 0xe9
+    (SBC, Immediate, Exact(2)), // 0xe9
     (NOP, Implied, Exact(2)), // 0xea
-    (None, Implied, Exact(0)), // 0xeb
+    (NOP, Implied, Exact(1)), // 0xeb
     (CPX, Absolute, Exact(4)), // 0xec
-    (None, Implied, Exact(0)), // 0xed
+    (SBC, Absolute, Exact(4)), // 0xed
     (INC, Absolute, Exact(6)), // 0xee
-    (None, Implied, Exact(0)), // 0xef
+    (BBS, ZeroPageRelative, Exact(5)), // 0xef
     (BEQ, Relative, Branching), // 0xf0
-    (None, Implied, Exact(0)), // 0xf1
-    (None, Implied, Exact(0)), // 0xf2
-    (None, Implied, Exact(0)), // 0xf3
-    (None, Implied, Exact(0)), // 0xf4
-    (None, Implied, Exact(0)), // 0xf5
+    (SBC, IndirectY, PageBoundary(5)), // 0xf1
+    (SBC, ZeroPageIndirect, Exact(5)), // 0xf2
+    (NOP, Implied, Exact(1)), // 0xf3
+    (NOP, ZeroPageX, Exact(4)), // 0xf4
+    (SBC, ZeroPageX, Exact(4)), // 0xf5
     (INC, ZeroPageX, Exact(6)), // 0xf6
-    (None, Implied, Exact(0)), // 0xf7
+    (SMB, ZeroPage, Exact(5)), // 0xf7
     (SED, Implied, Exact(2)), // 0xf8
-    (None, Implied, Exact(0)), // 0xf9
-    (None, Implied, Exact(0)), // 0xfa
-    (None, Implied, Exact(0)), // 0xfb
-    (None, Implied, Exact(0)), // 0xfc
-    (None, Implied, Exact(0)), // 0xfd
+    (SBC, AbsoluteY, PageBoundary(4)), // 0xf9
+    (PLX, Implied, Exact(4)), // 0xfa
+    (NOP, Implied, Exact(1)), // 0xfb
+    (NOP, Absolute, Exact(4)), // 0xfc
+    (SBC, AbsoluteX, PageBoundary(4)), // 0xfd
     (INC, AbsoluteX, Exact(7)), // 0xfe
-    (None, Implied, Exact(0)), // 0xff
+    (BBS, ZeroPageRelative, Exact(5)), // 0xff
 ];
@@ -0,0 +1,25 @@
+#![allow(clippy::upper_case_acronyms)]
+
+pub mod assembler;
+pub mod breakpoints;
+pub mod bus;
+pub mod cpu;
+pub mod debugger;
+pub mod device;
+pub mod disassembler;
+mod expr;
+pub mod instructions;
+pub mod memory_map;
+pub mod state;
+
+pub use crate::assembler::{Assembler, AssemblyError, Program, assemble};
+pub use crate::breakpoints::{Access, Breakpoint, BreakpointId, BreakpointKind, Breakpoints, Condition, ConditionError};
+pub use crate::bus::{Bus, Ram};
+pub use crate::cpu::{Cpu, Registers, Flags, Variant, ExecutionError, StepInfo, NMI_VECTOR, RESET_VECTOR, IRQ_VECTOR};
+pub use crate::cpu::{CpuState, RunSummary, StopConditions, StopReason};
+pub use crate::debugger::{Command, Debugger, DebuggerError};
+pub use crate::device::Device;
+pub use crate::disassembler::{Disassembler, disassemble, disassemble_range};
+pub use crate::instructions::{Instruction, AddressingMode, Cycles, OPCODES, OPCODES_65C02};
+pub use crate::memory_map::{MemoryMap, MemoryMapBuilder, MemoryMapError, RomWrites};
+pub use crate::state::{StateError, StateReader, StateWriter};
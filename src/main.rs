@@ -1,11 +1,105 @@
-mod cpu;
-mod instructions;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::process;
+use mos6502::{assemble, Command, Cpu, Debugger, StopConditions};
+use mos6502::assembler::DEFAULT_ORIGIN;
 
-use crate::cpu::*;
+const PROGRAM: &str = "
+    jmp (target)
+target:
+    .word $2012
+";
+
+const USAGE: &str = "usage: 6502 [debug [program.asm | program.bin]]";
 
 fn main() {
-    let mut cpu = CPU::new();
-    cpu.load_at(0x600, &[0x6c, 0x03, 0x06, 0x12, 0x20]);
-    cpu.execute();
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        [] => run_demo(),
+        ["debug"] => debug(None),
+        ["debug", path] => debug(Some(path)),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+fn run_demo() {
+    let program = assemble(PROGRAM).unwrap_or_else(|e| panic!("{}", e));
+    let mut cpu = Cpu::new();
+    program.load_into(&mut cpu);
+    for segment in &program.segments {
+        let end = segment.origin + segment.bytes.len() as u16 - 1;
+        for line in cpu.disassembler().disassemble_range(cpu.bus(), segment.origin..=end) {
+            println!("{}", line);
+        }
+    }
+
+    let summary = cpu.run_until_brk();
+    println!("{:?} after {} instructions, {} cycles", summary.stop, summary.instructions, summary.cycles);
     cpu.print();
-}
\ No newline at end of file
+}
+
+// Assembly sources are assembled, anything else is loaded as a raw binary at $0600.
+// Without a file the demo program is used
+fn load_program(cpu: &mut Cpu, path: Option<&str>) -> Result<(), String> {
+    let source = match path {
+        None => String::from(PROGRAM),
+        Some(path) if path.ends_with(".asm") || path.ends_with(".s") =>
+            fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?,
+        Some(path) => {
+            let binary = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+            cpu.load_at(DEFAULT_ORIGIN as usize, &binary);
+            cpu.set_pc(DEFAULT_ORIGIN);
+            return Ok(());
+        }
+    };
+
+    let program = assemble(&source).map_err(|e| e.to_string())?;
+    program.load_into(cpu);
+    cpu.set_pc(program.segments.first().map_or(DEFAULT_ORIGIN, |segment| segment.origin));
+    Ok(())
+}
+
+fn debug(path: Option<&str>) {
+    let mut cpu = Cpu::new();
+    if let Err(e) = load_program(&mut cpu, path) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+    cpu.set_stop_conditions(StopConditions { brk: true, trap: true });
+
+    let mut debugger = Debugger::new();
+    print!("{}", debugger.location(&cpu));
+
+    let mut last: Option<Command> = None;
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        print!("(6502) ");
+        let _ = io::stdout().flush();
+        let Some(Ok(line)) = lines.next() else { break; };
+
+        let command = if line.trim().is_empty() {
+            match &last {
+                Some(command) => command.clone(),
+                None => continue
+            }
+        } else {
+            match Command::parse(&line) {
+                Ok(command) => command,
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
+                }
+            }
+        };
+
+        if command == Command::Quit {
+            break;
+        }
+        print!("{}", debugger.run_command(&mut cpu, &command));
+        last = Some(command);
+    }
+}
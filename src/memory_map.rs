@@ -0,0 +1,299 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use crate::bus::Bus;
+use crate::device::Device;
+use crate::state::{StateError, StateReader, StateWriter};
+
+// What happens when the CPU writes into a ROM region
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RomWrites {
+    Ignore,
+    Record // Keep the write around so it can be inspected with take_rejected_writes
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MemoryMapError {
+    Overlap { first: RangeInclusive<u16>, second: RangeInclusive<u16> },
+    EmptyBacking { range: RangeInclusive<u16> },
+    RomTooLarge { range: RangeInclusive<u16>, size: usize }
+}
+
+impl fmt::Display for MemoryMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryMapError::Overlap { first, second } =>
+                write!(f, "region 0x{:04x}-0x{:04x} overlaps region 0x{:04x}-0x{:04x}",
+                       second.start(), second.end(), first.start(), first.end()),
+            MemoryMapError::EmptyBacking { range } =>
+                write!(f, "region 0x{:04x}-0x{:04x} has no memory behind it", range.start(), range.end()),
+            MemoryMapError::RomTooLarge { range, size } =>
+                write!(f, "ROM image of {} bytes doesn't fit in region 0x{:04x}-0x{:04x}", size, range.start(), range.end())
+        }
+    }
+}
+
+impl std::error::Error for MemoryMapError {}
+
+enum Backing {
+    Ram(Vec<u8>),
+    Rom(Vec<u8>),
+    OpenBus,
+    Device(Box<dyn Device>)
+}
+
+impl Backing {
+    // Stored with every region so a state can't be loaded into a differently built map
+    fn kind(&self) -> u8 {
+        match self {
+            Backing::Ram(_) => 0,
+            Backing::Rom(_) => 1,
+            Backing::OpenBus => 2,
+            Backing::Device(_) => 3
+        }
+    }
+}
+
+struct Region {
+    range: RangeInclusive<u16>,
+    backing: Backing
+}
+
+impl Region {
+    // Backing memory smaller than the region is mirrored over the whole range
+    fn offset(&self, addr: u16, len: usize) -> usize {
+        (addr - self.range.start()) as usize % len
+    }
+}
+
+const UNMAPPED: u16 = u16::MAX;
+
+// Bus built out of address ranges. Addresses that aren't covered by any region behave as open bus
+pub struct MemoryMap {
+    regions: Vec<Region>,
+    lookup: Vec<u16>, // Index into regions for every address
+    data_bus: u8, // Last value that was read or written
+    rom_writes: RomWrites,
+    rejected_writes: Vec<(u16, u8)>
+}
+
+impl MemoryMap {
+    pub fn builder() -> MemoryMapBuilder {
+        MemoryMapBuilder::new()
+    }
+
+    pub fn take_rejected_writes(&mut self) -> Vec<(u16, u8)> {
+        std::mem::take(&mut self.rejected_writes)
+    }
+
+    fn devices(&self) -> impl Iterator<Item = &Box<dyn Device>> {
+        self.regions.iter().filter_map(|region| match &region.backing {
+            Backing::Device(device) => Some(device),
+            _ => None
+        })
+    }
+
+    fn devices_mut(&mut self) -> impl Iterator<Item = &mut Box<dyn Device>> {
+        self.regions.iter_mut().filter_map(|region| match &mut region.backing {
+            Backing::Device(device) => Some(device),
+            _ => None
+        })
+    }
+
+    fn region(&self, addr: u16) -> Option<&Region> {
+        match self.lookup[addr as usize] {
+            UNMAPPED => None,
+            i => Some(&self.regions[i as usize])
+        }
+    }
+}
+
+impl Bus for MemoryMap {
+    fn read(&mut self, addr: u16) -> u8 {
+        let i = self.lookup[addr as usize];
+        self.data_bus = match self.regions.get_mut(i as usize) {
+            Some(Region { range, backing: Backing::Device(device) }) => device.read(addr - range.start()),
+            _ => self.peek(addr)
+        };
+        self.data_bus
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        self.data_bus = val;
+        let i = self.lookup[addr as usize];
+        if i == UNMAPPED {
+            return;
+        }
+
+        let region = &mut self.regions[i as usize];
+        let start = *region.range.start();
+        match &mut region.backing {
+            Backing::Ram(bytes) => {
+                let len = bytes.len();
+                bytes[(addr - start) as usize % len] = val;
+            }
+            Backing::Rom(_) => {
+                if self.rom_writes == RomWrites::Record {
+                    self.rejected_writes.push((addr, val));
+                }
+            }
+            Backing::OpenBus => {}
+            Backing::Device(device) => device.write(addr - start, val)
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        match self.region(addr) {
+            Some(region) => match &region.backing {
+                Backing::Ram(bytes) | Backing::Rom(bytes) => bytes[region.offset(addr, bytes.len())],
+                Backing::OpenBus => self.data_bus,
+                Backing::Device(device) => device.peek(addr - region.range.start())
+            },
+            None => self.data_bus
+        }
+    }
+
+    fn tick(&mut self, cycles: u16) {
+        for device in self.devices_mut() {
+            device.tick(cycles);
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.devices().any(|device| device.irq())
+    }
+
+    fn nmi(&self) -> bool {
+        self.devices().any(|device| device.nmi())
+    }
+
+    // ROM contents aren't saved, they come with the map
+    fn save_state(&self, out: &mut StateWriter) {
+        out.u8(self.data_bus);
+        out.u16(self.regions.len() as u16);
+        for region in &self.regions {
+            out.u8(region.backing.kind());
+            match &region.backing {
+                Backing::Ram(bytes) => out.bytes(bytes),
+                Backing::Device(device) => {
+                    let mut device_state = StateWriter::new();
+                    device.save_state(&mut device_state);
+                    out.bytes(&device_state.into_bytes());
+                }
+                Backing::Rom(_) | Backing::OpenBus => {}
+            }
+        }
+    }
+
+    fn load_state(&mut self, data: &mut StateReader) -> Result<(), StateError> {
+        let data_bus = data.u8()?;
+        if data.u16()? as usize != self.regions.len() {
+            return Err(StateError::Mismatch("memory map"));
+        }
+        for region in &mut self.regions {
+            if data.u8()? != region.backing.kind() {
+                return Err(StateError::Mismatch("memory map"));
+            }
+            match &mut region.backing {
+                Backing::Ram(bytes) => {
+                    let saved = data.bytes()?;
+                    if saved.len() != bytes.len() {
+                        return Err(StateError::Mismatch("memory map"));
+                    }
+                    bytes.copy_from_slice(saved);
+                }
+                Backing::Device(device) => {
+                    let mut device_state = StateReader::new(data.bytes()?);
+                    device.load_state(&mut device_state)?;
+                    device_state.finish()?;
+                }
+                Backing::Rom(_) | Backing::OpenBus => {}
+            }
+        }
+        self.data_bus = data_bus;
+        Ok(())
+    }
+}
+
+pub struct MemoryMapBuilder {
+    regions: Vec<Region>,
+    rom_writes: RomWrites
+}
+
+impl MemoryMapBuilder {
+    pub fn new() -> MemoryMapBuilder {
+        MemoryMapBuilder { regions: vec![], rom_writes: RomWrites::Ignore }
+    }
+
+    pub fn ram(self, range: RangeInclusive<u16>) -> MemoryMapBuilder {
+        let size = (*range.end() as usize + 1).saturating_sub(*range.start() as usize);
+        self.mirrored_ram(range, size)
+    }
+
+    // `size` bytes of RAM repeated over the whole range
+    pub fn mirrored_ram(self, range: RangeInclusive<u16>, size: usize) -> MemoryMapBuilder {
+        self.region(range, Backing::Ram(vec![0; size]))
+    }
+
+    // Images that run past $ffff are cut off at the end of the address space, which build reports as an error
+    pub fn rom(self, start: u16, data: &[u8]) -> MemoryMapBuilder {
+        let end = (start as usize + data.len().saturating_sub(1)).min(0xffff) as u16;
+        self.mirrored_rom(start..=end, data)
+    }
+
+    // ROM image repeated over the whole range
+    pub fn mirrored_rom(self, range: RangeInclusive<u16>, data: &[u8]) -> MemoryMapBuilder {
+        self.region(range, Backing::Rom(data.to_vec()))
+    }
+
+    // Reads return whatever was last seen on the data bus, writes go nowhere
+    pub fn open_bus(self, range: RangeInclusive<u16>) -> MemoryMapBuilder {
+        self.region(range, Backing::OpenBus)
+    }
+
+    // Hands the whole range over to a device. Keep an Rc<RefCell<_>> to the device to inspect it later
+    pub fn device(self, range: RangeInclusive<u16>, device: impl Device + 'static) -> MemoryMapBuilder {
+        self.region(range, Backing::Device(Box::new(device)))
+    }
+
+    pub fn rom_writes(mut self, rom_writes: RomWrites) -> MemoryMapBuilder {
+        self.rom_writes = rom_writes;
+        self
+    }
+
+    fn region(mut self, range: RangeInclusive<u16>, backing: Backing) -> MemoryMapBuilder {
+        self.regions.push(Region { range, backing });
+        self
+    }
+
+    pub fn build(self) -> Result<MemoryMap, MemoryMapError> {
+        let mut lookup = vec![UNMAPPED; 0x10000];
+        for (i, region) in self.regions.iter().enumerate() {
+            match &region.backing {
+                Backing::Ram(bytes) | Backing::Rom(bytes) if bytes.is_empty() =>
+                    return Err(MemoryMapError::EmptyBacking { range: region.range.clone() }),
+                Backing::Rom(bytes) if bytes.len() > region.range.clone().count() =>
+                    return Err(MemoryMapError::RomTooLarge { range: region.range.clone(), size: bytes.len() }),
+                _ => {}
+            }
+
+            for addr in region.range.clone() {
+                let other = lookup[addr as usize];
+                if other != UNMAPPED {
+                    let first = self.regions[other as usize].range.clone();
+                    return Err(MemoryMapError::Overlap { first, second: region.range.clone() });
+                }
+                lookup[addr as usize] = i as u16;
+            }
+        }
+
+        Ok(MemoryMap { regions: self.regions, lookup, data_bus: 0, rom_writes: self.rom_writes, rejected_writes: vec![] })
+    }
+}
+
+impl Default for MemoryMapBuilder {
+    fn default() -> Self {
+        MemoryMapBuilder::new()
+    }
+}
+
+mod test;
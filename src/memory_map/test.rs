@@ -0,0 +1,143 @@
+#![allow(clippy::module_inception)]
+
+#[cfg(test)]
+mod test {
+    use super::super::*;
+    use crate::cpu::{Cpu, Variant};
+
+    #[test]
+    fn test_ram() {
+        let mut map = MemoryMap::builder().ram(0x0000..=0x07ff).build().unwrap();
+        map.write(0x0000, 0x11);
+        map.write(0x07ff, 0x22);
+        assert_eq!(map.read(0x0000), 0x11);
+        assert_eq!(map.read(0x07ff), 0x22);
+    }
+
+    #[test]
+    fn test_full_ram() {
+        let mut map = MemoryMap::builder().ram(0x0000..=0xffff).build().unwrap();
+        map.write(0xffff, 0x33);
+        assert_eq!(map.read(0xffff), 0x33);
+    }
+
+    #[test]
+    fn test_mirrored_ram() {
+        // 2 KiB mirrored 4 times, like the NES
+        let mut map = MemoryMap::builder().mirrored_ram(0x0000..=0x1fff, 0x800).build().unwrap();
+        map.write(0x0012, 0x44);
+        assert_eq!(map.read(0x0812), 0x44);
+        assert_eq!(map.read(0x1012), 0x44);
+        assert_eq!(map.read(0x1812), 0x44);
+        map.write(0x1fff, 0x55);
+        assert_eq!(map.read(0x07ff), 0x55);
+    }
+
+    #[test]
+    fn test_rom() {
+        let mut map = MemoryMap::builder()
+            .rom(0xfffc, &[0x00, 0x80, 0x11, 0x22])
+            .build().unwrap();
+        assert_eq!(map.read(0xfffc), 0x00);
+        assert_eq!(map.read(0xfffd), 0x80);
+        map.write(0xfffe, 0xff);
+        assert_eq!(map.read(0xfffe), 0x11);
+        assert_eq!(map.take_rejected_writes(), vec![]);
+    }
+
+    #[test]
+    fn test_rom_writes_recorded() {
+        let mut map = MemoryMap::builder()
+            .rom(0x8000, &[0xea; 0x10])
+            .rom_writes(RomWrites::Record)
+            .build().unwrap();
+        map.write(0x8001, 0x12);
+        map.write(0x8002, 0x34);
+        assert_eq!(map.read(0x8001), 0xea);
+        assert_eq!(map.take_rejected_writes(), vec![(0x8001, 0x12), (0x8002, 0x34)]);
+        assert_eq!(map.take_rejected_writes(), vec![]);
+    }
+
+    #[test]
+    fn test_mirrored_rom() {
+        // 16 KiB cartridge mirrored into both halves of $8000-$ffff
+        let mut data = vec![0; 0x4000];
+        data[0x0000] = 0x12;
+        data[0x3fff] = 0x34;
+        let mut map = MemoryMap::builder().mirrored_rom(0x8000..=0xffff, &data).build().unwrap();
+        assert_eq!(map.read(0x8000), 0x12);
+        assert_eq!(map.read(0xc000), 0x12);
+        assert_eq!(map.read(0xbfff), 0x34);
+        assert_eq!(map.read(0xffff), 0x34);
+    }
+
+    #[test]
+    fn test_open_bus() {
+        let mut map = MemoryMap::builder()
+            .ram(0x0000..=0x00ff)
+            .open_bus(0x4000..=0x401f)
+            .build().unwrap();
+        map.write(0x0010, 0x5a);
+        assert_eq!(map.read(0x4000), 0x5a);
+        map.write(0x4001, 0x77);
+        assert_eq!(map.read(0x4001), 0x77);
+        assert_eq!(map.read(0x0010), 0x5a);
+        assert_eq!(map.read(0x401f), 0x5a);
+        // unmapped addresses behave the same way
+        assert_eq!(map.read(0x9000), 0x5a);
+        assert_eq!(map.peek(0x9000), 0x5a);
+    }
+
+    #[test]
+    fn test_overlap() {
+        let result = MemoryMap::builder()
+            .ram(0x0000..=0x07ff)
+            .rom(0x0700, &[0; 0x200])
+            .build();
+        assert_eq!(result.err(), Some(MemoryMapError::Overlap { first: 0x0000..=0x07ff, second: 0x0700..=0x08ff }));
+    }
+
+    #[test]
+    fn test_rom_too_large() {
+        let result = MemoryMap::builder().rom(0x0000, &vec![0; 0x10001]).build();
+        assert_eq!(result.err(), Some(MemoryMapError::RomTooLarge { range: 0x0000..=0xffff, size: 0x10001 }));
+        let result = MemoryMap::builder().rom(0xc000, &[0; 0x4001]).build();
+        assert_eq!(result.err(), Some(MemoryMapError::RomTooLarge { range: 0xc000..=0xffff, size: 0x4001 }));
+        let result = MemoryMap::builder().mirrored_rom(0x8000..=0x80ff, &[0; 0x101]).build();
+        assert_eq!(result.err(), Some(MemoryMapError::RomTooLarge { range: 0x8000..=0x80ff, size: 0x101 }));
+
+        // Exactly filling the address space is fine
+        let mut map = MemoryMap::builder().rom(0xc000, &[0x42; 0x4000]).build().unwrap();
+        assert_eq!(map.read(0xffff), 0x42);
+    }
+
+    #[test]
+    fn test_empty_backing() {
+        let result = MemoryMap::builder().mirrored_ram(0x0000..=0x1fff, 0).build();
+        assert_eq!(result.err(), Some(MemoryMapError::EmptyBacking { range: 0x0000..=0x1fff }));
+    }
+
+    #[test]
+    fn test_cpu_with_memory_map() {
+        // reset vector points to $8000:
+        // lda #$42
+        // sta $0800 (mirror of $0000)
+        // sta $8000 (ROM)
+        let mut rom = vec![0; 0x8000];
+        rom[..7].copy_from_slice(&[0xa9, 0x42, 0x8d, 0x00, 0x08, 0x8d, 0x00]);
+        rom[7] = 0x80;
+        rom[0x7ffc] = 0x00;
+        rom[0x7ffd] = 0x80;
+        let map = MemoryMap::builder()
+            .mirrored_ram(0x0000..=0x1fff, 0x800)
+            .rom(0x8000, &rom)
+            .build().unwrap();
+
+        let mut cpu = Cpu::with_bus(map, Variant::NMOS);
+        cpu.reset();
+        assert_eq!(cpu.pc(), 0x8000);
+        cpu.execute().unwrap(); cpu.execute().unwrap(); cpu.execute().unwrap();
+        assert_eq!(cpu.get_byte(0x0000), 0x42);
+        assert_eq!(cpu.get_byte(0x8000), 0xa9);
+    }
+}
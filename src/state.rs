@@ -0,0 +1,130 @@
+use std::fmt;
+
+// Save states start with the magic and a format version, everything after that is little endian
+pub const STATE_MAGIC: [u8; 4] = *b"6502";
+pub const STATE_VERSION: u16 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StateError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    UnexpectedEnd, // Data stops in the middle of a value
+    TrailingData(usize), // Bytes left over after everything was read
+    Invalid(&'static str), // Value that can't be restored, e.g. an unknown variant
+    Mismatch(&'static str) // State was saved from a differently built bus or device
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version) =>
+                write!(f, "save state version {} isn't supported (expected {})", version, STATE_VERSION),
+            StateError::UnexpectedEnd => write!(f, "save state is truncated"),
+            StateError::TrailingData(len) => write!(f, "{} unexpected bytes at the end of the save state", len),
+            StateError::Invalid(what) => write!(f, "invalid {} in save state", what),
+            StateError::Mismatch(what) => write!(f, "save state doesn't match this {}", what)
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+// Appends values to a save state
+#[derive(Default)]
+pub struct StateWriter {
+    bytes: Vec<u8>
+}
+
+impl StateWriter {
+    pub fn new() -> StateWriter {
+        StateWriter { bytes: vec![] }
+    }
+
+    pub fn u8(&mut self, val: u8) {
+        self.bytes.push(val);
+    }
+
+    pub fn bool(&mut self, val: bool) {
+        self.bytes.push(val as u8);
+    }
+
+    pub fn u16(&mut self, val: u16) {
+        self.bytes.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, val: u32) {
+        self.bytes.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, val: u64) {
+        self.bytes.extend_from_slice(&val.to_le_bytes());
+    }
+
+    // Length-prefixed, so the reader knows where the block ends
+    pub fn bytes(&mut self, data: &[u8]) {
+        self.u32(data.len() as u32);
+        self.bytes.extend_from_slice(data);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+// Reads values back in the same order they were written
+pub struct StateReader<'a> {
+    data: &'a [u8]
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> StateReader<'a> {
+        StateReader { data }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() < len {
+            return Err(StateError::UnexpectedEnd);
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Invalid("boolean"))
+        }
+    }
+
+    pub fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8], StateError> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    // Fails if anything is left unread
+    pub fn finish(self) -> Result<(), StateError> {
+        match self.data.len() {
+            0 => Ok(()),
+            len => Err(StateError::TrailingData(len))
+        }
+    }
+}
@@ -0,0 +1,16 @@
+// Every integration test compiles its own copy of this module and not all of them use every helper
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+
+// Fixtures aren't checked in, so the tests that need them are #[ignore]d and run with `cargo test -- --ignored`.
+// Once asked for, a missing fixture is a failure rather than a silent pass
+pub fn fixture(name: &str) -> Vec<u8> {
+    let path = fixture_path(name);
+    fs::read(&path).unwrap_or_else(|e| panic!("{}: {} (see tests/fixtures/README.md)", path.display(), e))
+}
+
+pub fn fixture_path(name: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", name].iter().collect()
+}
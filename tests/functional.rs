@@ -0,0 +1,63 @@
+// Klaus Dormann's 6502 test suites, https://github.com/Klaus2m5/6502_65C02_functional_tests
+mod common;
+
+use mos6502::{Cpu, StopConditions, StopReason, Variant};
+use common::fixture;
+
+// Addresses below match the binaries as they're distributed. Rebuilding the suites with different
+// settings moves them around; the listing files have the new values
+struct Suite {
+    file: &'static str,
+    load_at: usize,
+    start: u16,
+    success: Option<u16>, // Trap the suite ends in when everything passed
+    status: u16 // Functional test: number of the current test. Decimal test: 0 once it passed
+}
+
+const FUNCTIONAL_TEST: Suite = Suite {
+    file: "6502_functional_test.bin",
+    load_at: 0x0000,
+    start: 0x0400,
+    success: Some(0x3469),
+    status: 0x0200
+};
+
+const DECIMAL_TEST: Suite = Suite {
+    file: "6502_decimal_test.bin",
+    load_at: 0x0200,
+    start: 0x0200,
+    success: None, // Ends in BRK either way, the result is in ERROR
+    status: 0x000b
+};
+
+// Runs the suite until it traps or hits BRK. Returns where it stopped and the status byte
+fn run(suite: &Suite) -> (StopReason, u8) {
+    let image = fixture(suite.file);
+    let mut cpu = Cpu::with_variant(Variant::NMOS);
+    cpu.load_at(suite.load_at, &image);
+    cpu.set_pc(suite.start);
+
+    // The functional test executes BRK on purpose, so only the decimal test stops on it
+    cpu.set_stop_conditions(StopConditions { brk: suite.success.is_none(), trap: true });
+    let summary = cpu.run_until(|_| false);
+    (summary.stop, cpu.get_byte(suite.status))
+}
+
+#[test]
+#[ignore = "needs tests/fixtures/6502_functional_test.bin"]
+fn functional_test() {
+    let (stop, test_case) = run(&FUNCTIONAL_TEST);
+    match stop {
+        StopReason::Trap { addr } if Some(addr) == FUNCTIONAL_TEST.success => {}
+        StopReason::Trap { addr } => panic!("test case 0x{:02x} failed, trapped at 0x{:04x}", test_case, addr),
+        stop => panic!("test case 0x{:02x} stopped unexpectedly: {:?}", test_case, stop)
+    }
+}
+
+#[test]
+#[ignore = "needs tests/fixtures/6502_decimal_test.bin"]
+fn decimal_test() {
+    let (stop, error) = run(&DECIMAL_TEST);
+    assert!(matches!(stop, StopReason::Brk { .. } | StopReason::Trap { .. }), "stopped unexpectedly: {:?}", stop);
+    assert_eq!(error, 0, "decimal test failed, stopped with {:?}", stop);
+}
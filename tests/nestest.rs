@@ -0,0 +1,76 @@
+// Runs nestest.nes in automation mode on the 2A03 variant and checks every step against the golden log
+mod common;
+
+use mos6502::{Cpu, Flags, MemoryMap, Registers, Variant};
+use common::fixture;
+
+// Width of "C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD"
+const TRACE_COLUMNS: usize = 73;
+
+struct LogLine {
+    registers: Registers,
+    cycles: Option<u64>
+}
+
+// C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+fn parse_line(line: &str) -> Option<LogLine> {
+    let pc = u16::from_str_radix(line.get(0..4)?, 16).ok()?;
+    let field = |name: &str| {
+        let start = line.find(name)? + name.len();
+        line[start..].split_whitespace().next()
+    };
+    let byte = |name: &str| field(name).and_then(|value| u8::from_str_radix(value, 16).ok());
+
+    let registers = Registers {
+        pc,
+        sp: byte("SP:")?,
+        a: byte("A:")?,
+        x: byte("X:")?,
+        y: byte("Y:")?,
+        status: Flags::from_bits_retain(byte("P:")?)
+    };
+    // Older versions of the log don't have a CPU cycle counter
+    let cycles = field("CYC:").and_then(|value| value.parse().ok());
+
+    Some(LogLine { registers, cycles })
+}
+
+#[test]
+#[ignore = "needs tests/fixtures/nestest.nes and nestest.log"]
+fn nestest_golden_log() {
+    let (rom, log) = (fixture("nestest.nes"), fixture("nestest.log"));
+
+    // iNES header, then a single 16K PRG bank that's mirrored at 0x8000 and 0xc000
+    assert_eq!(&rom[0..4], b"NES\x1a", "nestest.nes is not an iNES file");
+    let prg = &rom[16..16 + 0x4000];
+    let bus = MemoryMap::builder()
+        .mirrored_ram(0x0000..=0x1fff, 0x800)
+        .mirrored_rom(0x2000..=0x401f, &[0xff]) // PPU and APU registers, nestest only cares that they read back 0xff
+        .mirrored_rom(0x8000..=0xffff, prg)
+        .build()
+        .unwrap();
+
+    // Power up leaves SP at 0 and reset moves it down by 3
+    let mut cpu = Cpu::with_bus(bus, Variant::RP2A03);
+    cpu.set_sp(0x00);
+    cpu.reset();
+    cpu.set_pc(0xc000); // automation mode entry point
+
+    let log = String::from_utf8_lossy(&log);
+    for (n, line) in log.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let expected = parse_line(line).unwrap_or_else(|| panic!("can't parse line {}: {}", n + 1, line));
+        assert_eq!(cpu.registers(), expected.registers, "registers differ on line {}: {}", n + 1, line);
+        // Everything up to the PPU column has to match the trace character for character
+        let trace = cpu.trace_line();
+        assert_eq!(&trace[..TRACE_COLUMNS], line.get(..TRACE_COLUMNS).unwrap_or(line), "trace differs on line {}", n + 1);
+        if let Some(cycles) = expected.cycles {
+            assert_eq!(cpu.cycles(), cycles, "cycle count differs on line {}: {}", n + 1, line);
+        }
+
+        cpu.execute().unwrap_or_else(|e| panic!("{} on line {}: {}", e, n + 1, line));
+    }
+
+    // nestest leaves its error codes at 0x02 and 0x03
+    assert_eq!(cpu.get_byte(0x02), 0x00);
+    assert_eq!(cpu.get_byte(0x03), 0x00);
+}
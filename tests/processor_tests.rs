@@ -0,0 +1,203 @@
+// Single step tests in the ProcessorTests JSON format, https://github.com/SingleStepTests/65x02
+// Every file holds thousands of cases for one opcode: registers and RAM before and after the instruction,
+// plus the bus activity of every cycle
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use mos6502::{Bus, Cpu, ExecutionError, Flags, Ram, Registers, Variant};
+use serde_json::Value;
+use common::fixture_path;
+
+// Bit 5 and B don't exist in the status register, only in copies of it pushed to the stack
+const STATUS_MASK: u8 = !0b00110000;
+
+// Only the first few failures of a run get printed in full
+const REPORTED_FAILURES: usize = 20;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Access {
+    Read,
+    Write
+}
+
+// RAM that logs every access the CPU makes, in order
+struct RecordingBus {
+    ram: Ram,
+    log: Vec<(u16, u8, Access)>
+}
+
+impl Bus for RecordingBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        let val = self.ram.read(addr);
+        self.log.push((addr, val, Access::Read));
+        val
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        self.ram.write(addr, val);
+        self.log.push((addr, val, Access::Write));
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.ram.peek(addr)
+    }
+}
+
+struct State {
+    registers: Registers,
+    ram: Vec<(u16, u8)>
+}
+
+fn parse_state(value: &Value) -> Option<State> {
+    let field = |name: &str| value.get(name)?.as_u64();
+    let ram = value.get("ram")?.as_array()?.iter().map(|cell| {
+        Some((cell.get(0)?.as_u64()? as u16, cell.get(1)?.as_u64()? as u8))
+    }).collect::<Option<_>>()?;
+
+    Some(State {
+        registers: Registers {
+            pc: field("pc")? as u16,
+            sp: field("s")? as u8,
+            a: field("a")? as u8,
+            x: field("x")? as u8,
+            y: field("y")? as u8,
+            status: Flags::from_bits_retain(field("p")? as u8)
+        },
+        ram
+    })
+}
+
+fn parse_cycles(value: &Value) -> Option<Vec<(u16, u8, Access)>> {
+    value.as_array()?.iter().map(|cycle| {
+        let access = match cycle.get(2)?.as_str()? {
+            "read" => Access::Read,
+            "write" => Access::Write,
+            _ => return None
+        };
+        Some((cycle.get(0)?.as_u64()? as u16, cycle.get(1)?.as_u64()? as u8, access))
+    }).collect()
+}
+
+// The core isn't cycle stepped and skips the dummy reads and writes the real chip makes, so every access it
+// does make has to show up in the expected cycles in the same order, with the same address, value and direction
+fn diff_bus(expected: &[(u16, u8, Access)], actual: &[(u16, u8, Access)]) -> Option<String> {
+    let mut remaining = expected.iter();
+    for (n, access) in actual.iter().enumerate() {
+        if !remaining.any(|cycle| cycle == access) {
+            return Some(format!("bus access {} doesn't match, expected [{}], got [{}]", n, format_bus(expected), format_bus(actual)));
+        }
+    }
+    None
+}
+
+fn format_bus(cycles: &[(u16, u8, Access)]) -> String {
+    cycles.iter().map(|(addr, val, access)| {
+        let access = if *access == Access::Read { "r" } else { "w" };
+        format!("{} 0x{:04x} 0x{:02x}", access, addr, val)
+    }).collect::<Vec<_>>().join(", ")
+}
+
+// Runs a single case, returns what went wrong. Ok(None) means the case was skipped
+fn run_case(variant: Variant, case: &Value) -> Result<Option<()>, String> {
+    let initial = case.get("initial").and_then(parse_state).ok_or("malformed initial state")?;
+    let expected = case.get("final").and_then(parse_state).ok_or("malformed final state")?;
+    let expected_cycles = case.get("cycles").and_then(parse_cycles).ok_or("malformed cycles")?;
+
+    let mut cpu = Cpu::with_bus(RecordingBus { ram: Ram::new(), log: vec![] }, variant);
+    for (addr, byte) in &initial.ram {
+        cpu.load_at(*addr as usize, &[*byte]);
+    }
+    cpu.set_registers(initial.registers);
+    cpu.bus_mut().log.clear();
+
+    let info = match cpu.execute() {
+        Ok(info) => info,
+        // JAM and STP stop the CPU, there's nothing to compare against
+        Err(ExecutionError::Jam { .. }) | Err(ExecutionError::Stopped { .. }) => return Ok(None),
+        Err(e) => return Err(e.to_string())
+    };
+
+    let mut diffs = vec![];
+    let registers = cpu.registers();
+    let pairs = [
+        ("pc", expected.registers.pc, registers.pc),
+        ("s", expected.registers.sp as u16, registers.sp as u16),
+        ("a", expected.registers.a as u16, registers.a as u16),
+        ("x", expected.registers.x as u16, registers.x as u16),
+        ("y", expected.registers.y as u16, registers.y as u16),
+        ("p", (expected.registers.status.bits() & STATUS_MASK) as u16, (registers.status.bits() & STATUS_MASK) as u16)
+    ];
+    for (name, expected, actual) in pairs {
+        if expected != actual {
+            diffs.push(format!("{}: expected 0x{:02x}, got 0x{:02x}", name, expected, actual));
+        }
+    }
+    for (addr, byte) in &expected.ram {
+        let actual = cpu.bus().peek(*addr);
+        if actual != *byte {
+            diffs.push(format!("[0x{:04x}]: expected 0x{:02x}, got 0x{:02x}", addr, byte, actual));
+        }
+    }
+    if info.cycles as usize != expected_cycles.len() {
+        diffs.push(format!("cycles: expected {}, got {}", expected_cycles.len(), info.cycles));
+    }
+    if let Some(diff) = diff_bus(&expected_cycles, &cpu.bus().log) {
+        diffs.push(diff);
+    }
+
+    if diffs.is_empty() { Ok(Some(())) } else { Err(diffs.join(", ")) }
+}
+
+fn run_directory(dir: &Path, variant: Variant) {
+    let entries = fs::read_dir(dir).unwrap_or_else(|e| panic!("{}: {} (see tests/fixtures/README.md)", dir.display(), e));
+    let mut files: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    assert!(!files.is_empty(), "{}: no test files", dir.display());
+
+    let (mut passed, mut skipped, mut failed) = (0, 0, 0);
+    for file in &files {
+        let text = fs::read_to_string(file).unwrap();
+        let cases: Value = serde_json::from_str(&text).unwrap_or_else(|e| panic!("{}: {}", file.display(), e));
+        for case in cases.as_array().unwrap_or_else(|| panic!("{}: expected an array of cases", file.display())) {
+            match run_case(variant, case) {
+                Ok(Some(())) => passed += 1,
+                Ok(None) => skipped += 1,
+                Err(diff) => {
+                    if failed < REPORTED_FAILURES {
+                        let name = case.get("name").and_then(Value::as_str).unwrap_or("?");
+                        eprintln!("{} \"{}\": {}", file.display(), name, diff);
+                    }
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    eprintln!("{}: {} passed, {} skipped, {} failed", dir.display(), passed, skipped, failed);
+    assert_eq!(failed, 0, "{} of {} cases failed in {}", failed, passed + failed, dir.display());
+}
+
+fn fixture_dir(cpu: &str) -> PathBuf {
+    fixture_path(&format!("ProcessorTests/{}/v1", cpu))
+}
+
+#[test]
+#[ignore = "needs tests/fixtures/ProcessorTests/6502/v1"]
+fn nmos_6502() {
+    run_directory(&fixture_dir("6502"), Variant::NMOS);
+}
+
+#[test]
+#[ignore = "needs tests/fixtures/ProcessorTests/wdc65c02/v1"]
+fn wdc_65c02() {
+    run_directory(&fixture_dir("wdc65c02"), Variant::CMOS);
+}
+
+#[test]
+#[ignore = "needs tests/fixtures/ProcessorTests/nes6502/v1"]
+fn ricoh_2a03() {
+    run_directory(&fixture_dir("nes6502"), Variant::RP2A03);
+}